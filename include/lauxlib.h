@@ -0,0 +1,7 @@
+/* auxiliary functions of the C API, declared with the rest of the API in lua.h */
+#ifndef lauxlib_h
+#define lauxlib_h
+
+#include "lua.h"
+
+#endif
@@ -0,0 +1,214 @@
+/* C API of luaca, modelled on the stack API of Lua 5.1
+ * checked against src/capi.rs by test/header.rs, rewrite it with `LUACA_BLESS=1 cargo test --test header`
+ */
+#ifndef lua_h
+#define lua_h
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct lua_State lua_State;
+
+typedef double lua_Number;
+typedef ptrdiff_t lua_Integer;
+
+typedef int (*lua_CFunction) (lua_State *L);
+typedef void * (*lua_Alloc) (void *ud, void *ptr, size_t osize, size_t nsize);
+
+#define LUA_MULTRET         (-1)
+#define LUA_GLOBALSINDEX    (-10002)
+
+#define LUA_ERRRUN          (2)
+#define LUA_ERRSYNTAX       (3)
+#define LUA_ERRMEM          (4)
+#define LUA_ERRERR          (5)
+
+#define LUA_TNONE           (-1)
+#define LUA_TNIL            (0)
+#define LUA_TBOOLEAN        (1)
+#define LUA_TLIGHTUSERDATA  (2)
+#define LUA_TNUMBER         (3)
+#define LUA_TSTRING         (4)
+#define LUA_TTABLE          (5)
+#define LUA_TFUNCTION       (6)
+#define LUA_TUSERDATA       (7)
+#define LUA_TTHREAD         (8)
+
+/* creates a state with the standard library, the allocator is not used: values are allocated by the rust allocator */
+lua_State *lua_newstate(lua_Alloc f, void *ud);
+
+/* creates a state with the standard library */
+lua_State *luaL_newstate(void);
+
+/* the libraries are opened by lua_newstate, kept for the programs calling it */
+void luaL_openlibs(lua_State *L);
+
+/* calls the finalizers of the userdata and releases the state */
+void lua_close(lua_State *L);
+
+int lua_gettop(lua_State *L);
+
+/* sets the top of the stack, new values are nil */
+void lua_settop(lua_State *L, int idx);
+
+void lua_pushvalue(lua_State *L, int idx);
+
+/* removes the value at the index, the values above it are shifted down */
+void lua_remove(lua_State *L, int idx);
+
+/* moves the top value to the index, the values above it are shifted up */
+void lua_insert(lua_State *L, int idx);
+
+/* pops the top value and stores it at the index */
+void lua_replace(lua_State *L, int idx);
+
+/* the stack grows as needed */
+int lua_checkstack(lua_State *L, int sz);
+
+/* type of the value at the index, LUA_TNONE for an index without a value */
+int lua_type(lua_State *L, int idx);
+
+const char *lua_typename(lua_State *L, int tp);
+
+/* tells if the value is a number or a string convertible to a number */
+int lua_isnumber(lua_State *L, int idx);
+
+/* tells if the value is a string or a number */
+int lua_isstring(lua_State *L, int idx);
+
+int lua_iscfunction(lua_State *L, int idx);
+
+int lua_isuserdata(lua_State *L, int idx);
+
+/* compares two values without calling the __eq handlers, 0 if an index is not valid */
+int lua_rawequal(lua_State *L, int idx1, int idx2);
+
+/* compares two values as the == operator, 0 if an index is not valid */
+int lua_equal(lua_State *L, int idx1, int idx2);
+
+/* compares two values as the < operator, 0 if an index is not valid */
+int lua_lessthan(lua_State *L, int idx1, int idx2);
+
+/* number value of a number or of a string convertible to a number, 0 otherwise */
+lua_Number lua_tonumber(lua_State *L, int idx);
+
+/* number value truncated to an integer, 0 if the value is not a number */
+lua_Integer lua_tointeger(lua_State *L, int idx);
+
+int lua_toboolean(lua_State *L, int idx);
+
+/* returns the string at the index and stores its length in len if it is not null, NULL if the value is not a string or a number
+ * a number is replaced by its string in the stack, the returned string is valid while the value is referenced
+ */
+const char *lua_tolstring(lua_State *L, int idx, size_t *len);
+
+/* length of a string, of the array part of a table or of the block of a userdata */
+size_t lua_objlen(lua_State *L, int idx);
+
+/* address of the block of a userdata created by lua_newuserdata or of a light userdata, NULL otherwise */
+void *lua_touserdata(lua_State *L, int idx);
+
+void lua_pushnil(lua_State *L);
+
+void lua_pushnumber(lua_State *L, lua_Number n);
+
+void lua_pushinteger(lua_State *L, lua_Integer n);
+
+/* pushes a copy of the len bytes at s, they can contain zeros */
+void lua_pushlstring(lua_State *L, const char *s, size_t len);
+
+/* pushes a copy of a NUL terminated string, nil if s is NULL */
+void lua_pushstring(lua_State *L, const char *s);
+
+void lua_pushboolean(lua_State *L, int b);
+
+void lua_pushlightuserdata(lua_State *L, void *p);
+
+/* pops n values and pushes a function calling f with them as its upvalues */
+void lua_pushcclosure(lua_State *L, lua_CFunction f, int n);
+
+/* pops a key and pushes the value of t[key], where t is the value at the index, handlers of __index are called */
+void lua_gettable(lua_State *L, int idx);
+
+/* pushes the value of t[k], where t is the value at the index */
+void lua_getfield(lua_State *L, int idx, const char *k);
+
+/* pops a key and pushes the value of the field of the table at the index, without calling handlers */
+void lua_rawget(lua_State *L, int idx);
+
+/* pushes the value of t[n] without calling handlers, where t is the table at the index */
+void lua_rawgeti(lua_State *L, int idx, int n);
+
+/* pushes a new table with space for narr elements in its array part and nrec other fields */
+void lua_createtable(lua_State *L, int narr, int nrec);
+
+/* pushes a new userdata holding a block of size bytes and returns its address */
+void *lua_newuserdata(lua_State *L, size_t size);
+
+/* pushes the metatable of the value at the index and returns 1, returns 0 and pushes nothing if it has none */
+int lua_getmetatable(lua_State *L, int idx);
+
+/* pops a table or nil and sets it as the metatable of the table or userdata at the index */
+int lua_setmetatable(lua_State *L, int idx);
+
+/* pops a value and a key and does t[key] = value, where t is the value at the index, handlers of __newindex are called */
+void lua_settable(lua_State *L, int idx);
+
+/* pops a value and does t[k] = value, where t is the value at the index */
+void lua_setfield(lua_State *L, int idx, const char *k);
+
+/* pops a value and a key and sets the field of the table at the index, without calling handlers */
+void lua_rawset(lua_State *L, int idx);
+
+/* pops a value and does t[n] = value without calling handlers, where t is the table at the index */
+void lua_rawseti(lua_State *L, int idx, int n);
+
+/* pops a key and pushes the next key and value of the table at the index and returns 1, returns 0 at the end of the traversal */
+int lua_next(lua_State *L, int idx);
+
+/* pops nargs arguments and the function below them, calls it and pushes nresults results
+ * unlike Lua, an error doesn't unwind the calling C function: nresults nils are pushed and the C function goes on,
+ * the error is raised when it returns; use lua_pcall to stop at the error
+ */
+void lua_call(lua_State *L, int nargs, int nresults);
+
+/* calls a function as lua_call in protected mode, returns 0 or LUA_ERRRUN with the error object pushed instead of the results
+ * errfunc is the index of the message handler, 0 for none
+ */
+int lua_pcall(lua_State *L, int nargs, int nresults, int errfunc);
+
+/* pops the error object and raises it when the running C function returns, call it as `return lua_error(L);` */
+int lua_error(lua_State *L);
+
+/* decodes a chunk compiled by luac 5.1 and pushes its main function, returns LUA_ERRSYNTAX with a message pushed if it is invalid */
+int luaL_loadbuffer(lua_State *L, const char *buff, size_t sz, const char *name);
+
+#define lua_upvalueindex(i)     (LUA_GLOBALSINDEX - (i))
+
+#define lua_pop(L, n)           lua_settop(L, -(n) - 1)
+#define lua_newtable(L)         lua_createtable(L, 0, 0)
+#define lua_pushcfunction(L, f) lua_pushcclosure(L, (f), 0)
+#define lua_register(L, n, f)   (lua_pushcfunction(L, (f)), lua_setglobal(L, (n)))
+#define lua_pushliteral(L, s)   lua_pushlstring(L, "" s, (sizeof(s) / sizeof(char)) - 1)
+
+#define lua_isfunction(L, n)      (lua_type(L, (n)) == LUA_TFUNCTION)
+#define lua_istable(L, n)         (lua_type(L, (n)) == LUA_TTABLE)
+#define lua_islightuserdata(L, n) (lua_type(L, (n)) == LUA_TLIGHTUSERDATA)
+#define lua_isnil(L, n)           (lua_type(L, (n)) == LUA_TNIL)
+#define lua_isboolean(L, n)       (lua_type(L, (n)) == LUA_TBOOLEAN)
+#define lua_isthread(L, n)        (lua_type(L, (n)) == LUA_TTHREAD)
+#define lua_isnone(L, n)          (lua_type(L, (n)) == LUA_TNONE)
+#define lua_isnoneornil(L, n)     (lua_type(L, (n)) <= 0)
+
+#define lua_setglobal(L, s)     lua_setfield(L, LUA_GLOBALSINDEX, (s))
+#define lua_getglobal(L, s)     lua_getfield(L, LUA_GLOBALSINDEX, (s))
+#define lua_tostring(L, i)      lua_tolstring(L, (i), NULL)
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
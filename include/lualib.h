@@ -0,0 +1,7 @@
+/* standard libraries of the C API, opened by luaL_newstate and declared in lua.h */
+#ifndef lualib_h
+#define lualib_h
+
+#include "lua.h"
+
+#endif
@@ -0,0 +1,4 @@
+/* static and dynamic libraries of the C API of luaca, for the C programs including include/lua.h
+ * the functions are defined by the module capi of luaca, this crate only links them in the libraries
+ */
+pub use luaca::capi;
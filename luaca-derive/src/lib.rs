@@ -0,0 +1,261 @@
+//! Derive macros binding host types as userdata of luaca
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, FnArg, ImplItem, ItemImpl, LitStr, ReturnType, Type, Visibility};
+
+/// options given by the #[lua(...)] attributes of a struct, a field or a method
+#[derive(Default)]
+struct LuaOptions {
+    // the field or method is not given to the scripts
+    skip     : bool,
+    // the field can't be written by the scripts
+    readonly : bool,
+    // name in the scripts instead of the name in rust
+    name     : Option<String>,
+    // the method is the handler of this event of the metatable
+    meta     : Option<String>,
+    // the method returns a Result whose type is not named Result, as an alias
+    result   : bool
+}
+
+fn is_lua_attribute(attr : &Attribute) -> bool {
+    attr.path().is_ident("lua")
+}
+
+fn parse_options(attrs : &[Attribute]) -> syn::Result<LuaOptions> {
+    let mut options = LuaOptions::default();
+    for attr in attrs.iter().filter(|attr| is_lua_attribute(attr)) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else if meta.path.is_ident("readonly") {
+                options.readonly = true;
+            } else if meta.path.is_ident("name") {
+                options.name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("meta") {
+                options.meta = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("result") {
+                options.result = true;
+            } else {
+                return Err(meta.error("expected skip, readonly, result, name = \"...\" or meta = \"...\""));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+/** Implements LuaUserData for a struct, its public fields and the fields with a #[lua] attribute can be read and written by the scripts
+ * #[lua(name = "...")] renames the type or a field, #[lua(readonly)] only gives a getter and #[lua(skip)] hides a public field
+ * the values of the struct are converted to userdata, the fields are cloned when they are read
+ */
+#[proc_macro_derive(LuaUserData, attributes(lua))]
+pub fn derive_lua_user_data(input : TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive_user_data(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn derive_user_data(input : DeriveInput) -> syn::Result<TokenStream2> {
+    let options = parse_options(&input.attrs)?;
+    let ident = &input.ident;
+    let type_name = options.name.unwrap_or_else(|| ident.to_string());
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => { &data.fields }
+        _ => { return Err(syn::Error::new(ident.span(), "LuaUserData can only be derived for structs")) }
+    };
+
+    let mut registrations = Vec::new();
+    if let Fields::Named(fields) = fields {
+        for field in &fields.named {
+            let options = parse_options(&field.attrs)?;
+            let exposed = matches!(field.vis, Visibility::Public(_)) || field.attrs.iter().any(is_lua_attribute);
+            if options.skip || !exposed {
+                continue;
+            }
+            let field_ident = field.ident.as_ref().expect("named fields have a name");
+            let field_type = &field.ty;
+            let name = options.name.unwrap_or_else(|| field_ident.to_string());
+            registrations.push(quote! {
+                registry.add_field_getter(#name, |_, this : &Self| ::std::result::Result::Ok(::std::clone::Clone::clone(&this.#field_ident)));
+            });
+            if !options.readonly {
+                registrations.push(quote! {
+                    registry.add_field_setter(#name, |_, this : &mut Self, value : #field_type| {
+                        this.#field_ident = value;
+                        ::std::result::Result::Ok(())
+                    });
+                });
+            }
+        }
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::luaca::binding::LuaUserData for #ident #ty_generics #where_clause {
+            const TYPE_NAME : &'static str = #type_name;
+
+            #[allow(unused_variables)]
+            fn add_fields(registry : &mut ::luaca::binding::UserDataRegistry<Self>) {
+                #(#registrations)*
+            }
+        }
+
+        impl #impl_generics ::luaca::conversion::IntoLua for #ident #ty_generics #where_clause {
+            fn into_lua(self, ctx : &::luaca::lua::Context) -> ::std::result::Result<::luaca::interpreter::object::Value, ::luaca::lua::LuaError> {
+                ::std::result::Result::Ok(ctx.create_userdata(self))
+            }
+        }
+    })
+}
+
+/** Implements LuaMethods with the public functions of an impl block and the ones with a #[lua] attribute
+ * functions taking &self or &mut self are methods of the userdata, the other ones are fields of the global table named after the type
+ * an argument &mut Context receives the context of the call, &str is converted from a string,
+ * other references are borrowed from userdata and the other arguments are converted with FromLua
+ * #[lua(meta = "__add")] makes the function a handler of the metatable, #[lua(name = "...")] renames it and #[lua(skip)] hides it
+ * the error of a function returning a type named Result is raised, a function returning an alias of Result,
+ * as LuaResult<T>, needs #[lua(result)] or its Result is returned as a value, which fails to compile
+ */
+#[proc_macro_attribute]
+pub fn lua_methods(_ : TokenStream, item : TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+    bind_methods(item).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// how an argument of a function is given by the call
+enum Argument {
+    Context,
+    Str,
+    Ref(Type),
+    RefMut(Type),
+    Value(Type)
+}
+
+/// tells if the type is a path whose last segment is the given name
+fn type_named(ty : &Type, name : &str) -> bool {
+    match ty {
+        Type::Path(path) => { path.path.segments.last().is_some_and(|segment| segment.ident == name) }
+        _ => { false }
+    }
+}
+
+fn classify(ty : &Type) -> syn::Result<Argument> {
+    match ty {
+        Type::Reference(reference) if type_named(&reference.elem, "Context") => {
+            match reference.mutability {
+                Some(_) => { Ok(Argument::Context) }
+                None => { Err(syn::Error::new_spanned(ty, "the context is given as &mut Context")) }
+            }
+        }
+        Type::Reference(reference) if type_named(&reference.elem, "str") => { Ok(Argument::Str) }
+        Type::Reference(reference) if reference.mutability.is_some() => { Ok(Argument::RefMut((*reference.elem).clone())) }
+        Type::Reference(reference) => { Ok(Argument::Ref((*reference.elem).clone())) }
+        _ => { Ok(Argument::Value(ty.clone())) }
+    }
+}
+
+fn bind_methods(mut item : ItemImpl) -> syn::Result<TokenStream2> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new_spanned(path, "#[lua_methods] applies to inherent impl blocks"));
+    }
+
+    let mut registrations = Vec::new();
+    for impl_item in &mut item.items {
+        let ImplItem::Fn(method) = impl_item else {
+            continue;
+        };
+        let options = parse_options(&method.attrs)?;
+        let exposed = matches!(method.vis, Visibility::Public(_)) || method.attrs.iter().any(is_lua_attribute);
+        method.attrs.retain(|attr| !is_lua_attribute(attr));
+        if options.skip || !exposed {
+            continue;
+        }
+
+        let fn_ident = &method.sig.ident;
+        let mut receiver = None;
+        let mut patterns = Vec::new();
+        let mut types = Vec::new();
+        let mut borrows = Vec::new();
+        let mut call_args = Vec::new();
+        for (i, input) in method.sig.inputs.iter().enumerate() {
+            let pat_type = match input {
+                FnArg::Receiver(r) if r.reference.is_none() => {
+                    return Err(syn::Error::new_spanned(r, "methods of a userdata take &self or &mut self"));
+                }
+                FnArg::Receiver(r) => {
+                    receiver = Some(r.mutability.is_some());
+                    continue;
+                }
+                FnArg::Typed(pat_type) => { pat_type }
+            };
+            let arg = format_ident!("__lua_arg{}", i);
+            match classify(&pat_type.ty)? {
+                Argument::Context => {
+                    call_args.push(quote! { __lua_ctx });
+                    continue;
+                }
+                Argument::Str => {
+                    types.push(quote! { ::std::string::String });
+                    call_args.push(quote! { &#arg });
+                }
+                Argument::Ref(ty) => {
+                    types.push(quote! { ::luaca::binding::UserDataRef<#ty> });
+                    borrows.push(quote! { let #arg = #arg.borrow()?; });
+                    call_args.push(quote! { &#arg });
+                }
+                Argument::RefMut(ty) => {
+                    types.push(quote! { ::luaca::binding::UserDataRef<#ty> });
+                    borrows.push(quote! { let mut #arg = #arg.borrow_mut()?; });
+                    call_args.push(quote! { &mut #arg });
+                }
+                Argument::Value(ty) => {
+                    types.push(quote! { #ty });
+                    call_args.push(quote! { #arg });
+                }
+            }
+            patterns.push(arg);
+        }
+
+        let call = match receiver {
+            Some(_) => { quote! { Self::#fn_ident(__lua_this, #(#call_args),*) } }
+            None => { quote! { Self::#fn_ident(#(#call_args),*) } }
+        };
+        // functions returning a Result raise its error, the other results are always returned
+        let body = match &method.sig.output {
+            ReturnType::Type(_, ty) if options.result || type_named(ty, "Result") => { quote! { #call.map_err(::std::convert::Into::into) } }
+            _ => { quote! { ::std::result::Result::Ok(#call) } }
+        };
+
+        let name = options.meta.clone().or(options.name).unwrap_or_else(|| fn_ident.to_string());
+        let register = match (receiver, options.meta.is_some()) {
+            (Some(false), false) => { quote! { add_method } }
+            (Some(true), false) => { quote! { add_method_mut } }
+            (Some(false), true) => { quote! { add_meta_method } }
+            (Some(true), true) => { quote! { add_meta_method_mut } }
+            (None, false) => { quote! { add_function } }
+            (None, true) => { quote! { add_meta_function } }
+        };
+        let this = receiver.map(|_| quote! { __lua_this, });
+        registrations.push(quote! {
+            registry.#register(#name, |__lua_ctx, #this (#(#patterns,)*) : (#(#types,)*)| {
+                #(#borrows)*
+                #body
+            });
+        });
+    }
+
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    Ok(quote! {
+        #item
+
+        impl #impl_generics ::luaca::binding::LuaMethods for #self_ty #where_clause {
+            #[allow(unused_variables, unused_mut)]
+            fn add_methods(registry : &mut ::luaca::binding::UserDataRegistry<Self>) {
+                #(#registrations)*
+            }
+        }
+    })
+}
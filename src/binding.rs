@@ -0,0 +1,232 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::conversion::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, Values};
+use crate::interpreter::interpreter::InterpreterError;
+use crate::interpreter::object::{LuaStr, TableRef, Value};
+use crate::interpreter::table::Table;
+use crate::interpreter::userdata::UserData;
+use crate::lua::{host_function, Context, LuaError};
+
+pub use luaca_derive::{lua_methods, LuaUserData};
+
+/** Host type whose values are given to the scripts as userdata, implemented by #[derive(LuaUserData)]
+ * the fields added to the registry are read and written by indexing the userdata
+ */
+pub trait LuaUserData : 'static + Sized {
+    /// name of the type in error messages and of the global table holding its associated functions
+    const TYPE_NAME : &'static str;
+
+    fn add_fields(_registry : &mut UserDataRegistry<Self>) {}
+}
+
+/// Methods of a host type given to the scripts, implemented by #[lua_methods] on an impl block
+pub trait LuaMethods : LuaUserData {
+    fn add_methods(_registry : &mut UserDataRegistry<Self>) {}
+}
+
+type Getter<T> = Box<dyn Fn(&mut Context, &T) -> Result<Value, LuaError>>;
+type Setter<T> = Box<dyn Fn(&mut Context, &mut T, Value) -> Result<(), LuaError>>;
+
+/** Fields, methods and metamethods of a host type, turned into the metatable of its userdata
+ * methods and fields are found by the __index handler of the metatable, fields are written by its __newindex handler
+ * associated functions are the fields of a global table named after the type
+ */
+pub struct UserDataRegistry<T> {
+    getters     : HashMap<LuaStr, Getter<T>>,
+    setters     : HashMap<LuaStr, Setter<T>>,
+    methods     : Table,
+    metamethods : Table,
+    functions   : Table
+}
+
+impl<T : LuaUserData> UserDataRegistry<T> {
+
+    pub fn new() -> Self {
+        UserDataRegistry {
+            getters     : HashMap::new(),
+            setters     : HashMap::new(),
+            methods     : Table::new(),
+            metamethods : Table::new(),
+            functions   : Table::new()
+        }
+    }
+
+    /// field read with userdata.name
+    pub fn add_field_getter<R, F>(&mut self, name : &str, getter : F)
+    where R : IntoLua, F : Fn(&mut Context, &T) -> Result<R, LuaError> + 'static {
+        self.getters.insert(LuaStr::from(name.as_bytes()), Box::new(move |ctx, this| getter(ctx, this)?.into_lua(ctx)));
+    }
+
+    /// field written with userdata.name = value, a value that can't be converted raises an error
+    pub fn add_field_setter<A, F>(&mut self, name : &str, setter : F)
+    where A : FromLua, F : Fn(&mut Context, &mut T, A) -> Result<(), LuaError> + 'static {
+        let field = String::from(name);
+        self.setters.insert(LuaStr::from(name.as_bytes()), Box::new(move |ctx, this, value| {
+            let value = A::from_lua(value, ctx)
+                .map_err(|err| LuaError::runtime(format!("invalid value for field '{}' of {} ({})", field, T::TYPE_NAME, err)))?;
+            setter(ctx, this, value)
+        }));
+    }
+
+    /// method called with userdata:name(...), the userdata is borrowed during the call
+    pub fn add_method<A, R, F>(&mut self, name : &str, method : F)
+    where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, &T, A) -> Result<R, LuaError> + 'static {
+        set_field(&mut self.methods, name, method_value(method));
+    }
+
+    /// method changing the userdata, it can't be called again on the same userdata while it runs
+    pub fn add_method_mut<A, R, F>(&mut self, name : &str, method : F)
+    where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, &mut T, A) -> Result<R, LuaError> + 'static {
+        set_field(&mut self.methods, name, method_mut_value(method));
+    }
+
+    /// function called with TypeName.name(...), as the constructors of the type
+    pub fn add_function<A, R, F>(&mut self, name : &str, func : F)
+    where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, A) -> Result<R, LuaError> + 'static {
+        set_field(&mut self.functions, name, function_value(func));
+    }
+
+    /// handler of the metatable whose first argument is the userdata, as __tostring or __len
+    pub fn add_meta_method<A, R, F>(&mut self, name : &str, method : F)
+    where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, &T, A) -> Result<R, LuaError> + 'static {
+        set_field(&mut self.metamethods, name, method_value(method));
+    }
+
+    /// handler of the metatable changing the userdata given as its first argument
+    pub fn add_meta_method_mut<A, R, F>(&mut self, name : &str, method : F)
+    where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, &mut T, A) -> Result<R, LuaError> + 'static {
+        set_field(&mut self.metamethods, name, method_mut_value(method));
+    }
+
+    /// handler of the metatable converting all its arguments, as the operators whose first operand may not be the userdata
+    pub fn add_meta_function<A, R, F>(&mut self, name : &str, func : F)
+    where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, A) -> Result<R, LuaError> + 'static {
+        set_field(&mut self.metamethods, name, function_value(func));
+    }
+
+    /// returns the metatable of the userdata and the table of the associated functions
+    pub fn build(self) -> (TableRef, TableRef) {
+        let methods = Rc::new(RefCell::new(self.methods));
+        let getters = self.getters;
+        let index = host_function(move |ctx, args| {
+            let mut values = Values::new(args);
+            let this = UserDataRef::<T>::from_lua_multi(&mut values, ctx).map_err(|err| values.argument_error(err))?;
+            let key = values.take().unwrap_or_default();
+            let method = methods.borrow().get(&key);
+            if !method.is_nil() {
+                return Ok(vec![method]);
+            }
+            match key.get_string().and_then(|name| getters.get(name)) {
+                Some(getter) => { Ok(vec![getter(ctx, &*this.borrow()?)?]) }
+                None => { Ok(vec![Value::Nil]) }
+            }
+        });
+
+        let setters = self.setters;
+        let new_index = host_function(move |ctx, args| {
+            let mut values = Values::new(args);
+            let this = UserDataRef::<T>::from_lua_multi(&mut values, ctx).map_err(|err| values.argument_error(err))?;
+            let key = values.take().unwrap_or_default();
+            let value = values.take().unwrap_or_default();
+            match key.get_string().and_then(|name| setters.get(name)) {
+                Some(setter) => { setter(ctx, &mut *this.borrow_mut()?, value)?; }
+                None => { return Err(LuaError::runtime(format!("cannot set field '{}' of {}", key, T::TYPE_NAME))) }
+            }
+            Ok(Vec::new())
+        });
+
+        let mut metatable = self.metamethods;
+        set_field(&mut metatable, "__index", index);
+        set_field(&mut metatable, "__newindex", new_index);
+        (Rc::new(RefCell::new(metatable)), Rc::new(RefCell::new(self.functions)))
+    }
+
+}
+
+impl<T : LuaUserData> Default for UserDataRegistry<T> {
+    fn default() -> Self {
+        UserDataRegistry::new()
+    }
+}
+
+/// Userdata holding a value of type T, borrowed by the host functions taking a reference to a T
+pub struct UserDataRef<T> {
+    userdata : Rc<UserData>,
+    marker   : PhantomData<T>
+}
+
+impl<T : LuaUserData> UserDataRef<T> {
+
+    pub fn userdata(&self) -> &Rc<UserData> {
+        &self.userdata
+    }
+
+    /// returns the value, a method changing it can't be running
+    pub fn borrow(&self) -> Result<Ref<'_, T>, LuaError> {
+        self.userdata.borrow::<T>().ok_or_else(|| borrow_error::<T>())
+    }
+
+    /// returns the value to change it, no other method of the userdata can be running
+    pub fn borrow_mut(&self) -> Result<RefMut<'_, T>, LuaError> {
+        self.userdata.borrow_mut::<T>().ok_or_else(|| borrow_error::<T>())
+    }
+
+}
+
+impl<T : LuaUserData> FromLua for UserDataRef<T> {
+    fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+        match value {
+            Value::UserData(userdata) if userdata.is::<T>() => { Ok(UserDataRef { userdata, marker: PhantomData }) }
+            value => { Err(InterpreterError::ConversionError { expected: T::TYPE_NAME, got: value.get_type().to_string() }.into()) }
+        }
+    }
+}
+
+impl<T : LuaUserData> IntoLua for UserDataRef<T> {
+    fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+        Ok(Value::UserData(self.userdata))
+    }
+}
+
+fn borrow_error<T : LuaUserData>() -> LuaError {
+    LuaError::runtime(format!("{} is already borrowed", T::TYPE_NAME))
+}
+
+fn set_field(table : &mut Table, name : &str, val : Value) {
+    table.set(Value::from_string(name), val).expect("field names are strings");
+}
+
+// the userdata is the first argument, the other arguments are counted from 2 as in the call
+fn method_value<T, A, R, F>(method : F) -> Value
+where T : LuaUserData, A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, &T, A) -> Result<R, LuaError> + 'static {
+    host_function(move |ctx, args| {
+        let mut values = Values::new(args);
+        let this = UserDataRef::<T>::from_lua_multi(&mut values, ctx).map_err(|err| values.argument_error(err))?;
+        let args = A::from_lua_multi(&mut values, ctx).map_err(|err| values.argument_error(err))?;
+        let res = method(ctx, &*this.borrow()?, args)?;
+        res.into_lua_multi(ctx)
+    })
+}
+
+fn method_mut_value<T, A, R, F>(method : F) -> Value
+where T : LuaUserData, A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, &mut T, A) -> Result<R, LuaError> + 'static {
+    host_function(move |ctx, args| {
+        let mut values = Values::new(args);
+        let this = UserDataRef::<T>::from_lua_multi(&mut values, ctx).map_err(|err| values.argument_error(err))?;
+        let args = A::from_lua_multi(&mut values, ctx).map_err(|err| values.argument_error(err))?;
+        let res = method(ctx, &mut *this.borrow_mut()?, args)?;
+        res.into_lua_multi(ctx)
+    })
+}
+
+fn function_value<A, R, F>(func : F) -> Value
+where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, A) -> Result<R, LuaError> + 'static {
+    host_function(move |ctx, args| {
+        let mut values = Values::new(args);
+        let args = A::from_lua_multi(&mut values, ctx).map_err(|err| values.argument_error(err))?;
+        func(ctx, args)?.into_lua_multi(ctx)
+    })
+}
@@ -0,0 +1,707 @@
+/* C interface modelled on the stack API of Lua 5.1, declared by the header include/lua.h and built for C programs by luaca-capi
+ * the C functions are given a stack of values, their arguments from index 1 and their results pushed on top
+ * errors can't unwind the C stack: lua_error and the errors of lua_call are raised once the running C function returns,
+ * so lua_error must be called as `return lua_error(L);`
+ */
+#![allow(non_camel_case_types, non_snake_case, clippy::missing_safety_doc)]
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::{process, ptr, slice};
+use std::rc::Rc;
+
+use crate::decompile::decompile::decompile_bytes;
+use crate::interpreter::global_environment::GlobalEnvironment;
+use crate::interpreter::interpreter::{call_value, InterpreterError};
+use crate::interpreter::metatable;
+use crate::interpreter::object::{Closure, LuaStr, Value};
+use crate::interpreter::runtime_library::RuntimeClosure;
+use crate::interpreter::table::Table;
+use crate::interpreter::userdata::run_finalizers;
+use crate::lua::{Lua, LuaError};
+
+pub type lua_Number = f64;
+pub type lua_Integer = isize;
+pub type lua_CFunction = Option<unsafe extern "C" fn(*mut lua_State) -> c_int>;
+pub type lua_Alloc = Option<unsafe extern "C" fn(*mut c_void, *mut c_void, usize, usize) -> *mut c_void>;
+
+pub const LUA_MULTRET : c_int = -1;
+pub const LUA_GLOBALSINDEX : c_int = -10002;
+
+pub const LUA_ERRRUN : c_int = 2;
+pub const LUA_ERRSYNTAX : c_int = 3;
+pub const LUA_ERRMEM : c_int = 4;
+pub const LUA_ERRERR : c_int = 5;
+
+pub const LUA_TNONE : c_int = -1;
+pub const LUA_TNIL : c_int = 0;
+pub const LUA_TBOOLEAN : c_int = 1;
+pub const LUA_TLIGHTUSERDATA : c_int = 2;
+pub const LUA_TNUMBER : c_int = 3;
+pub const LUA_TSTRING : c_int = 4;
+pub const LUA_TTABLE : c_int = 5;
+pub const LUA_TFUNCTION : c_int = 6;
+pub const LUA_TUSERDATA : c_int = 7;
+pub const LUA_TTHREAD : c_int = 8;
+
+/// State given to the C code, it owns an interpreter state and the stack of values of the C functions
+pub struct lua_State {
+    // dropped first so that the finalizers run while the rest of the state is alive
+    lua      : Lua,
+    stack    : Vec<Value>,
+    // first index of the stack seen by the running C function
+    base     : usize,
+    // environment of the running C function, the one of the state when no C function is running
+    env      : *mut GlobalEnvironment,
+    // upvalues of the running C function, seen at the pseudo-indices lua_upvalueindex(i)
+    upvalues : Rc<RefCell<Vec<Value>>>,
+    // number of C functions running
+    calls    : usize,
+    // error raised by the running C function, raised again when it returns
+    error    : Option<LuaError>,
+    // NUL terminated copies of the strings returned by lua_tolstring, kept while the strings are referenced
+    strings  : Vec<(LuaStr, Box<[u8]>)>
+}
+
+/// memory block allocated by lua_newuserdata, aligned for any C type, and the size requested for it
+struct Block(Box<[u64]>, usize);
+
+unsafe fn env<'a>(L : *mut lua_State) -> &'a mut GlobalEnvironment {
+    &mut *(*L).env
+}
+
+unsafe fn push(L : *mut lua_State, val : Value) {
+    (*L).stack.push(val);
+}
+
+unsafe fn pop(L : *mut lua_State) -> Value {
+    let base = (*L).base;
+    let stack = &mut (*L).stack;
+    if stack.len() > base { stack.pop().unwrap_or_default() } else { Value::Nil }
+}
+
+/// position in the stack of an acceptable index, None for pseudo-indices and indices outside of the running function
+unsafe fn position(L : *mut lua_State, idx : c_int) -> Option<usize> {
+    let (base, len) = ((*L).base, (*L).stack.len());
+    if idx > 0 {
+        let position = base + idx as usize - 1;
+        (position < len).then_some(position)
+    } else if idx < 0 && idx > LUA_GLOBALSINDEX {
+        let position = len as isize + idx as isize;
+        (position >= base as isize).then_some(position as usize)
+    } else {
+        None
+    }
+}
+
+/// value at a valid index or pseudo-index, None for the other indices
+unsafe fn get(L : *mut lua_State, idx : c_int) -> Option<Value> {
+    match idx {
+        LUA_GLOBALSINDEX => { Some(Value::Table(env(L).globals())) }
+        i if i < LUA_GLOBALSINDEX => { (*L).upvalues.borrow().get((LUA_GLOBALSINDEX - i - 1) as usize).cloned() }
+        _ => { position(L, idx).map(|p| (&(*L).stack)[p].clone()) }
+    }
+}
+
+/// value at an index, nil for the indices without a value
+unsafe fn value(L : *mut lua_State, idx : c_int) -> Value {
+    get(L, idx).unwrap_or_default()
+}
+
+/* raises an error in the running C function, it is raised again when the function returns
+ * errors outside of a C function and of a protected call end the program, as the default panic function
+ */
+unsafe fn raise(L : *mut lua_State, err : LuaError) {
+    if (*L).calls == 0 {
+        eprintln!("PANIC: unprotected error in call to Lua API ({})", err);
+        process::exit(1);
+    }
+    (*L).error.get_or_insert(err);
+}
+
+/// pushes the results of a call, adjusted to nresults values unless it is LUA_MULTRET
+unsafe fn push_results(L : *mut lua_State, mut results : Vec<Value>, nresults : c_int) {
+    if nresults != LUA_MULTRET {
+        results.resize(nresults.max(0) as usize, Value::Nil);
+    }
+    (*L).stack.extend(results);
+}
+
+/// pops the arguments and the function of a call
+unsafe fn pop_call(L : *mut lua_State, nargs : c_int) -> (Value, Vec<Value>) {
+    let stack = &mut (*L).stack;
+    let start = stack.len().saturating_sub(nargs.max(0) as usize).max((*L).base);
+    let args = stack.split_off(start);
+    (pop(L), args)
+}
+
+/// string from a C string, nil for a null pointer
+unsafe fn c_str(s : *const c_char) -> Value {
+    if s.is_null() {
+        Value::Nil
+    } else {
+        Value::from_bytes(CStr::from_ptr(s).to_bytes())
+    }
+}
+
+/// NUL terminated copy of a string, valid while the string is referenced by a value
+unsafe fn c_string(L : *mut lua_State, s : LuaStr) -> *const c_char {
+    let strings = &mut (*L).strings;
+    strings.retain(|(s, _)| Rc::strong_count(s) > 1);
+    if let Some((_, copy)) = strings.iter().find(|(t, _)| Rc::ptr_eq(t, &s)) {
+        return copy.as_ptr() as *const c_char;
+    }
+    let copy : Box<[u8]> = [&s[..], &[0]].concat().into_boxed_slice();
+    let res = copy.as_ptr() as *const c_char;
+    strings.push((s, copy));
+    res
+}
+
+/// calls a C function from the interpreter, with the arguments in a new frame of the stack
+unsafe fn call_c_function(L : *mut lua_State, f : unsafe extern "C" fn(*mut lua_State) -> c_int, upvalues : &Rc<RefCell<Vec<Value>>>,
+    args : Vec<Value>, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let saved_base = (*L).base;
+    let saved_env = (*L).env;
+    let saved_upvalues = std::mem::replace(&mut (*L).upvalues, Rc::clone(upvalues));
+    let saved_error = (*L).error.take();
+    (*L).base = (*L).stack.len();
+    (*L).stack.extend(args);
+    (*L).env = env;
+    (*L).calls += 1;
+
+    let nresults = f(L);
+
+    (*L).calls -= 1;
+    let base = (*L).base;
+    let stack = &mut (*L).stack;
+    let start = stack.len() - (nresults.max(0) as usize).min(stack.len() - base);
+    returned_values.extend(stack.drain(start..));
+    stack.truncate(base);
+    (*L).base = saved_base;
+    (*L).env = saved_env;
+    (*L).upvalues = saved_upvalues;
+    match std::mem::replace(&mut (*L).error, saved_error) {
+        Some(err) => { Err(err.into()) }
+        None => { Ok(()) }
+    }
+}
+
+/// creates a state with the standard library, the allocator is not used: values are allocated by the rust allocator
+#[no_mangle]
+pub unsafe extern "C" fn lua_newstate(_f : lua_Alloc, _ud : *mut c_void) -> *mut lua_State {
+    luaL_newstate()
+}
+
+/// creates a state with the standard library
+#[no_mangle]
+pub unsafe extern "C" fn luaL_newstate() -> *mut lua_State {
+    let L = Box::into_raw(Box::new(lua_State {
+        lua      : Lua::new(),
+        stack    : Vec::new(),
+        base     : 0,
+        env      : ptr::null_mut(),
+        upvalues : Rc::default(),
+        calls    : 0,
+        error    : None,
+        strings  : Vec::new()
+    }));
+    (*L).env = &mut (*L).lua.env;
+    L
+}
+
+/// the libraries are opened by lua_newstate, kept for the programs calling it
+#[no_mangle]
+pub unsafe extern "C" fn luaL_openlibs(_L : *mut lua_State) {
+}
+
+/// calls the finalizers of the userdata and releases the state
+#[no_mangle]
+pub unsafe extern "C" fn lua_close(L : *mut lua_State) {
+    // the values of the stack are released first so that the finalizers of their userdata are called
+    (*L).stack.clear();
+    drop(Box::from_raw(L));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_gettop(L : *mut lua_State) -> c_int {
+    ((*L).stack.len() - (*L).base) as c_int
+}
+
+/// sets the top of the stack, new values are nil
+#[no_mangle]
+pub unsafe extern "C" fn lua_settop(L : *mut lua_State, idx : c_int) {
+    let len = if idx >= 0 {
+        (*L).base + idx as usize
+    } else {
+        ((*L).stack.len() as isize + idx as isize + 1).max((*L).base as isize) as usize
+    };
+    (*L).stack.resize(len, Value::Nil);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_pushvalue(L : *mut lua_State, idx : c_int) {
+    push(L, value(L, idx));
+}
+
+/// removes the value at the index, the values above it are shifted down
+#[no_mangle]
+pub unsafe extern "C" fn lua_remove(L : *mut lua_State, idx : c_int) {
+    if let Some(p) = position(L, idx) {
+        (*L).stack.remove(p);
+    }
+}
+
+/// moves the top value to the index, the values above it are shifted up
+#[no_mangle]
+pub unsafe extern "C" fn lua_insert(L : *mut lua_State, idx : c_int) {
+    if let Some(p) = position(L, idx) {
+        let val = pop(L);
+        (*L).stack.insert(p, val);
+    }
+}
+
+/// pops the top value and stores it at the index
+#[no_mangle]
+pub unsafe extern "C" fn lua_replace(L : *mut lua_State, idx : c_int) {
+    let val = pop(L);
+    match idx {
+        LUA_GLOBALSINDEX => {
+            if let Value::Table(globals) = val {
+                env(L).set_globals(globals);
+            }
+        }
+        i if i < LUA_GLOBALSINDEX => {
+            if let Some(upvalue) = (*L).upvalues.borrow_mut().get_mut((LUA_GLOBALSINDEX - i - 1) as usize) {
+                *upvalue = val;
+            }
+        }
+        _ => {
+            if let Some(p) = position(L, idx) {
+                (&mut (*L).stack)[p] = val;
+            }
+        }
+    }
+}
+
+/// the stack grows as needed
+#[no_mangle]
+pub unsafe extern "C" fn lua_checkstack(_L : *mut lua_State, _sz : c_int) -> c_int {
+    1
+}
+
+/// type of the value at the index, LUA_TNONE for an index without a value
+#[no_mangle]
+pub unsafe extern "C" fn lua_type(L : *mut lua_State, idx : c_int) -> c_int {
+    match get(L, idx) {
+        None => { LUA_TNONE }
+        Some(Value::Nil) => { LUA_TNIL }
+        Some(Value::Boolean(_)) => { LUA_TBOOLEAN }
+        Some(Value::LightUserData(_)) => { LUA_TLIGHTUSERDATA }
+        Some(Value::Number(_)) => { LUA_TNUMBER }
+        Some(Value::LuaString(_)) => { LUA_TSTRING }
+        Some(Value::Table(_)) => { LUA_TTABLE }
+        Some(Value::LuaFunction(_) | Value::RuntimeFunction(_) | Value::RuntimeClosure(_)) => { LUA_TFUNCTION }
+        Some(Value::UserData(_)) => { LUA_TUSERDATA }
+        Some(Value::Thread(_)) => { LUA_TTHREAD }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_typename(_L : *mut lua_State, tp : c_int) -> *const c_char {
+    let name : &'static [u8] = match tp {
+        LUA_TNIL => b"nil\0",
+        LUA_TBOOLEAN => b"boolean\0",
+        LUA_TLIGHTUSERDATA | LUA_TUSERDATA => b"userdata\0",
+        LUA_TNUMBER => b"number\0",
+        LUA_TSTRING => b"string\0",
+        LUA_TTABLE => b"table\0",
+        LUA_TFUNCTION => b"function\0",
+        LUA_TTHREAD => b"thread\0",
+        _ => b"no value\0"
+    };
+    name.as_ptr() as *const c_char
+}
+
+/// tells if the value is a number or a string convertible to a number
+#[no_mangle]
+pub unsafe extern "C" fn lua_isnumber(L : *mut lua_State, idx : c_int) -> c_int {
+    value(L, idx).get_number().is_some() as c_int
+}
+
+/// tells if the value is a string or a number
+#[no_mangle]
+pub unsafe extern "C" fn lua_isstring(L : *mut lua_State, idx : c_int) -> c_int {
+    matches!(value(L, idx), Value::LuaString(_) | Value::Number(_)) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_iscfunction(L : *mut lua_State, idx : c_int) -> c_int {
+    matches!(value(L, idx), Value::RuntimeFunction(_) | Value::RuntimeClosure(_)) as c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_isuserdata(L : *mut lua_State, idx : c_int) -> c_int {
+    matches!(value(L, idx), Value::UserData(_) | Value::LightUserData(_)) as c_int
+}
+
+/// compares two values without calling the __eq handlers, 0 if an index is not valid
+#[no_mangle]
+pub unsafe extern "C" fn lua_rawequal(L : *mut lua_State, idx1 : c_int, idx2 : c_int) -> c_int {
+    match (get(L, idx1), get(L, idx2)) {
+        (Some(a), Some(b)) => { (a == b) as c_int }
+        _ => { 0 }
+    }
+}
+
+/// compares two values as the == operator, 0 if an index is not valid
+#[no_mangle]
+pub unsafe extern "C" fn lua_equal(L : *mut lua_State, idx1 : c_int, idx2 : c_int) -> c_int {
+    let (Some(a), Some(b)) = (get(L, idx1), get(L, idx2)) else {
+        return 0;
+    };
+    match metatable::equals(env(L), a, b) {
+        Ok(res) => { res as c_int }
+        Err(err) => { raise(L, err.into()); 0 }
+    }
+}
+
+/// compares two values as the < operator, 0 if an index is not valid
+#[no_mangle]
+pub unsafe extern "C" fn lua_lessthan(L : *mut lua_State, idx1 : c_int, idx2 : c_int) -> c_int {
+    let (Some(a), Some(b)) = (get(L, idx1), get(L, idx2)) else {
+        return 0;
+    };
+    match metatable::less_than(env(L), a, b) {
+        Ok(res) => { res as c_int }
+        Err(err) => { raise(L, err.into()); 0 }
+    }
+}
+
+/// number value of a number or of a string convertible to a number, 0 otherwise
+#[no_mangle]
+pub unsafe extern "C" fn lua_tonumber(L : *mut lua_State, idx : c_int) -> lua_Number {
+    value(L, idx).get_number().unwrap_or(0.)
+}
+
+/// number value truncated to an integer, 0 if the value is not a number
+#[no_mangle]
+pub unsafe extern "C" fn lua_tointeger(L : *mut lua_State, idx : c_int) -> lua_Integer {
+    value(L, idx).get_number().unwrap_or(0.) as lua_Integer
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_toboolean(L : *mut lua_State, idx : c_int) -> c_int {
+    value(L, idx).to_boolean() as c_int
+}
+
+/** returns the string at the index and stores its length in len if it is not null, NULL if the value is not a string or a number
+ * a number is replaced by its string in the stack, the returned string is valid while the value is referenced
+ */
+#[no_mangle]
+pub unsafe extern "C" fn lua_tolstring(L : *mut lua_State, idx : c_int, len : *mut usize) -> *const c_char {
+    let s = match value(L, idx) {
+        Value::LuaString(s) => { s }
+        val @ Value::Number(_) => {
+            let s = val.to_lua_string().unwrap_or_default();
+            if let Some(p) = position(L, idx) {
+                (&mut (*L).stack)[p] = Value::LuaString(Rc::clone(&s));
+            }
+            s
+        }
+        _ => {
+            if !len.is_null() {
+                *len = 0;
+            }
+            return ptr::null();
+        }
+    };
+    if !len.is_null() {
+        *len = s.len();
+    }
+    c_string(L, s)
+}
+
+/// length of a string, of the array part of a table or of the block of a userdata
+#[no_mangle]
+pub unsafe extern "C" fn lua_objlen(L : *mut lua_State, idx : c_int) -> usize {
+    match value(L, idx) {
+        Value::LuaString(s) => { s.len() }
+        Value::Number(n) => { Value::Number(n).to_lua_string().map(|s| s.len()).unwrap_or(0) }
+        Value::Table(t) => { t.borrow().len() }
+        Value::UserData(u) => { u.borrow::<Block>().map(|block| block.1).unwrap_or(0) }
+        _ => { 0 }
+    }
+}
+
+/// address of the block of a userdata created by lua_newuserdata or of a light userdata, NULL otherwise
+#[no_mangle]
+pub unsafe extern "C" fn lua_touserdata(L : *mut lua_State, idx : c_int) -> *mut c_void {
+    match value(L, idx) {
+        Value::LightUserData(p) => { p }
+        Value::UserData(u) => { u.borrow::<Block>().map(|block| block.0.as_ptr() as *mut c_void).unwrap_or(ptr::null_mut()) }
+        _ => { ptr::null_mut() }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_pushnil(L : *mut lua_State) {
+    push(L, Value::Nil);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_pushnumber(L : *mut lua_State, n : lua_Number) {
+    push(L, Value::Number(n));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_pushinteger(L : *mut lua_State, n : lua_Integer) {
+    push(L, Value::Number(n as f64));
+}
+
+/// pushes a copy of the len bytes at s, they can contain zeros
+#[no_mangle]
+pub unsafe extern "C" fn lua_pushlstring(L : *mut lua_State, s : *const c_char, len : usize) {
+    let bytes = if len == 0 { &[][..] } else { slice::from_raw_parts(s as *const u8, len) };
+    push(L, Value::from_bytes(bytes));
+}
+
+/// pushes a copy of a NUL terminated string, nil if s is NULL
+#[no_mangle]
+pub unsafe extern "C" fn lua_pushstring(L : *mut lua_State, s : *const c_char) {
+    push(L, c_str(s));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_pushboolean(L : *mut lua_State, b : c_int) {
+    push(L, Value::Boolean(b != 0));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lua_pushlightuserdata(L : *mut lua_State, p : *mut c_void) {
+    push(L, Value::LightUserData(p));
+}
+
+/// pops n values and pushes a function calling f with them as its upvalues
+#[no_mangle]
+pub unsafe extern "C" fn lua_pushcclosure(L : *mut lua_State, f : lua_CFunction, n : c_int) {
+    let stack = &mut (*L).stack;
+    let start = stack.len().saturating_sub(n.max(0) as usize).max((*L).base);
+    let upvalues = Rc::new(RefCell::new(stack.split_off(start)));
+    let Some(f) = f else {
+        push(L, Value::Nil);
+        return;
+    };
+    push(L, RuntimeClosure::new_value(move |frame, returned_values, env| {
+        let args = (0..frame.len()).map(|i| frame.load(i)).collect();
+        call_c_function(L, f, &upvalues, args, returned_values, env)
+    }));
+}
+
+/// pops a key and pushes the value of t[key], where t is the value at the index, handlers of __index are called
+#[no_mangle]
+pub unsafe extern "C" fn lua_gettable(L : *mut lua_State, idx : c_int) {
+    let table = value(L, idx);
+    let key = pop(L);
+    let res = metatable::index(env(L), table, key).unwrap_or_else(|err| { raise(L, err.into()); Value::Nil });
+    push(L, res);
+}
+
+/// pushes the value of t[k], where t is the value at the index
+#[no_mangle]
+pub unsafe extern "C" fn lua_getfield(L : *mut lua_State, idx : c_int, k : *const c_char) {
+    let table = value(L, idx);
+    let res = metatable::index(env(L), table, c_str(k)).unwrap_or_else(|err| { raise(L, err.into()); Value::Nil });
+    push(L, res);
+}
+
+/// pops a key and pushes the value of the field of the table at the index, without calling handlers
+#[no_mangle]
+pub unsafe extern "C" fn lua_rawget(L : *mut lua_State, idx : c_int) {
+    let table = value(L, idx);
+    let key = pop(L);
+    let res = match table {
+        Value::Table(t) => { t.borrow().get(&key) }
+        _ => { Value::Nil }
+    };
+    push(L, res);
+}
+
+/// pushes the value of t[n] without calling handlers, where t is the table at the index
+#[no_mangle]
+pub unsafe extern "C" fn lua_rawgeti(L : *mut lua_State, idx : c_int, n : c_int) {
+    let res = match value(L, idx) {
+        Value::Table(t) => { t.borrow().get(&Value::Number(n as f64)) }
+        _ => { Value::Nil }
+    };
+    push(L, res);
+}
+
+/// pushes a new table with space for narr elements in its array part and nrec other fields
+#[no_mangle]
+pub unsafe extern "C" fn lua_createtable(L : *mut lua_State, narr : c_int, nrec : c_int) {
+    let table = Table::with_capacity(narr.max(0) as usize, nrec.max(0) as usize);
+    push(L, Value::Table(Rc::new(RefCell::new(table))));
+}
+
+/// pushes a new userdata holding a block of size bytes and returns its address
+#[no_mangle]
+pub unsafe extern "C" fn lua_newuserdata(L : *mut lua_State, size : usize) -> *mut c_void {
+    let block = Block(vec![0; size.div_ceil(8)].into_boxed_slice(), size);
+    let address = block.0.as_ptr() as *mut c_void;
+    push(L, Value::UserData(env(L).new_userdata(block)));
+    address
+}
+
+/// pushes the metatable of the value at the index and returns 1, returns 0 and pushes nothing if it has none
+#[no_mangle]
+pub unsafe extern "C" fn lua_getmetatable(L : *mut lua_State, idx : c_int) -> c_int {
+    match metatable::get_metatable(env(L), &value(L, idx)) {
+        Some(mt) => { push(L, Value::Table(mt)); 1 }
+        None => { 0 }
+    }
+}
+
+/// pops a table or nil and sets it as the metatable of the table or userdata at the index
+#[no_mangle]
+pub unsafe extern "C" fn lua_setmetatable(L : *mut lua_State, idx : c_int) -> c_int {
+    let obj = value(L, idx);
+    let metatable = match pop(L) {
+        Value::Table(mt) => { Some(mt) }
+        _ => { None }
+    };
+    match obj {
+        Value::Table(t) => { t.borrow_mut().metatable = metatable; }
+        Value::UserData(u) => { u.set_metatable(metatable); }
+        _ => { return 0 }
+    }
+    1
+}
+
+/// pops a value and a key and does t[key] = value, where t is the value at the index, handlers of __newindex are called
+#[no_mangle]
+pub unsafe extern "C" fn lua_settable(L : *mut lua_State, idx : c_int) {
+    let table = value(L, idx);
+    let val = pop(L);
+    let key = pop(L);
+    if let Err(err) = metatable::new_index(env(L), table, key, val) {
+        raise(L, err.into());
+    }
+}
+
+/// pops a value and does t[k] = value, where t is the value at the index
+#[no_mangle]
+pub unsafe extern "C" fn lua_setfield(L : *mut lua_State, idx : c_int, k : *const c_char) {
+    let table = value(L, idx);
+    let val = pop(L);
+    if let Err(err) = metatable::new_index(env(L), table, c_str(k), val) {
+        raise(L, err.into());
+    }
+}
+
+/// pops a value and a key and sets the field of the table at the index, without calling handlers
+#[no_mangle]
+pub unsafe extern "C" fn lua_rawset(L : *mut lua_State, idx : c_int) {
+    let table = value(L, idx);
+    let val = pop(L);
+    let key = pop(L);
+    if let Value::Table(t) = table {
+        if let Err(err) = t.borrow_mut().set(key, val) {
+            raise(L, InterpreterError::from(err).into());
+        }
+    }
+}
+
+/// pops a value and does t[n] = value without calling handlers, where t is the table at the index
+#[no_mangle]
+pub unsafe extern "C" fn lua_rawseti(L : *mut lua_State, idx : c_int, n : c_int) {
+    let table = value(L, idx);
+    let val = pop(L);
+    if let Value::Table(t) = table {
+        if let Err(err) = t.borrow_mut().set(Value::Number(n as f64), val) {
+            raise(L, InterpreterError::from(err).into());
+        }
+    }
+}
+
+/// pops a key and pushes the next key and value of the table at the index and returns 1, returns 0 at the end of the traversal
+#[no_mangle]
+pub unsafe extern "C" fn lua_next(L : *mut lua_State, idx : c_int) -> c_int {
+    let table = value(L, idx);
+    let key = pop(L);
+    let Value::Table(t) = table else {
+        return 0;
+    };
+    let next = t.borrow().next(&key);
+    match next {
+        Ok(Some((key, val))) => { push(L, key); push(L, val); 1 }
+        Ok(None) => { 0 }
+        Err(err) => { raise(L, InterpreterError::from(err).into()); 0 }
+    }
+}
+
+/** pops nargs arguments and the function below them, calls it and pushes nresults results
+ * unlike Lua, an error doesn't unwind the calling C function: nresults nils are pushed and the C function goes on,
+ * the error is raised when it returns; use lua_pcall to stop at the error
+ */
+#[no_mangle]
+pub unsafe extern "C" fn lua_call(L : *mut lua_State, nargs : c_int, nresults : c_int) {
+    let (func, args) = pop_call(L, nargs);
+    match call_value(env(L), func, args) {
+        Ok(results) => { push_results(L, results, nresults); }
+        Err(err) => {
+            raise(L, err.into());
+            push_results(L, Vec::new(), nresults);
+        }
+    }
+}
+
+/** calls a function as lua_call in protected mode, returns 0 or LUA_ERRRUN with the error object pushed instead of the results
+ * errfunc is the index of the message handler, 0 for none
+ */
+#[no_mangle]
+pub unsafe extern "C" fn lua_pcall(L : *mut lua_State, nargs : c_int, nresults : c_int, errfunc : c_int) -> c_int {
+    let handler = if errfunc == 0 { Value::Nil } else { value(L, errfunc) };
+    let (func, args) = pop_call(L, nargs);
+    let env = env(L);
+    env.error_handlers.push(handler);
+    let res = call_value(env, func, args);
+    env.error_handlers.pop();
+    if (*L).calls == 0 {
+        run_finalizers(env);
+    }
+    match res {
+        Ok(results) => {
+            push_results(L, results, nresults);
+            0
+        }
+        Err(err) => {
+            push(L, err.to_value());
+            LUA_ERRRUN
+        }
+    }
+}
+
+/// pops the error object and raises it when the running C function returns, call it as `return lua_error(L);`
+#[no_mangle]
+pub unsafe extern "C" fn lua_error(L : *mut lua_State) -> c_int {
+    let value = pop(L);
+    raise(L, InterpreterError::RaisedError { value }.into());
+    0
+}
+
+/// decodes a chunk compiled by luac 5.1 and pushes its main function, returns LUA_ERRSYNTAX with a message pushed if it is invalid
+#[no_mangle]
+pub unsafe extern "C" fn luaL_loadbuffer(L : *mut lua_State, buff : *const c_char, sz : usize, _name : *const c_char) -> c_int {
+    let bytes = if sz == 0 { Vec::new() } else { slice::from_raw_parts(buff as *const u8, sz).to_vec() };
+    match decompile_bytes(bytes) {
+        Ok(main) => {
+            let closure = Closure::new(Rc::new(main), env(L).globals());
+            push(L, Value::LuaFunction(Rc::new(closure)));
+            0
+        }
+        Err(err) => {
+            push(L, Value::from_string(&err.to_string()));
+            LUA_ERRSYNTAX
+        }
+    }
+}
@@ -1,9 +1,14 @@
+use crate::interpreter::limits::Limits;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct Vmconfig {
     file_path : String,
     dump : bool,
-    version : String
+    version : String,
+    seed : u64,
+    sandbox : bool,
+    limits : Limits
 }
 
 impl Vmconfig {
@@ -12,12 +17,38 @@ impl Vmconfig {
         let mut d = false;
         let mut s: String = String::new();
         let mut ver : String = String::from("5.1");
+        let mut seed : u64 = 0;
+        let mut sandbox = false;
+        let mut limits = Limits::default();
         
-        for i in 1..args.len() 
+        for arg in args.iter().skip(1)
         {
-            match args[i].as_str() {
-                "-dump" | "-d" if d == false => { d = true; }
-                "ver=5.1" | "ver=5.3" => { ver = String::from(args[i].as_str()).split_off(4); }
+            match arg.as_str() {
+                "-dump" | "-d" if !d => { d = true; }
+                // the script has no access to the io and os functions
+                "-sandbox" | "-s" => { sandbox = true; }
+                "ver=5.1" | "ver=5.3" => { ver = String::from(arg.as_str()).split_off(4); }
+                // seed of math.random, to replay a run
+                other if other.starts_with("seed=") => {
+                    seed = other[5..].parse().map_err(|_| "seed must be a non-negative integer")?;
+                }
+                // limits on the resources used by the script
+                other if other.starts_with("instructions=") => {
+                    limits.instructions = Some(other[13..].parse().map_err(|_| "instructions must be a non-negative integer")?);
+                }
+                other if other.starts_with("memory=") => {
+                    limits.memory = Some(other[7..].parse().map_err(|_| "memory must be a number of bytes")?);
+                }
+                other if other.starts_with("depth=") => {
+                    limits.call_depth = Some(other[6..].parse().map_err(|_| "depth must be a non-negative integer")?);
+                }
+                other if other.starts_with("strlen=") => {
+                    limits.string_length = Some(other[7..].parse().map_err(|_| "strlen must be a number of bytes")?);
+                }
+                other if other.starts_with("time=") => {
+                    let millis = other[5..].parse().map_err(|_| "time must be a number of milliseconds")?;
+                    limits.time = Some(Duration::from_millis(millis));
+                }
                 other /* if other.ends_with(".out") */ => 
                 {
                     if s.is_empty() {
@@ -33,7 +64,10 @@ impl Vmconfig {
         Ok(Vmconfig {
             file_path : s,
             dump : d,
-            version : ver
+            version : ver,
+            seed,
+            sandbox,
+            limits
         })
     }
 
@@ -45,6 +79,18 @@ impl Vmconfig {
         self.file_path.as_str()
     }
 
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_sandbox(&self) -> bool {
+        self.sandbox
+    }
+
+    pub fn get_limits(&self) -> Limits {
+        self.limits
+    }
+
     pub fn get_ver(&self) -> u32 {
         let mut chrs = self.version.chars();
         const RADIX: u32 = 10;
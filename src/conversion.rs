@@ -0,0 +1,411 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::vec;
+
+use crate::interpreter::interpreter::InterpreterError;
+use crate::interpreter::object::{LuaStr, TableRef, Value};
+use crate::interpreter::table::Table;
+use crate::interpreter::userdata::UserData;
+use crate::lua::{Context, LuaError};
+
+/// Host value that can be given to the scripts
+pub trait IntoLua {
+    fn into_lua(self, ctx : &Context) -> Result<Value, LuaError>;
+}
+
+/// Host value that can be read from a lua value, the conversion fails when the value has another type
+pub trait FromLua : Sized {
+    fn from_lua(value : Value, ctx : &Context) -> Result<Self, LuaError>;
+}
+
+/// Host values given to the scripts as a list of values, the results of a host function or the arguments of a call
+pub trait IntoLuaMulti {
+    fn into_lua_multi(self, ctx : &Context) -> Result<Vec<Value>, LuaError>;
+}
+
+/// Host values read from a list of values, each conversion takes the values it needs from the list
+pub trait FromLuaMulti : Sized {
+    fn from_lua_multi(values : &mut Values, ctx : &Context) -> Result<Self, LuaError>;
+}
+
+/** List of values being converted by FromLuaMulti, missing values are nil
+ * it remembers the position of the last value taken to tell which argument can't be converted
+ */
+pub struct Values {
+    values   : vec::IntoIter<Value>,
+    position : usize
+}
+
+impl Values {
+
+    pub fn new(values : Vec<Value>) -> Self {
+        Values { values: values.into_iter(), position: 0 }
+    }
+
+    /// takes the next value, None when every value was taken
+    pub fn take(&mut self) -> Option<Value> {
+        self.position += 1;
+        self.values.next()
+    }
+
+    /// position of the last value taken, 1 for the first value
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// turns an error raised while converting the last value taken into an error on the argument at its position
+    pub fn argument_error(&self, err : LuaError) -> LuaError {
+        InterpreterError::HostArgumentError { arg: self.position, msg: err.to_string() }.into()
+    }
+
+}
+
+/// Values of the same type given to or taken from the end of a list of values, as the ... of a lua function
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Variadic<T>(pub Vec<T>);
+
+/// Function value of the scripts, called from the host with converted arguments and results
+#[derive(Clone, Debug)]
+pub struct Function(Value);
+
+impl Function {
+
+    pub fn call<A : IntoLuaMulti, R : FromLuaMulti>(&self, ctx : &mut Context, args : A) -> Result<R, LuaError> {
+        let args = args.into_lua_multi(ctx)?;
+        let results = ctx.call(self.0.clone(), args)?;
+        R::from_lua_multi(&mut Values::new(results), ctx)
+    }
+
+}
+
+/** Host closure given to the scripts as a function value, so that a host function can return a closure
+ * its arguments and results are converted as by Context::create_typed_function
+ */
+pub struct HostFunction<A, R, F> {
+    func  : F,
+    types : PhantomData<fn(A) -> R>
+}
+
+impl<A, R, F> HostFunction<A, R, F>
+where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, A) -> Result<R, LuaError> + 'static {
+
+    pub fn new(func : F) -> Self {
+        HostFunction { func, types: PhantomData }
+    }
+
+}
+
+/// error on a value that doesn't have the expected type
+fn conversion_error(expected : &'static str, value : &Value) -> LuaError {
+    InterpreterError::ConversionError { expected, got: value.get_type().to_string() }.into()
+}
+
+impl IntoLua for Value {
+    fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+        Ok(self)
+    }
+}
+
+impl FromLua for Value {
+    fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+        Ok(value)
+    }
+}
+
+// numbers are converted as lua_tonumber and lua_pushnumber do, strings holding numbers are accepted
+macro_rules! float_conversions {
+    ($($typ:ty),*) => {
+        $(
+            impl IntoLua for $typ {
+                fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+                    Ok(Value::Number(self as f64))
+                }
+            }
+
+            impl FromLua for $typ {
+                fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+                    value.get_number().map(|n| n as $typ).ok_or_else(|| conversion_error("number", &value))
+                }
+            }
+        )*
+    };
+}
+
+float_conversions!(f64, f32);
+
+/* integers are read from the numbers having an exact value of the type, the others raise an error instead of being rounded or saturated
+ * the maximum of a type converted to a float may be rounded up to the next power of two, which is out of range
+ */
+macro_rules! integer_conversions {
+    ($($typ:ty),*) => {
+        $(
+            impl IntoLua for $typ {
+                fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+                    Ok(Value::Number(self as f64))
+                }
+            }
+
+            impl FromLua for $typ {
+                fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+                    let n = value.get_number().ok_or_else(|| conversion_error("number", &value))?;
+                    if n.fract() == 0. && n >= <$typ>::MIN as f64 && n < <$typ>::MAX as f64 + 1. {
+                        Ok(n as $typ)
+                    } else {
+                        Err(InterpreterError::ConversionError { expected: stringify!($typ), got: Value::Number(n).to_string() }.into())
+                    }
+                }
+            }
+        )*
+    };
+}
+
+integer_conversions!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl IntoLua for bool {
+    fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+        Ok(Value::Boolean(self))
+    }
+}
+
+// any value is a condition, only nil and false are false
+impl FromLua for bool {
+    fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+        Ok(value.to_boolean())
+    }
+}
+
+impl IntoLua for &str {
+    fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+        Ok(Value::from_string(self))
+    }
+}
+
+impl IntoLua for String {
+    fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+        Ok(Value::from_string(&self))
+    }
+}
+
+// numbers are converted to strings as by the concatenation, the string must be valid UTF-8
+impl FromLua for String {
+    fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+        let bytes = value.to_lua_string().ok_or_else(|| conversion_error("string", &value))?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| LuaError::runtime("string is not valid UTF-8"))
+    }
+}
+
+impl IntoLua for LuaStr {
+    fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+        Ok(Value::LuaString(self))
+    }
+}
+
+impl FromLua for LuaStr {
+    fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+        value.to_lua_string().ok_or_else(|| conversion_error("string", &value))
+    }
+}
+
+impl<T : IntoLua> IntoLua for Option<T> {
+    fn into_lua(self, ctx : &Context) -> Result<Value, LuaError> {
+        match self {
+            Some(val) => { val.into_lua(ctx) }
+            None => { Ok(Value::Nil) }
+        }
+    }
+}
+
+impl<T : FromLua> FromLua for Option<T> {
+    fn from_lua(value : Value, ctx : &Context) -> Result<Self, LuaError> {
+        match value {
+            Value::Nil => { Ok(None) }
+            value => { T::from_lua(value, ctx).map(Some) }
+        }
+    }
+}
+
+impl IntoLua for TableRef {
+    fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+        Ok(Value::Table(self))
+    }
+}
+
+impl FromLua for TableRef {
+    fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+        match value {
+            Value::Table(table) => { Ok(table) }
+            value => { Err(conversion_error("table", &value)) }
+        }
+    }
+}
+
+impl IntoLua for Rc<UserData> {
+    fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+        Ok(Value::UserData(self))
+    }
+}
+
+impl FromLua for Rc<UserData> {
+    fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+        match value {
+            Value::UserData(userdata) => { Ok(userdata) }
+            value => { Err(conversion_error("userdata", &value)) }
+        }
+    }
+}
+
+impl IntoLua for Function {
+    fn into_lua(self, _ : &Context) -> Result<Value, LuaError> {
+        Ok(self.0)
+    }
+}
+
+// lua functions and host functions, other callable values are not accepted
+impl FromLua for Function {
+    fn from_lua(value : Value, _ : &Context) -> Result<Self, LuaError> {
+        match value {
+            Value::LuaFunction(_) | Value::RuntimeFunction(_) | Value::RuntimeClosure(_) => { Ok(Function(value)) }
+            value => { Err(conversion_error("function", &value)) }
+        }
+    }
+}
+
+impl<A, R, F> IntoLua for HostFunction<A, R, F>
+where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, A) -> Result<R, LuaError> + 'static {
+    fn into_lua(self, ctx : &Context) -> Result<Value, LuaError> {
+        Ok(ctx.create_typed_function(self.func))
+    }
+}
+
+// vectors are the array part of a table, the elements are at the keys 1 to n
+impl<T : IntoLua> IntoLua for Vec<T> {
+    fn into_lua(self, ctx : &Context) -> Result<Value, LuaError> {
+        let mut table = Table::with_capacity(self.len(), 0);
+        for (i, val) in self.into_iter().enumerate() {
+            table.set(Value::Number((i + 1) as f64), val.into_lua(ctx)?).map_err(InterpreterError::from)?;
+        }
+        Ok(Value::Table(Rc::new(RefCell::new(table))))
+    }
+}
+
+impl<T : FromLua> FromLua for Vec<T> {
+    fn from_lua(value : Value, ctx : &Context) -> Result<Self, LuaError> {
+        let table = TableRef::from_lua(value, ctx)?;
+        // the elements are converted once the table is released
+        let elements : Vec<Value> = {
+            let table = table.borrow();
+            (1..=table.len()).map(|i| table.get(&Value::Number(i as f64))).collect()
+        };
+        elements.into_iter().map(|val| T::from_lua(val, ctx)).collect()
+    }
+}
+
+impl<K : IntoLua, V : IntoLua> IntoLua for HashMap<K, V> {
+    fn into_lua(self, ctx : &Context) -> Result<Value, LuaError> {
+        let mut table = Table::with_capacity(0, self.len());
+        for (key, val) in self {
+            table.set(key.into_lua(ctx)?, val.into_lua(ctx)?).map_err(InterpreterError::from)?;
+        }
+        Ok(Value::Table(Rc::new(RefCell::new(table))))
+    }
+}
+
+impl<K : FromLua + Eq + Hash, V : FromLua> FromLua for HashMap<K, V> {
+    fn from_lua(value : Value, ctx : &Context) -> Result<Self, LuaError> {
+        let table = TableRef::from_lua(value, ctx)?;
+        let mut fields = Vec::new();
+        {
+            let table = table.borrow();
+            let mut key = Value::Nil;
+            while let Some((next_key, val)) = table.next(&key).map_err(InterpreterError::from)? {
+                fields.push((next_key.clone(), val));
+                key = next_key;
+            }
+        }
+        fields.into_iter().map(|(key, val)| Ok((K::from_lua(key, ctx)?, V::from_lua(val, ctx)?))).collect()
+    }
+}
+
+impl<T : IntoLua> IntoLuaMulti for T {
+    fn into_lua_multi(self, ctx : &Context) -> Result<Vec<Value>, LuaError> {
+        Ok(vec![self.into_lua(ctx)?])
+    }
+}
+
+// a missing value is converted from nil, it is reported as no value
+impl<T : FromLua> FromLuaMulti for T {
+    fn from_lua_multi(values : &mut Values, ctx : &Context) -> Result<Self, LuaError> {
+        match values.take() {
+            Some(val) => { T::from_lua(val, ctx) }
+            None => {
+                T::from_lua(Value::Nil, ctx).map_err(|err| match err {
+                    LuaError::RuntimeError { interpreter_error: InterpreterError::ConversionError { expected, .. } } => {
+                        InterpreterError::ConversionError { expected, got: String::from("no value") }.into()
+                    }
+                    err => { err }
+                })
+            }
+        }
+    }
+}
+
+impl<T : IntoLua> IntoLuaMulti for Variadic<T> {
+    fn into_lua_multi(self, ctx : &Context) -> Result<Vec<Value>, LuaError> {
+        self.0.into_iter().map(|val| val.into_lua(ctx)).collect()
+    }
+}
+
+impl<T : FromLua> FromLuaMulti for Variadic<T> {
+    fn from_lua_multi(values : &mut Values, ctx : &Context) -> Result<Self, LuaError> {
+        let mut res = Vec::new();
+        while let Some(val) = values.take() {
+            res.push(T::from_lua(val, ctx)?);
+        }
+        Ok(Variadic(res))
+    }
+}
+
+impl IntoLuaMulti for () {
+    fn into_lua_multi(self, _ : &Context) -> Result<Vec<Value>, LuaError> {
+        Ok(Vec::new())
+    }
+}
+
+impl FromLuaMulti for () {
+    fn from_lua_multi(_ : &mut Values, _ : &Context) -> Result<Self, LuaError> {
+        Ok(())
+    }
+}
+
+// the elements of a tuple are converted in order, each one from the values following the previous one
+macro_rules! tuple_conversions {
+    ($($name:ident),+) => {
+        impl<$($name : IntoLuaMulti),+> IntoLuaMulti for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn into_lua_multi(self, ctx : &Context) -> Result<Vec<Value>, LuaError> {
+                let ($($name,)+) = self;
+                let mut res = Vec::new();
+                $(res.extend($name.into_lua_multi(ctx)?);)+
+                Ok(res)
+            }
+        }
+
+        impl<$($name : FromLuaMulti),+> FromLuaMulti for ($($name,)+) {
+            fn from_lua_multi(values : &mut Values, ctx : &Context) -> Result<Self, LuaError> {
+                Ok(($($name::from_lua_multi(values, ctx)?,)+))
+            }
+        }
+    };
+}
+
+tuple_conversions!(A);
+tuple_conversions!(A, B);
+tuple_conversions!(A, B, C);
+tuple_conversions!(A, B, C, D);
+tuple_conversions!(A, B, C, D, E);
+tuple_conversions!(A, B, C, D, E, F);
+tuple_conversions!(A, B, C, D, E, F, G);
+tuple_conversions!(A, B, C, D, E, F, G, H);
@@ -5,10 +5,12 @@ use crate::structure::{constant::Constant, local_variable::LocalVariable, instru
 use crate::decompile::metadata::Metadata;
 
 use std::{error::Error, fs};
+use std::rc::Rc;
 use std::vec::IntoIter;
 
+/// Errors raised while decoding a compiled chunk
 #[derive(thiserror::Error, Debug)]
-enum DecompileError{
+pub enum DecompileError{
     #[error("Signature is invalid")]
     SignatureError,
     #[error("Missing Metadata")]
@@ -34,7 +36,7 @@ enum DecompileError{
 fn next_byte_or_error(iter: &mut IntoIter<u8>, err : DecompileError) -> Result<u8, DecompileError> {
     match iter.next() {
         Some(b) => { Ok(b) }
-        None        => { return Err(err) }
+        None        => { Err(err) }
     }
 }
 
@@ -58,8 +60,8 @@ fn decode_litendian(iter: &mut IntoIter<u8>, size: u8) -> Result<u64, DecompileE
     for _ in 0..size {
         let byte = next_byte_or_error(iter, DecompileError::FileFormatError)?;
         
-        res = res + u64::from(byte) * acc;
-        acc = acc << 8;
+        res += u64::from(byte) * acc;
+        acc <<= 8;
     }    
 
     Ok(res)
@@ -115,9 +117,9 @@ fn decode_metadata(iter: &mut IntoIter<u8>) -> Result<Metadata, DecompileError>
 }
 
 /// Checks that the metadata of the file are correct (mainly that the version of lua is correct)
-fn verify_metadata(metadata: &Metadata, config: Vmconfig) -> Result<(), DecompileError> {
+fn verify_metadata(metadata: &Metadata, version: u32) -> Result<(), DecompileError> {
 
-    if u32::from(metadata.version) != config.get_ver() {
+    if u32::from(metadata.version) != version {
         return Err(DecompileError::VersionDataError);
     }
 
@@ -138,13 +140,20 @@ fn decode_double(iter: &mut IntoIter<u8>, size: u8, bigendian : bool) -> Result<
 /// parse a string from the file
 fn decode_str(iter: &mut IntoIter<u8>, size: u64) -> Result<String, DecompileError>{
 
-    let mut s = String::new();
+    let bytes = decode_bytes(iter, size)?;
+
+    Ok(bytes.into_iter().map(char::from).collect())
+}
+
+/// parse a lua string from the file, lua strings can contain any byte
+fn decode_bytes(iter: &mut IntoIter<u8>, size: u64) -> Result<Vec<u8>, DecompileError>{
+
+    let mut s = Vec::new();
 
     if size != 0 {
 
         for _ in 0..(size-1) {
-            let c = char::from(next_byte_or_error(iter, DecompileError::FileFormatError)?); 
-            s.push(c);
+            s.push(next_byte_or_error(iter, DecompileError::FileFormatError)?);
         }
     
         // We ignore the next byte as it represents the null character of the string
@@ -172,8 +181,8 @@ fn decode_constant(iter: &mut IntoIter<u8>, metadata: &Metadata) -> Result<Const
         }
         4 => {
             let string_size = decode_int(iter, metadata.u_size, metadata.bigendian)?;
-            let str = decode_str(iter, string_size)?;
-            Constant::String(str)
+            let str = decode_bytes(iter, string_size)?;
+            Constant::String(Rc::from(str))
         }
         _ => { return Err(DecompileError::ConstantTypeError) }
     };
@@ -240,10 +249,28 @@ fn decode_instruction(iter: &mut IntoIter<u8>, metadata: &Metadata) -> Result<In
         0 | 2..=4 | 6 | 8..=21 | 23..=30 | 33 | 34 | 35 | 37 => { Ok(get_register_abc(instruction_bytes, opcode)?) }
         1 | 5 | 7 | 36 => { Ok(get_register_abx(instruction_bytes, opcode)?) }
         22 | 31 | 32 => { Ok(get_register_asb(instruction_bytes, opcode)?) }
-        _  => { return Err(DecompileError::InstrEncodingError{instr_code : opcode} ) }
+        _  => { Err(DecompileError::InstrEncodingError{instr_code : opcode} ) }
     }
 }
 
+/// parse the list of instructions, the word following a SetList whose C is 0 is its block number and not an instruction
+fn decode_instruction_list(iter: &mut IntoIter<u8>, metadata: &Metadata) -> Result<Vec<Instruction>, DecompileError> {
+
+    let capacity = decode_int(iter, metadata.i_size, metadata.bigendian)? as usize;
+
+    let mut res: Vec<Instruction> = Vec::with_capacity(capacity);
+
+    for _ in 0..capacity {
+        let instruction = match res.last() {
+            Some(Instruction::SetList(_, _, 0)) => { Instruction::ExtraArg(decode_int(iter, metadata.instr_size, metadata.bigendian)? as usize) }
+            _ => { decode_instruction(iter, metadata)? }
+        };
+        res.push(instruction);
+    }
+
+    Ok(res)
+}
+
 /// parse a list of element (Constant, Function, ...) according to the decode function provided
 fn decode_list<T>(iter: &mut IntoIter<u8>, metadata: &Metadata, decoder : fn(&mut IntoIter<u8>, &Metadata) -> Result<T, DecompileError>) -> Result<Vec<T>, DecompileError> {
 
@@ -316,29 +343,38 @@ fn decode_function_block(iter: &mut IntoIter<u8>, metadata: &Metadata) -> Result
         args       : next_byte_or_error(iter, DecompileError::FileFormatError)?,
         vargs      : next_byte_or_error(iter, DecompileError::FileFormatError)?,
         stack      : next_byte_or_error(iter, DecompileError::FileFormatError)?,
-        instr_list : decode_list(iter, metadata, decode_instruction)?,
+        instr_list : decode_instruction_list(iter, metadata)?,
         const_list : decode_list(iter, metadata, decode_constant)?,
-        func_list  : decode_list(iter, metadata, decode_function_block)?,
+        func_list  : decode_list(iter, metadata, |iter, metadata| Ok(Rc::new(decode_function_block(iter, metadata)?)))?,
         lines_list : decode_lines_list(iter, metadata)?,
         local_list : decode_list(iter, metadata, decode_local_variable)?,
-        upvalues_list : decode_upvalues_list(iter, metadata)?,
-        // we assign the identifier to the function in the interpreter
-        identifier : 0
+        upvalues_list : decode_upvalues_list(iter, metadata)?
     };
 
     Ok (res)
 } 
 
-/// parse the compiled file
-fn decode_bytecode(mut iter:IntoIter<u8>, config: Vmconfig) -> Result<Function, DecompileError>{
-    decode_signature(&mut iter)?;
+/// nested functions have the same source as their parent, the compiler does not repeat it
+fn propagate_source(func: &mut Function) {
+    for nested in &mut func.func_list {
+        if let Some(nested) = Rc::get_mut(nested) {
+            if nested.name.is_empty() {
+                nested.name = func.name.clone();
+            }
+            propagate_source(nested);
+        }
+    }
+}
 
-    let dump = config.get_dump();
+/// parse the compiled file, version is the expected version of lua and dump prints the decoded function
+fn decode_bytecode(mut iter:IntoIter<u8>, version: u32, dump: bool) -> Result<Function, DecompileError>{
+    decode_signature(&mut iter)?;
 
     let metadata = decode_metadata(&mut iter)?;
-    verify_metadata(&metadata, config)?;
+    verify_metadata(&metadata, version)?;
 
-    let main = decode_function_block(&mut iter, &metadata)?;
+    let mut main = decode_function_block(&mut iter, &metadata)?;
+    propagate_source(&mut main);
 
     if dump {
         println!("{}", metadata);
@@ -354,7 +390,12 @@ pub fn decompile(config : Vmconfig) -> Result<Function, Box<dyn Error>> {
     // We only need to read each byte once
     let bytecode_iter = fs::read(config.get_path())?.into_iter();
 
-    let main = decode_bytecode(bytecode_iter, config)?;
+    let main = decode_bytecode(bytecode_iter, config.get_ver(), config.get_dump())?;
 
     Ok(main)
+}
+
+/// decodes a chunk compiled by luac 5.1 held in memory
+pub fn decompile_bytes(bytecode : Vec<u8>) -> Result<Function, DecompileError> {
+    decode_bytecode(bytecode.into_iter(), 0x51, false)
 }
\ No newline at end of file
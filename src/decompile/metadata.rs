@@ -14,15 +14,15 @@ pub struct Metadata {
 
 impl fmt::Display for Metadata {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Metadata:\n")?;
-        write!(f, "\tVersion         : {}\n", self.version)?;
-        write!(f, "\tFormat          : {}\n", self.format)?;
-        write!(f, "\tBigendian       : {}\n", self.bigendian)?;
-        write!(f, "\tInteger Size    : {}\n", self.i_size)?;
-        write!(f, "\tUnsigned Size   : {}\n", self.u_size)?;
-        write!(f, "\tInstruction Size: {}\n", self.instr_size)?;
-        write!(f, "\tNumber Size     : {}\n", self.number_size)?;
-        write!(f, "\tInteger Flag    : {}\n", self.int_flag)
+        writeln!(f, "Metadata:")?;
+        writeln!(f, "\tVersion         : {}", self.version)?;
+        writeln!(f, "\tFormat          : {}", self.format)?;
+        writeln!(f, "\tBigendian       : {}", self.bigendian)?;
+        writeln!(f, "\tInteger Size    : {}", self.i_size)?;
+        writeln!(f, "\tUnsigned Size   : {}", self.u_size)?;
+        writeln!(f, "\tInstruction Size: {}", self.instr_size)?;
+        writeln!(f, "\tNumber Size     : {}", self.number_size)?;
+        writeln!(f, "\tInteger Flag    : {}", self.int_flag)
     }
 }
 
@@ -1,48 +1,110 @@
-use crate::interpreter::object::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
 
-// this struct is 
-pub struct CallFrame<'guard> {
-    frame : Vec<Value<'guard>>,
-    // useful for call, when b == 0 the arguments called in a function 
-    // are all the values between a given register and the top of the stack 
+use crate::interpreter::object::{UpValue, Value};
+
+// this struct is
+pub struct CallFrame {
+    frame : Vec<Value>,
+    // registers captured by closures, the value of a captured register lives in its cell
+    captured : Vec<Option<UpValue>>,
+    // arguments passed to a vararg function in addition to its parameters
+    pub varargs : Vec<Value>,
+    // useful for call, when b == 0 the arguments called in a function
+    // are all the values between a given register and the top of the stack
     pub top_stack : usize
 }
 
-impl <'frm> CallFrame<'frm> {
+impl CallFrame {
 
     // Allocating a new stack frame with capacity n
     pub fn with_capacity(n : u8) -> Self {
         CallFrame {
             frame : vec![Value::Nil; n as usize],
+            captured : Vec::new(),
+            varargs : Vec::new(),
             top_stack : 0
         }
     }
 
+    // Allocating a stack frame containing the arguments of a runtime function
+    pub fn from_arguments(args : Vec<Value>) -> Self {
+        CallFrame {
+            top_stack : args.len(),
+            frame : args,
+            captured : Vec::new(),
+            varargs : Vec::new()
+        }
+    }
+
     // Stores a new value in the register with the given index
-    pub fn store(&mut self, index: usize, v : Value<'frm>) {
+    pub fn store(&mut self, index: usize, v : Value) {
         if index >= self.top_stack {
             self.top_stack = index + 1;
         }
-        self.frame[index] = v;
+        if index >= self.frame.len() {
+            self.frame.resize(index + 1, Value::Nil);
+        }
+        match self.captured.get(index) {
+            Some(Some(cell)) => { *cell.borrow_mut() = v; }
+            _ => { self.frame[index] = v; }
+        }
     }
 
     // returns the value store in the register with the given index
-    pub fn load(&self, index: usize) -> Value<'frm> {
-        self.frame[index]
+    // registers outside of the frame contains nil
+    pub fn load(&self, index: usize) -> Value {
+        match self.captured.get(index) {
+            Some(Some(cell)) => { cell.borrow().clone() }
+            _ => { self.frame.get(index).cloned().unwrap_or_default() }
+        }
     }
 
     // Copies the move register
     pub fn move_register(&mut self, a : usize, b : usize) {
         let register_b = self.load(b);
-        self.frame[a] = register_b;
+        match self.captured.get(a) {
+            Some(Some(cell)) => { *cell.borrow_mut() = register_b; }
+            _ => { self.frame[a] = register_b; }
+        }
+    }
+
+    // returns the cell shared between the register and the closures capturing it
+    pub fn capture(&mut self, index: usize) -> UpValue {
+        if index >= self.captured.len() {
+            self.captured.resize(index + 1, None);
+        }
+        match &self.captured[index] {
+            Some(cell) => { Rc::clone(cell) }
+            None => {
+                let cell = Rc::new(RefCell::new(self.load(index)));
+                self.captured[index] = Some(Rc::clone(&cell));
+                cell
+            }
+        }
+    }
+
+    // registers from index a goes out of scope, closures keep their own copy of the values
+    pub fn close_upvalues(&mut self, a : usize) {
+        for i in a..self.captured.len() {
+            if let Some(cell) = self.captured[i].take() {
+                if i < self.frame.len() {
+                    self.frame[i] = cell.borrow().clone();
+                }
+            }
+        }
     }
 
     pub fn len(&self) -> usize {
         self.top_stack
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.top_stack == 0
+    }
+
     pub fn set_length(&mut self, size: usize) {
         self.top_stack = size;
     }
 
-}
\ No newline at end of file
+}
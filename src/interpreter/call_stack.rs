@@ -0,0 +1,193 @@
+use std::rc::Rc;
+
+use crate::interpreter::{debug_info, object::{Closure, Value}};
+
+// maximum size of a chunk name in messages, same as the reference implementation
+const ID_SIZE : usize = 60;
+// number of functions shown at the top and at the bottom of long tracebacks
+const LEVELS_TOP : usize = 12;
+const LEVELS_BOTTOM : usize = 10;
+
+/// Function being evaluated
+pub enum CallInfo {
+    // pc is the index of the next instruction to evaluate, tail_call tells if a tail call replaced the function of the caller
+    Lua { closure : Rc<Closure>, pc : usize, tail_call : bool },
+    // the function value of a runtime function or closure
+    Runtime { func : Value }
+}
+
+impl CallInfo {
+
+    pub fn function(&self) -> Value {
+        match self {
+            CallInfo::Lua { closure, .. } => { Value::LuaFunction(Rc::clone(closure)) }
+            CallInfo::Runtime { func } => { func.clone() }
+        }
+    }
+
+    /// line of the instruction being evaluated, None for runtime functions and functions without line information
+    pub fn current_line(&self) -> Option<u64> {
+        match self {
+            CallInfo::Lua { closure, pc, .. } => { closure.proto.lines_list.get(pc.saturating_sub(1)).copied() }
+            CallInfo::Runtime { .. } => { None }
+        }
+    }
+
+    /// line of the traceback describing the function, name is the name of the function if it has one
+    fn describe(&self, name : Option<String>) -> String {
+        match self {
+            CallInfo::Lua { closure, .. } => {
+                let proto = &closure.proto;
+                let source = chunk_id(&proto.name);
+                let mut res = format!("{}:", source);
+                if let Some(line) = self.current_line() {
+                    res += &format!("{}:", line);
+                }
+                match name {
+                    // the main chunk is the only function defined at line 0
+                    _ if proto.first_line == 0 => { res += " in main chunk" }
+                    Some(name) => { res += &format!(" in function '{}'", name) }
+                    None => { res += &format!(" in function <{}:{}>", source, proto.first_line) }
+                }
+                res
+            }
+            CallInfo::Runtime { .. } => {
+                match name {
+                    Some(name) => { format!("[C]: in function '{}'", name) }
+                    None => { String::from("[C]: ?") }
+                }
+            }
+        }
+    }
+
+}
+
+/** Functions being evaluated, from the main chunk to the running function
+ * it is used to locate errors since the evaluation itself happens on the rust stack
+ */
+#[derive(Default)]
+pub struct CallStack {
+    frames : Vec<CallInfo>
+}
+
+/// returns the name of a chunk as displayed in messages from the source name stored in the bytecode
+pub fn chunk_id(source : &str) -> String {
+    if let Some(name) = source.strip_prefix('=') {
+        return name.chars().take(ID_SIZE - 1).collect();
+    }
+
+    if let Some(file) = source.strip_prefix('@') {
+        let length = file.chars().count();
+        if length < ID_SIZE {
+            return String::from(file);
+        }
+        // the end of the path is kept
+        let end: String = file.chars().skip(length - (ID_SIZE - 4)).collect();
+        return format!("...{}", end);
+    }
+
+    let first_line = source.lines().next().unwrap_or("");
+    let max_length = ID_SIZE - "[string \"...\"]".len() - 1;
+    if first_line.len() < source.len() || first_line.chars().count() > max_length {
+        let truncated: String = first_line.chars().take(max_length).collect();
+        format!("[string \"{}...\"]", truncated)
+    } else {
+        format!("[string \"{}\"]", first_line)
+    }
+}
+
+impl CallStack {
+
+    /// adds a function on top of the stack and returns its depth
+    pub fn push(&mut self, info : CallInfo) -> usize {
+        self.frames.push(info);
+        self.frames.len() - 1
+    }
+
+    pub fn pop(&mut self) {
+        self.frames.pop();
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn set_pc(&mut self, depth : usize, new_pc : usize) {
+        if let Some(CallInfo::Lua { pc, .. }) = self.frames.get_mut(depth) {
+            *pc = new_pc;
+        }
+    }
+
+    /// a tail call replaces the function at the given depth
+    pub fn set_closure(&mut self, depth : usize, new_closure : Rc<Closure>) {
+        if let Some(CallInfo::Lua { closure, pc, tail_call }) = self.frames.get_mut(depth) {
+            *closure = new_closure;
+            *pc = 0;
+            *tail_call = true;
+        }
+    }
+
+    /// returns the function at the given level, 0 is the running function, 1 the function that called it...
+    pub fn get(&self, level : usize) -> Option<&CallInfo> {
+        let depth = self.frames.len().checked_sub(level + 1)?;
+        self.frames.get(depth)
+    }
+
+    /* returns the position of the function at the given level as "chunkname:line: "
+     * the position is empty for runtime functions and functions without line information
+     */
+    pub fn location(&self, level : usize) -> String {
+        match self.get(level) {
+            Some(info @ CallInfo::Lua { closure, .. }) => {
+                match info.current_line() {
+                    Some(line) => { format!("{}:{}: ", chunk_id(&closure.proto.name), line) }
+                    None => { String::new() }
+                }
+            }
+            _ => { String::new() }
+        }
+    }
+
+    /* returns the kind and name of the function at the given level from the instruction of the lua function that called it
+     * a function reached by a tail call has no name, the instruction of the caller called another function
+     */
+    pub fn called_name(&self, level : usize) -> Option<(&'static str, String)> {
+        if let CallInfo::Lua { tail_call: true, .. } = self.get(level)? {
+            return None;
+        }
+        match self.get(level + 1)? {
+            CallInfo::Lua { closure, pc, .. } => { debug_info::called_function_name(&closure.proto, pc.checked_sub(1)?) }
+            CallInfo::Runtime { .. } => { None }
+        }
+    }
+
+    /* returns the traceback of the functions from the given level to the main chunk
+     * functions are named after the variable they were called from, name_of gives the name
+     * of the other functions, long tracebacks only show their ends
+     * as in the reference implementation, the ellipsis of a long traceback replaces the last function of the top part
+     */
+    pub fn traceback(&self, level : usize, name_of : impl Fn(&Value) -> Option<String>) -> String {
+        let mut res = String::from("stack traceback:");
+        let nb_levels = self.frames.len().saturating_sub(level);
+
+        let mut i = 0;
+        while i < nb_levels {
+            if i == LEVELS_TOP - 1 && nb_levels > LEVELS_TOP + LEVELS_BOTTOM {
+                res += "\n\t...";
+                i = nb_levels - LEVELS_BOTTOM;
+            }
+            if let Some(info) = self.get(level + i) {
+                res += "\n\t";
+                let name = self.called_name(level + i).map(|(_, name)| name).or_else(|| name_of(&info.function()));
+                res += &info.describe(name);
+                // the functions replaced by tail calls are not known
+                if let CallInfo::Lua { tail_call: true, .. } = info {
+                    res += "\n\t(tail call): ?";
+                }
+            }
+            i += 1;
+        }
+        res
+    }
+
+}
@@ -0,0 +1,267 @@
+use core::fmt;
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+
+use crate::interpreter::{call_frame::CallFrame, call_stack::CallStack, global_environment::GlobalEnvironment, object::{TableRef, Value}};
+use crate::interpreter::interpreter::{call_value, enter_nested_call, eval_closure, Activation, Completion, InterpreterError};
+use crate::interpreter::runtime_library::{caller_sandbox, type_error, with_location, RuntimeClosure};
+
+/// Errors raised by the functions of the coroutine library
+#[derive(thiserror::Error, Debug)]
+pub enum CoroutineError {
+    #[error("cannot resume {status} coroutine")]
+    ResumeError {
+        status : CoroutineStatus
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CoroutineStatus {
+    /// created or stopped by coroutine.yield
+    Suspended,
+    Running,
+    /// resumed another coroutine
+    Normal,
+    /// its function returned or raised an error
+    Dead
+}
+
+impl fmt::Display for CoroutineStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CoroutineStatus::Suspended => "suspended",
+            CoroutineStatus::Running => "running",
+            CoroutineStatus::Normal => "normal",
+            CoroutineStatus::Dead => "dead"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/** Thread of execution created by coroutine.create, it has its own stack of lua functions
+ * the stack is kept while the coroutine is suspended and evaluated again by resume
+ */
+pub struct Coroutine {
+    state   : RefCell<CoroutineState>,
+    // environment of the sandbox of the function creating the coroutine, its runtime functions run in the sandbox
+    sandbox : Option<TableRef>
+}
+
+struct CoroutineState {
+    status      : CoroutineStatus,
+    // function of the coroutine, until the first resume
+    body        : Option<Value>,
+    // lua functions suspended by coroutine.yield
+    activations : Vec<Activation>,
+    // call stack of the coroutine while it is not running
+    call_stack  : CallStack
+}
+
+impl Coroutine {
+
+    pub fn new(body : Value, sandbox : Option<TableRef>) -> Self {
+        Coroutine {
+            state   : RefCell::new(CoroutineState {
+                status      : CoroutineStatus::Suspended,
+                body        : Some(body),
+                activations : Vec::new(),
+                call_stack  : CallStack::default()
+            }),
+            sandbox
+        }
+    }
+
+    /// environment of the sandbox the coroutine was created in, None when it was created outside of a sandbox
+    pub fn sandbox(&self) -> Option<TableRef> {
+        self.sandbox.clone()
+    }
+
+    pub fn status(&self) -> CoroutineStatus {
+        self.state.borrow().status
+    }
+
+    fn set_status(&self, status : CoroutineStatus) {
+        self.state.borrow_mut().status = status;
+    }
+
+}
+
+impl fmt::Debug for Coroutine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Coroutine")
+    }
+}
+
+/* runs a suspended coroutine until it yields or its function returns, the arguments are given to its function on the first resume
+ * and returned by coroutine.yield on the next ones
+ * the coroutine runs with its own call stack and errors raised inside it are not given to the message handlers of the resumer
+ * the evaluation of the coroutine nests on the stack of the host, it counts toward the C stack limit
+ */
+pub fn resume(env : &mut GlobalEnvironment, coroutine : &Rc<Coroutine>, args : Vec<Value>) -> Result<Vec<Value>, InterpreterError> {
+    let status = coroutine.status();
+    if status != CoroutineStatus::Suspended {
+        return Err(CoroutineError::ResumeError { status }.into());
+    }
+    enter_nested_call(env)?;
+
+    let (body, mut activations, call_stack) = {
+        let mut state = coroutine.state.borrow_mut();
+        state.status = CoroutineStatus::Running;
+        (state.body.take(), mem::take(&mut state.activations), mem::take(&mut state.call_stack))
+    };
+
+    let resumer_stack = mem::replace(&mut env.call_stack, call_stack);
+    env.resumer_calls += resumer_stack.depth();
+    let resumer_handlers = mem::replace(&mut env.error_handlers, vec![Value::Nil]);
+    let resumer = env.coroutine.replace(Rc::clone(coroutine));
+    if let Some(resumer) = &resumer {
+        resumer.set_status(CoroutineStatus::Normal);
+    }
+
+    let res = match body {
+        Some(Value::LuaFunction(body)) => {
+            Activation::new(env, body, args).and_then(|activation| {
+                activations.push(activation);
+                eval_closure(env, &mut activations, true)
+            })
+        }
+        // a runtime function can only be suspended when it is coroutine.yield itself
+        Some(body) => {
+            match call_value(env, body, args) {
+                Ok(values) => { Ok(Completion::Return(values)) }
+                Err(InterpreterError::Yield { values, depth: 0 }) => { Ok(Completion::Yield(values)) }
+                Err(err) => { Err(err) }
+            }
+        }
+        // the runtime function that yielded returns the values given to resume
+        None if activations.is_empty() => { Ok(Completion::Return(args)) }
+        None => {
+            if let Some(activation) = activations.last_mut() {
+                activation.complete_call(args);
+            }
+            eval_closure(env, &mut activations, true)
+        }
+    };
+
+    if let Some(resumer) = &resumer {
+        resumer.set_status(CoroutineStatus::Running);
+    }
+    env.coroutine = resumer;
+    env.error_handlers = resumer_handlers;
+    env.nested_calls -= 1;
+    let call_stack = mem::replace(&mut env.call_stack, resumer_stack);
+    env.resumer_calls -= env.call_stack.depth();
+
+    let mut state = coroutine.state.borrow_mut();
+    match res {
+        Ok(Completion::Yield(values)) => {
+            state.status = CoroutineStatus::Suspended;
+            state.activations = activations;
+            state.call_stack = call_stack;
+            Ok(values)
+        }
+        Ok(Completion::Return(values)) => {
+            state.status = CoroutineStatus::Dead;
+            Ok(values)
+        }
+        Err(err) => {
+            state.status = CoroutineStatus::Dead;
+            Err(err)
+        }
+    }
+}
+
+/// returns the coroutine passed as the argument number i (starting from 0) of the runtime function
+fn check_coroutine(frame : &CallFrame, i : usize, func : &'static str) -> Result<Rc<Coroutine>, InterpreterError> {
+    match frame.load(i) {
+        Value::Thread(co) => { Ok(co) }
+        _ => { Err(InterpreterError::ArgumentError { arg: i + 1, func, msg: String::from("coroutine expected") }) }
+    }
+}
+
+/// returns the function passed as the argument number i (starting from 0) of the runtime function
+fn check_function(frame : &CallFrame, i : usize, func : &'static str) -> Result<Value, InterpreterError> {
+    match frame.load(i) {
+        body @ (Value::LuaFunction(_) | Value::RuntimeFunction(_) | Value::RuntimeClosure(_)) => { Ok(body) }
+        _ => { Err(type_error(frame, i, func, "function")) }
+    }
+}
+
+/// coroutine.create(f) returns a new suspended coroutine running f
+pub fn create_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let body = check_function(&frame, 0, "create")?;
+    returned_values.push(Value::Thread(Rc::new(Coroutine::new(body, caller_sandbox(env)))));
+    Ok(())
+}
+
+/* coroutine.resume(co, ...) runs the coroutine co with the given values
+ * returns true followed by the values given to coroutine.yield or returned by its function,
+ * or false and the error object when the coroutine raised an error or can't be resumed
+ */
+pub fn resume_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let coroutine = check_coroutine(&frame, 0, "resume")?;
+    let args = (1..frame.len()).map(|i| frame.load(i)).collect();
+
+    match resume(env, &coroutine, args) {
+        Ok(values) => {
+            returned_values.push(Value::Boolean(true));
+            returned_values.extend(values);
+        }
+        Err(err) if err.is_fatal() => { return Err(err) }
+        Err(err) => {
+            returned_values.push(Value::Boolean(false));
+            returned_values.push(err.to_value());
+        }
+    }
+    Ok(())
+}
+
+/* coroutine.yield(...) suspends the running coroutine, its arguments are returned by resume
+ * the evaluation loop of the coroutine stops on the yield, it returns the values given to the next resume
+ */
+pub fn yield_lua(frame : CallFrame, _ : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let values = (0..frame.len()).map(|i| frame.load(i)).collect();
+    Err(InterpreterError::Yield { values, depth: env.call_stack.depth() - 1 })
+}
+
+/// coroutine.status(co) returns "suspended", "running", "normal" or "dead"
+pub fn status_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let coroutine = check_coroutine(&frame, 0, "status")?;
+    returned_values.push(Value::from_string(&coroutine.status().to_string()));
+    Ok(())
+}
+
+/// coroutine.running() returns the running coroutine, nil when called from the main program
+pub fn running_lua(_ : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    returned_values.push(env.coroutine.clone().map(Value::Thread).unwrap_or_default());
+    Ok(())
+}
+
+/* coroutine.wrap(f) returns a function resuming a new coroutine running f, it returns the values given by the coroutine
+ * errors of the coroutine are propagated, string messages are prefixed by the position of the call
+ */
+pub fn wrap_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let coroutine = Rc::new(Coroutine::new(check_function(&frame, 0, "wrap")?, caller_sandbox(env)));
+    returned_values.push(RuntimeClosure::new_value(move |frame, returned_values, env| {
+        let args = (0..frame.len()).map(|i| frame.load(i)).collect();
+        match resume(env, &coroutine, args) {
+            Ok(values) => {
+                returned_values.extend(values);
+                Ok(())
+            }
+            Err(err) if err.is_fatal() => { Err(err) }
+            Err(err) => {
+                let value = match err.to_value() {
+                    value @ (Value::LuaString(_) | Value::Number(_)) => {
+                        let message = value.to_lua_string().unwrap_or_default();
+                        with_location(env, 1, &message)
+                    }
+                    value => { value }
+                };
+                Err(InterpreterError::RaisedError { value })
+            }
+        }
+    }));
+    Ok(())
+}
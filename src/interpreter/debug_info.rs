@@ -0,0 +1,90 @@
+use crate::structure::{constant::Constant, function::Function, instruction::Instruction};
+
+/* returns the index of the last instruction before lastpc storing a value in the register
+ * None when there is none or when it can't be known because of a conditional jump
+ */
+fn find_set_register(proto : &Function, lastpc : usize, register : usize) -> Option<usize> {
+    let mut set_register = None;
+    // instructions before the target of a forward jump are not always evaluated
+    let mut jump_target = 0;
+
+    for (pc, instr) in proto.instr_list.iter().enumerate().take(lastpc) {
+        let sets_register = match *instr {
+            Instruction::LoadNil(a, b, _) => { a <= register && register <= b }
+            Instruction::TForLoop(a, _, _) => { register >= a + 2 }
+            Instruction::Call(a, _, _) | Instruction::TailCall(a, _, _) => { register >= a }
+            Instruction::Jmp(_, b) => {
+                let target = (pc as isize + 1 + b) as usize;
+                if pc < target && target <= lastpc && target > jump_target {
+                    jump_target = target;
+                }
+                false
+            }
+            Instruction::Move(a, _, _) | Instruction::LoadK(a, _) | Instruction::LoadBool(a, _, _)
+            | Instruction::GetUpVal(a, _, _) | Instruction::GetGlobal(a, _) | Instruction::GetTable(a, _, _)
+            | Instruction::NewTable(a, _, _) | Instruction::SelF(a, _, _) | Instruction::Add(a, _, _)
+            | Instruction::Sub(a, _, _) | Instruction::Mul(a, _, _) | Instruction::Div(a, _, _)
+            | Instruction::Mod(a, _, _) | Instruction::Pow(a, _, _) | Instruction::Unm(a, _, _)
+            | Instruction::Not(a, _, _) | Instruction::Len(a, _, _) | Instruction::Concat(a, _, _)
+            | Instruction::TestSet(a, _, _) | Instruction::ForLoop(a, _) | Instruction::ForPrep(a, _)
+            | Instruction::Closure(a, _) | Instruction::VarArg(a, _, _) => { register == a }
+            _ => { false }
+        };
+
+        if sets_register {
+            set_register = if pc < jump_target { None } else { Some(pc) };
+        }
+    }
+
+    set_register
+}
+
+/// name of a constant used as a key, "?" if it is not a string
+fn constant_name(proto : &Function, rk : usize) -> String {
+    match proto.const_list.get(rk.wrapping_sub(256)) {
+        Some(Constant::String(s)) if rk >= 256 => { String::from_utf8_lossy(s).into_owned() }
+        _ => { String::from("?") }
+    }
+}
+
+/* returns how the value in the register was obtained when the instruction at index pc is evaluated,
+ * as a kind ("global", "local", "field", "upvalue" or "method") and a name
+ * used to name the values in error messages, as getobjname in the reference implementation
+ */
+pub fn object_name(proto : &Function, pc : usize, register : usize) -> Option<(&'static str, String)> {
+    if let Some(name) = proto.local_name(register, pc) {
+        return Some(("local", String::from(name)));
+    }
+
+    let set_pc = find_set_register(proto, pc, register)?;
+    match proto.instr_list[set_pc] {
+        Instruction::GetGlobal(_, b) => {
+            Some(("global", constant_name(proto, b + 256)))
+        }
+        Instruction::Move(a, b, _) if b < a => {
+            object_name(proto, set_pc, b)
+        }
+        Instruction::GetTable(_, _, c) => {
+            Some(("field", constant_name(proto, c)))
+        }
+        Instruction::GetUpVal(_, b, _) => {
+            let name = proto.upvalues_list.get(b).cloned().unwrap_or_else(|| String::from("?"));
+            Some(("upvalue", name))
+        }
+        Instruction::SelF(_, _, c) => {
+            Some(("method", constant_name(proto, c)))
+        }
+        _ => { None }
+    }
+}
+
+/// kind and name of the function called by the instruction at index pc, used by tracebacks and argument errors
+pub fn called_function_name(proto : &Function, pc : usize) -> Option<(&'static str, String)> {
+    match proto.instr_list.get(pc)? {
+        // the iterator of a generic for is called by TForLoop
+        Instruction::Call(a, _, _) | Instruction::TailCall(a, _, _) | Instruction::TForLoop(a, _, _) => {
+            object_name(proto, pc, *a)
+        }
+        _ => { None }
+    }
+}
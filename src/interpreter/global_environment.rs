@@ -1,44 +1,268 @@
+use crate::interpreter::{call_stack::CallStack, coroutine_library::{self, Coroutine}, io_library, limits::Budget, os_library, output::Output, sandbox::Capabilities, math_library::{self, Random}, object::{LuaStr, TableRef, Value}, runtime_library, string_library, table::Table, table_library, userdata::{FinalizerQueue, UserData}};
+use std::any::TypeId;
+use std::cell::RefCell;
 use std::collections::HashMap;
-use crate::interpreter::{object::Value, runtime_library};
+use std::rc::{Rc, Weak};
 
-#[derive(thiserror::Error, Debug)]
-pub enum EnvironmentError {
-    #[error("Symbol {sym} not found")]
-    GlobalNotFound {
-        sym : String
-    }
-}
-
-/** Table where global variables are stored during interpretation */
-pub struct GlobalEnvironment<'ge> {
-    global_map: HashMap<String, Value<'ge>>
+/** State of the interpreter: the table where global variables are stored and the functions being evaluated */
+pub struct GlobalEnvironment {
+    // default environment of the functions, also reachable from the scripts as _G
+    globals : TableRef,
+    // functions being evaluated
+    pub call_stack : CallStack,
+    // message handlers of the active protected calls, nil for pcall
+    pub error_handlers : Vec<Value>,
+    // calls of function values nested on the stack of the host, kept across the coroutines
+    pub nested_calls : usize,
+    // message handlers being called, they can nest more calls to handle a C stack overflow
+    pub handling_errors : usize,
+    // functions in the call stacks of the coroutines resuming the running one, they count toward the call depth limit
+    pub resumer_calls : usize,
+    // metatable shared by all strings, its __index field is the string library
+    pub string_metatable : Option<TableRef>,
+    // generator of math.random
+    pub random : Random,
+    // coroutine being evaluated, none for the main program
+    pub coroutine : Option<Rc<Coroutine>>,
+    // metatables given to the userdata created by the host, by type of value
+    pub userdata_metatables : HashMap<TypeId, TableRef>,
+    // released userdata waiting for their __gc handler
+    pub finalizers : FinalizerQueue,
+    // written by print, io.write and io.stdout
+    pub output : Output,
+    // written by io.stderr and the reports of the errors not caught by the script
+    pub error_output : Output,
+    // resources used by the current run and their limits
+    pub budget : Budget,
+    // environments of the sandboxed chunks
+    sandboxes : Vec<Weak<RefCell<Table>>>
 }
 
-impl <'ge> GlobalEnvironment<'ge> {
+impl GlobalEnvironment {
 
-    /* Creates a new global environment containing the function from the runtime library*/
-    pub fn new() -> Self {
+    /* Creates a new global environment containing the function from the runtime library
+     * the io and os libraries only contain the functions allowed by the capabilities
+     */
+    pub fn new(capabilities : &Capabilities) -> Self {
         let mut res = GlobalEnvironment { 
-            global_map : HashMap::new() 
+            globals : Rc::new(RefCell::new(Table::new())),
+            call_stack : CallStack::default(),
+            error_handlers : Vec::new(),
+            nested_calls : 0,
+            handling_errors : 0,
+            resumer_calls : 0,
+            string_metatable : None,
+            random : Random::default(),
+            coroutine : None,
+            userdata_metatables : HashMap::new(),
+            finalizers : FinalizerQueue::default(),
+            output : Output::stdout(),
+            error_output : Output::stderr(),
+            budget : Budget::default(),
+            sandboxes : Vec::new()
         };
 
-        // Adding runtime function in the HashTable
-        res.global_map.insert(String::from("print"), Value::RuntimeFunction(runtime_library::print_lua));
+        // Adding runtime function in the global table
+        res.register("print", runtime_library::print_lua);
+        res.register("type", runtime_library::type_lua);
+        res.register("tostring", runtime_library::tostring_lua);
+        res.register("tonumber", runtime_library::tonumber_lua);
+        res.register("rawget", runtime_library::rawget_lua);
+        res.register("rawset", runtime_library::rawset_lua);
+        res.register("rawequal", runtime_library::rawequal_lua);
+        res.register("setmetatable", runtime_library::setmetatable_lua);
+        res.register("getmetatable", runtime_library::getmetatable_lua);
+        res.register("getfenv", runtime_library::getfenv_lua);
+        res.register("setfenv", runtime_library::setfenv_lua);
+        res.register("unpack", runtime_library::unpack_lua);
+        res.register("select", runtime_library::select_lua);
+        res.register("next", runtime_library::next_lua);
+        res.register("pairs", runtime_library::pairs_lua);
+        res.register("ipairs", runtime_library::ipairs_lua);
+        res.register("error", runtime_library::error_lua);
+        res.register("assert", runtime_library::assert_lua);
+        res.register("pcall", runtime_library::pcall_lua);
+        res.register("xpcall", runtime_library::xpcall_lua);
+        res.register("collectgarbage", runtime_library::collectgarbage_lua);
+
+        res.insert_global(LuaStr::from(&b"_G"[..]), Value::Table(Rc::clone(&res.globals)));
+        res.insert_global(LuaStr::from(&b"_VERSION"[..]), Value::from_string("Lua 5.1"));
+
+        res.register_library("debug", &[
+            ("traceback", runtime_library::traceback_lua)
+        ]);
+
+        res.register_library("coroutine", &[
+            ("create", coroutine_library::create_lua),
+            ("resume", coroutine_library::resume_lua),
+            ("yield", coroutine_library::yield_lua),
+            ("status", coroutine_library::status_lua),
+            ("running", coroutine_library::running_lua),
+            ("wrap", coroutine_library::wrap_lua)
+        ]);
+
+        res.register_library("table", &[
+            ("insert", table_library::insert_lua),
+            ("remove", table_library::remove_lua),
+            ("concat", table_library::concat_lua),
+            ("sort", table_library::sort_lua),
+            ("maxn", table_library::maxn_lua),
+            ("getn", table_library::getn_lua),
+            ("setn", table_library::setn_lua)
+        ]);
+
+        let math = res.register_library("math", &[
+            ("floor", math_library::floor_lua),
+            ("ceil", math_library::ceil_lua),
+            ("abs", math_library::abs_lua),
+            ("sqrt", math_library::sqrt_lua),
+            ("sin", math_library::sin_lua),
+            ("cos", math_library::cos_lua),
+            ("tan", math_library::tan_lua),
+            ("asin", math_library::asin_lua),
+            ("acos", math_library::acos_lua),
+            ("atan", math_library::atan_lua),
+            ("atan2", math_library::atan2_lua),
+            ("exp", math_library::exp_lua),
+            ("log", math_library::log_lua),
+            ("log10", math_library::log10_lua),
+            ("pow", math_library::pow_lua),
+            ("fmod", math_library::fmod_lua),
+            ("modf", math_library::modf_lua),
+            ("frexp", math_library::frexp_lua),
+            ("ldexp", math_library::ldexp_lua),
+            ("min", math_library::min_lua),
+            ("max", math_library::max_lua),
+            ("random", math_library::random_lua),
+            ("randomseed", math_library::randomseed_lua)
+        ]);
+        math.borrow_mut().set(Value::from_string("huge"), Value::Number(f64::INFINITY))
+            .expect("constant names are strings");
+        math.borrow_mut().set(Value::from_string("pi"), Value::Number(std::f64::consts::PI))
+            .expect("constant names are strings");
+
+        let io = io_library::new_library(&mut res, capabilities);
+        res.insert_global(LuaStr::from(&b"io"[..]), Value::Table(io));
+        res.insert_global(LuaStr::from(&b"os"[..]), Value::Table(os_library::new_library(capabilities)));
+
+        let string = res.register_library("string", &[
+            ("len", string_library::len_lua),
+            ("sub", string_library::sub_lua),
+            ("upper", string_library::upper_lua),
+            ("lower", string_library::lower_lua),
+            ("rep", string_library::rep_lua),
+            ("reverse", string_library::reverse_lua),
+            ("byte", string_library::byte_lua),
+            ("char", string_library::char_lua),
+            ("find", string_library::find_lua),
+            ("match", string_library::match_lua),
+            ("gmatch", string_library::gmatch_lua),
+            ("gsub", string_library::gsub_lua),
+            ("format", string_library::format_lua)
+        ]);
+        // methods of strings are the functions of the string library, as in s:upper()
+        let mut string_metatable = Table::new();
+        string_metatable.set(Value::from_string("__index"), Value::Table(string))
+            .expect("metamethod names are strings");
+        res.string_metatable = Some(Rc::new(RefCell::new(string_metatable)));
 
         res
     }
 
-    pub fn insert_global(&mut self, key : &String, val : Value<'ge>) {
-        self.global_map.insert(String::from(key), val);
+    fn register(&mut self, name : &str, func : runtime_library::RuntimeFunction) {
+        self.insert_global(LuaStr::from(name.as_bytes()), Value::RuntimeFunction(func));
+    }
+
+    /// stores the functions of a library in a table named after the library and returns the table
+    fn register_library(&mut self, name : &str, functions : &[(&str, runtime_library::RuntimeFunction)]) -> TableRef {
+        let mut library = Table::with_capacity(0, functions.len());
+        for (func_name, func) in functions {
+            library.set(Value::from_string(func_name), Value::RuntimeFunction(*func))
+                .expect("library functions have string keys");
+        }
+        let library = Rc::new(RefCell::new(library));
+        self.insert_global(LuaStr::from(name.as_bytes()), Value::Table(Rc::clone(&library)));
+        library
+    }
+
+    /* returns the name under which a function is reachable from the global variables, used by tracebacks
+     * global functions are named after their variable and library functions as "library.function",
+     * global variables are preferred and the first name in the traversal order of the tables is used
+     */
+    pub fn function_name(&self, func : &Value) -> Option<String> {
+        let globals = self.globals.borrow();
+        let mut library_name = None;
+
+        let mut global = Value::Nil;
+        while let Ok(Some((key, val))) = globals.next(&global) {
+            if let Value::LuaString(name) = &key {
+                if &val == func {
+                    return Some(String::from_utf8_lossy(name).into_owned());
+                }
+                match &val {
+                    // _G would name every global function
+                    Value::Table(library) if library_name.is_none() && !Rc::ptr_eq(library, &self.globals) => {
+                        let library = library.borrow();
+                        let mut field = Value::Nil;
+                        while let Ok(Some((field_key, field_val))) = library.next(&field) {
+                            if let (true, Value::LuaString(field_name)) = (&field_val == func, &field_key) {
+                                library_name = Some(format!("{}.{}", String::from_utf8_lossy(name), String::from_utf8_lossy(field_name)));
+                                break;
+                            }
+                            field = field_key;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            global = key;
+        }
+        library_name
+    }
+
+    /// returns the table of the global variables
+    pub fn globals(&self) -> TableRef {
+        Rc::clone(&self.globals)
+    }
+
+    /// replaces the table of the global variables, used by setfenv(0, t)
+    pub fn set_globals(&mut self, globals : TableRef) {
+        self.globals = globals;
     }
 
-    pub fn get_global(&self, key : &String) -> Result<Value<'ge>, EnvironmentError> {
-        let val = self.global_map.get(key);
-        
-        match val {
-            Some(v) => { Ok(*v) }
-            None => { return Err(EnvironmentError::GlobalNotFound { sym: String::from(key) }) }
+    /// marks a table as the environment of sandboxed functions
+    pub fn register_sandbox(&mut self, table : &TableRef) {
+        self.sandboxes.retain(|sandbox| sandbox.strong_count() > 0);
+        if !self.is_sandbox(table) {
+            self.sandboxes.push(Rc::downgrade(table));
         }
     }
 
+    pub fn is_sandbox(&self, table : &TableRef) -> bool {
+        self.sandboxes.iter().any(|sandbox| std::ptr::eq(sandbox.as_ptr(), Rc::as_ptr(table)))
+    }
+
+    /// sets a global variable without calling any metamethod
+    pub fn insert_global(&mut self, key : LuaStr, val : Value) {
+        self.globals.borrow_mut().set(Value::LuaString(key), val)
+            .expect("global names are strings");
+    }
+
+    /// wraps a value in a userdata with the metatable registered for its type
+    pub fn new_userdata<T : 'static>(&self, value : T) -> Rc<UserData> {
+        let metatable = self.userdata_metatables.get(&TypeId::of::<T>()).cloned();
+        Rc::new(UserData::with_finalizers(value, metatable, &self.finalizers))
+    }
+
+    /// returns the value of a global variable without calling any metamethod, undefined variables are nil
+    pub fn get_global(&self, key : &LuaStr) -> Value {
+        self.globals.borrow().get(&Value::LuaString(Rc::clone(key)))
+    }
+
+}
+
+impl Default for GlobalEnvironment {
+    fn default() -> Self {
+        Self::new(&Capabilities::default())
+    }
 }
@@ -1,35 +1,328 @@
-use crate::interpreter::{global_environment::{EnvironmentError, GlobalEnvironment}, call_frame::CallFrame};
+use crate::interpreter::{global_environment::GlobalEnvironment, call_frame::CallFrame, call_stack::CallInfo};
+use crate::interpreter::metatable::{self, MetaMethod};
+use crate::interpreter::{debug_info, runtime_library};
+use crate::interpreter::math_library::Random;
+use crate::interpreter::table::{Table, TableError};
+use crate::interpreter::pattern::PatternError;
+use crate::interpreter::printf::FormatError;
+use crate::interpreter::table_library::TableLibraryError;
+use crate::interpreter::io_library::IoLibraryError;
+use crate::interpreter::os_library::OsLibraryError;
+use crate::interpreter::coroutine_library::CoroutineError;
+use crate::interpreter::limits::{Budget, Limit, Limits};
+use crate::interpreter::output::Output;
+use crate::interpreter::sandbox::Capabilities;
 use crate::structure::{function::Function, instruction::Instruction};
-use crate::interpreter::object::Value;
+use crate::interpreter::object::{Closure, LuaStr, TypeLua, Value};
+use std::cell::RefCell;
 use std::error::Error;
+use std::io::Write;
+use std::rc::Rc;
 
+// Number of array elements stored by one SetList instruction
+const FIELDS_PER_FLUSH : usize = 50;
+
+// Number of functions in the call stack above which a call raises a stack overflow, as in the reference implementation
+const MAX_CALLS : usize = 20000;
+
+/* Number of nested calls of function values above which a call raises a C stack overflow, LUAI_MAXCCALLS of the reference implementation
+ * each of them evaluates the function on the stack of the host: pcall, metamethods, comparators of sort, iterators...
+ * message handlers get an eighth more to report the error
+ */
+const MAX_NESTED_CALLS : usize = 200;
+
+/// Lua function being evaluated with its stack frame, its program counter and its position in the call stack
+pub struct Activation {
+    closure : Rc<Closure>,
+    frame   : CallFrame,
+    pc      : usize,
+    depth   : usize
+}
+
+impl Activation {
+
+    /// prepares the call of a closure with the given arguments and pushes it on the call stack
+    pub fn new(env : &mut GlobalEnvironment, closure : Rc<Closure>, args : Vec<Value>) -> Result<Self, InterpreterError> {
+        if env.call_stack.depth() >= MAX_CALLS {
+            return Err(InterpreterError::StackOverflowError);
+        }
+        env.budget.check_call_depth(env.resumer_calls + env.call_stack.depth())?;
+        let frame = prepare_frame(&closure.proto, args)?;
+        let depth = env.call_stack.push(CallInfo::Lua { closure: Rc::clone(&closure), pc: 0, tail_call: false });
+        Ok(Activation { closure, frame, pc: 0, depth })
+    }
+
+    /* stores the results of the call instruction the function is waiting on,
+     * a tail call is completed by the return instruction following it
+     */
+    pub fn complete_call(&mut self, returned_values : Vec<Value>) {
+        match self.closure.proto.instr_list[self.pc - 1] {
+            Instruction::Call(a, _, c) => { store_results(&mut self.frame, a, c, returned_values) }
+            Instruction::TailCall(a, _, _) => { store_results(&mut self.frame, a, 0, returned_values) }
+            _ => {}
+        }
+    }
+
+}
+
+/// How the evaluation of activations ended
+pub enum Completion {
+    /// the first function returned these values
+    Return(Vec<Value>),
+    /// coroutine.yield suspended the activations of a coroutine and gave these values to resume
+    Yield(Vec<Value>)
+}
+
+/// call of a lua function requested by an instruction, it is evaluated by the loop of eval_closure
+enum LuaCall {
+    /// the results of the called function are stored in the registers of the caller
+    Call(Rc<Closure>, Vec<Value>),
+    /// the called function replaces the current one
+    TailCall(Rc<Closure>, Vec<Value>)
+}
+
+/// Errors raised while running a script, their messages are the ones of the reference implementation
 #[derive(thiserror::Error, Debug)]
 pub enum InterpreterError {
-    #[error("Heap Error: ")]
-    GlobalError {
+    #[error("{table_error}")]
+    TableError {
+        #[from]
+        table_error : TableError
+    },
+    #[error("{pattern_error}")]
+    PatternError {
+        #[from]
+        pattern_error : PatternError
+    },
+    #[error("{format_error}")]
+    FormatError {
+        #[from]
+        format_error : FormatError
+    },
+    #[error("{table_library_error}")]
+    TableLibraryError {
         #[from]
-        heap_error : EnvironmentError
+        table_library_error : TableLibraryError
+    },
+    #[error("{io_library_error}")]
+    IoLibraryError {
+        #[from]
+        io_library_error : IoLibraryError
+    },
+    #[error("{os_library_error}")]
+    OsLibraryError {
+        #[from]
+        os_library_error : OsLibraryError
+    },
+    #[error("{coroutine_error}")]
+    CoroutineError {
+        #[from]
+        coroutine_error : CoroutineError
+    },
+    #[error("wrong number of arguments")]
+    RandomArgumentsError,
+    #[error("invalid replacement value (a {typ})")]
+    ReplacementError {
+        typ : TypeLua
+    },
+    #[error("attempt to call a {typ} value")]
+    CallError {
+        typ : TypeLua
     },
-    #[error("Return not caught")]
-    ReturnError,
-    #[error("Dividing by zero")]
-    ZeroDivisionError,
-    #[error("Object is not callable")]
-    NotCallableError,
-    #[error("Object can't be tail called")]
-    TailCallError,
     #[error("Error while making closure")]
-    ClosureError
+    ClosureError,
+    #[error("global name is not a string")]
+    GlobalNameError,
+    #[error("'for' {operand} must be a number")]
+    ForLoopError {
+        operand : &'static str
+    },
+    #[error("attempt to perform arithmetic on a {typ} value")]
+    ArithmeticError {
+        typ : TypeLua
+    },
+    #[error("attempt to concatenate a {typ} value")]
+    ConcatError {
+        typ : TypeLua
+    },
+    #[error("attempt to index a {typ} value")]
+    IndexError {
+        typ : TypeLua
+    },
+    #[error("attempt to get length of a {typ} value")]
+    LengthError {
+        typ : TypeLua
+    },
+    #[error("attempt to compare {operands}")]
+    CompareError {
+        operands : String
+    },
+    #[error("loop in {event}")]
+    MetaLoopError {
+        event : &'static str
+    },
+    #[error("bad argument #{arg} to '{func}' ({msg})")]
+    ArgumentError {
+        arg  : usize,
+        func : &'static str,
+        msg  : String
+    },
+    // argument of a host function that can't be converted, the function is named when the error is raised
+    #[error("bad argument #{arg} ({msg})")]
+    HostArgumentError {
+        arg : usize,
+        msg : String
+    },
+    // lua value that can't be converted to a value of the host application
+    #[error("{expected} expected, got {got}")]
+    ConversionError {
+        expected : &'static str,
+        got      : String
+    },
+    #[error("stack overflow")]
+    StackOverflowError,
+    #[error("C stack overflow")]
+    CStackOverflowError,
+    // the scripts used more of a resource than the limits of the state allow
+    #[error("{limit} limit exceeded")]
+    LimitError {
+        limit : Limit
+    },
+    // error raised by a function of the host application
+    #[error("{message}")]
+    HostError {
+        message : String
+    },
+    #[error("host function called recursively")]
+    RecursiveHostCallError,
+    #[error("cannot change a protected metatable")]
+    ProtectedMetatableError,
+    #[error("'tostring' must return a string to 'print'")]
+    ToStringError,
+    #[error("'setfenv' cannot change environment of given object")]
+    SetEnvironmentError,
+    #[error("attempt to modify a read-only table")]
+    ReadOnlyError,
+    #[error("too many results to unpack")]
+    UnpackError,
+    #[error("resulting string too large")]
+    StringSizeError,
+    // error object given to the function error, the message handler has not seen it yet
+    #[error("{}", error_message(.value))]
+    RaisedError {
+        value : Value
+    },
+    // error on a value named after the variable it was read from
+    #[error("{error} ({kind} '{name}')")]
+    NamedError {
+        error : Box<InterpreterError>,
+        kind  : &'static str,
+        name  : String
+    },
+    // error object propagated to the nearest protected call
+    #[error("{}", error_message(.value))]
+    LuaError {
+        value : Value
+    },
+    // values given to resume by coroutine.yield, called at the given depth of the call stack
+    // the message is the error it becomes when nothing can suspend the caller
+    #[error("attempt to yield across metamethod/C-call boundary")]
+    Yield {
+        values : Vec<Value>,
+        depth  : usize
+    }
+}
+
+/// message displayed for an error object, error objects can be any lua value
+fn error_message(value : &Value) -> String {
+    match value.to_lua_string() {
+        Some(s) => { String::from_utf8_lossy(&s).into_owned() }
+        None => { format!("(error object is a {} value)", value.get_type()) }
+    }
+}
+
+impl InterpreterError {
+
+    /// returns the error object seen by the script, the message of the error unless a value was raised
+    pub fn to_value(&self) -> Value {
+        match self {
+            InterpreterError::RaisedError { value } | InterpreterError::LuaError { value } => { value.clone() }
+            _ => { Value::from_string(&self.to_string()) }
+        }
+    }
+
+    /// the error stops the scripts, the protected calls don't catch it
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, InterpreterError::LimitError { limit } if limit.is_fatal())
+    }
+
+}
+
+/* turns an error raised by the running function into the error object propagated to the protected calls
+ * messages of the interpreter are prefixed with the position of the lua function raising them,
+ * errors of runtime functions are located in the function calling them
+ * the message handler of the innermost xpcall runs here, before the call stack is unwound
+ * errors already propagated by a called function, yields and fatal errors are returned as is
+ */
+pub fn raise_error(env : &mut GlobalEnvironment, err : InterpreterError) -> InterpreterError {
+    let value = match err {
+        InterpreterError::LuaError { .. } | InterpreterError::Yield { .. } => { return err }
+        _ if err.is_fatal() => { return err }
+        InterpreterError::RaisedError { value } => { value }
+        _ => {
+            let level = match env.call_stack.get(0) {
+                Some(CallInfo::Runtime { .. }) => { 1 }
+                _ => { 0 }
+            };
+            let location = env.call_stack.location(level);
+            let message = match err {
+                InterpreterError::HostArgumentError { arg, msg } => { argument_message(env, arg, &msg) }
+                _ => { err.to_string() }
+            };
+            Value::from_string(&format!("{}{}", location, message))
+        }
+    };
+
+    let handler = env.error_handlers.last().cloned().unwrap_or_default();
+    if handler.is_nil() {
+        return InterpreterError::LuaError { value };
+    }
+
+    // errors raised by the handler are not handled again
+    env.error_handlers.push(Value::Nil);
+    env.handling_errors += 1;
+    let res = call_value(env, handler, vec![value]);
+    env.handling_errors -= 1;
+    env.error_handlers.pop();
+
+    let value = match res {
+        Ok(results) => { results.into_iter().next().unwrap_or_default() }
+        Err(err) if err.is_fatal() => { return err }
+        Err(_) => { Value::from_string("error in error handling") }
+    };
+    InterpreterError::LuaError { value }
+}
+
+/* message of an argument error raised by the running host function, named after the variable it was called from
+ * the object of a method call is not counted as an argument, as in the reference implementation
+ */
+fn argument_message(env : &GlobalEnvironment, arg : usize, msg : &str) -> String {
+    match env.call_stack.called_name(0) {
+        Some(("method", name)) if arg == 1 => { format!("calling '{}' on bad self ({})", name, msg) }
+        Some(("method", name)) => { format!("bad argument #{} to '{}' ({})", arg - 1, name, msg) }
+        Some((_, name)) => { format!("bad argument #{} to '{}' ({})", arg, name, msg) }
+        None => { format!("bad argument #{} to '?' ({})", arg, msg) }
+    }
 }
 
 /// store the bth constant in the constant list of the current function in the ath register of the current frame
-fn load_k<'frm>(func: &'frm Function, frame: &mut CallFrame<'frm>, a : usize, b : usize) {
+fn load_k(func: &Function, frame: &mut CallFrame, a : usize, b : usize) {
     let constant = func.const_list[b].as_value();
     frame.store(a, constant);
 }
 
 /// store a boolean in the current stack frame, skips the next instruction if c is superior to 0
-fn load_bool(frame : &mut CallFrame<'_>, a : usize, b : usize, c : usize, pc: &mut usize) {
+fn load_bool(frame : &mut CallFrame, a : usize, b : usize, c : usize, pc: &mut usize) {
     frame.store(a, Value::Boolean(b > 0));
     if c > 0 {
         *pc += 1;
@@ -37,8 +330,8 @@ fn load_bool(frame : &mut CallFrame<'_>, a : usize, b : usize, c : usize, pc: &m
 }
 
 /// fils the frame with Nil between index a and b
-fn load_nil(frame : &mut CallFrame<'_>, a : usize, b : usize) {
-    
+fn load_nil(frame : &mut CallFrame, a : usize, b : usize) {
+
     for i in a..=b {
         frame.store(i, Value::Nil);
     }
@@ -46,13 +339,13 @@ fn load_nil(frame : &mut CallFrame<'_>, a : usize, b : usize) {
 }
 
 /// The value in register b is copied in register a
-fn move_operation(frame: &mut CallFrame<'_>, a : usize, b : usize) {
+fn move_operation(frame: &mut CallFrame, a : usize, b : usize) {
     frame.move_register(a, b);
 }
 
 /// returns the value stored in register r of the current stack frame if r < 256
 /// returns a constant from the constant list of the current function
-fn get_rk<'frm>(func : &'frm Function, frame : &CallFrame<'frm>, r : usize) -> Value<'frm> {
+fn get_rk(func : &Function, frame : &CallFrame, r : usize) -> Value {
     if r >= 256 {
         func.const_list[r % 256].as_value()
     } else {
@@ -65,67 +358,125 @@ fn get_rk<'frm>(func : &'frm Function, frame : &CallFrame<'frm>, r : usize) -> V
  * instr : instruction to evaluate
  * frame : current stack frame
 */
-fn arithmetic_operation<'frm>(
-    func : &'frm Function, 
-    instr : &Instruction, 
-    frame : &mut CallFrame<'frm>, 
-    a : usize, 
-    b : usize, 
-    c : usize) 
+fn arithmetic_operation(
+    func : &Function,
+    instr : &Instruction,
+    frame : &mut CallFrame,
+    env : &mut GlobalEnvironment,
+    a : usize,
+    b : usize,
+    c : usize)
     -> Result<(), InterpreterError> {
     let rk_b = get_rk(func, frame, b);
     let rk_c = get_rk(func, frame, c);
-    let number_b = rk_b.get_number().unwrap();
-    let number_c = rk_c.get_number().unwrap();
-    match instr {
-        Instruction::Add(_, _, _) => { frame.store(a, Value::Number( number_b + number_c )) }
-        Instruction::Sub(_, _, _) => { frame.store(a, Value::Number( number_b - number_c )) }
-        Instruction::Mul(_, _, _) => { frame.store(a, Value::Number( number_b * number_c )) }
-        Instruction::Pow(_, _, _) => { frame.store(a, Value::Number( number_b.powf(number_c) )) }
-        Instruction::Div(_, _, _) => {
-            if number_c == 0. {
-                return Err(InterpreterError::ZeroDivisionError)
-            }
-            frame.store(a, Value::Number( number_b / number_c ))
-        }
-        Instruction::Mod(_, _, _) => {
-            if number_c == 0. {
-                return Err(InterpreterError::ZeroDivisionError)
-            }
-            frame.store(a, Value::Number(number_b % number_c))
-        }
+
+    let event = match instr {
+        Instruction::Add(_, _, _) => { MetaMethod::Add }
+        Instruction::Sub(_, _, _) => { MetaMethod::Sub }
+        Instruction::Mul(_, _, _) => { MetaMethod::Mul }
+        Instruction::Pow(_, _, _) => { MetaMethod::Pow }
+        Instruction::Div(_, _, _) => { MetaMethod::Div }
+        Instruction::Mod(_, _, _) => { MetaMethod::Mod }
         _ => { panic!("Should not call arithmetic operation for non arithmetic instruction") }
+    };
+
+    // operands that are not numbers are handled by metamethods
+    let (number_b, number_c) = match (rk_b.get_number(), rk_c.get_number()) {
+        (Some(number_b), Some(number_c)) => { (number_b, number_c) }
+        _ => {
+            let res = metatable::arithmetic(env, event, rk_b, rk_c)?;
+            frame.store(a, res);
+            return Ok(());
+        }
+    };
+
+    match event {
+        MetaMethod::Add => { frame.store(a, Value::Number( number_b + number_c )) }
+        MetaMethod::Sub => { frame.store(a, Value::Number( number_b - number_c )) }
+        MetaMethod::Mul => { frame.store(a, Value::Number( number_b * number_c )) }
+        MetaMethod::Pow => { frame.store(a, Value::Number( number_b.powf(number_c) )) }
+        // dividing by zero gives an infinite or a NaN, it is not an error
+        MetaMethod::Div => { frame.store(a, Value::Number( number_b / number_c )) }
+        // the result of the modulo has the sign of the divisor
+        _ => { frame.store(a, Value::Number( number_b - (number_b / number_c).floor() * number_c )) }
     }
 
     Ok(())
 }
 
 /// store in register a the opposite value found in register b (LUANUMBER)
-fn minus_operator(frame: &mut CallFrame<'_>, a: usize, b: usize) {
-    let number = frame.load(b).get_number().unwrap();
-    frame.store(a, Value::Number(-number));
-} 
-
-/// store in register a the opposite value found in register b (BOOLEAN)
-fn not_operator(frame: &mut CallFrame<'_>, a: usize, b: usize) {
-    let boolean = frame.load(b).get_boolean().unwrap();
+fn minus_operator(frame: &mut CallFrame, env: &mut GlobalEnvironment, a: usize, b: usize) -> Result<(), InterpreterError> {
+    let register_b = frame.load(b);
+    let res = match register_b.get_number() {
+        Some(number) => { Value::Number(-number) }
+        None => { metatable::arithmetic(env, MetaMethod::Unm, register_b.clone(), register_b)? }
+    };
+    frame.store(a, res);
+    Ok(())
+}
+
+/// store in register a the negation of the value found in register b, only nil and false are false
+fn not_operator(frame: &mut CallFrame, a: usize, b: usize) {
+    let boolean = frame.load(b).to_boolean();
     frame.store(a, Value::Boolean(!boolean));
 }
 
-/// store in register a the length of string found in register b
-fn len_operator(frame: &mut CallFrame<'_>, a: usize, b: usize) {
-    let lua_string = frame.load(b).get_string().unwrap();
-    frame.store(a, Value::Number(lua_string.len() as f64));
+/// store in register a the length of the value found in register b
+fn len_operator(frame: &mut CallFrame, env: &mut GlobalEnvironment, a: usize, b: usize) -> Result<(), InterpreterError> {
+    let length = metatable::length(env, frame.load(b))?;
+    frame.store(a, length);
+    Ok(())
+}
+
+/// strings and numbers can be concatenated without metamethods
+fn is_concatenable(val : &Value) -> bool {
+    matches!(val, Value::LuaString(_) | Value::Number(_))
 }
 
-fn test_operator(frame: &CallFrame<'_>, pc: &mut usize, a: usize, c: usize) {
+/* store in register a the concatenation of the values in registers b to c
+ * as in the reference implementation the values are concatenated from right to left,
+ * consecutive strings and numbers are joined in a single allocation
+ */
+fn concat_operator(frame: &mut CallFrame, env: &mut GlobalEnvironment, a: usize, b: usize, c: usize) -> Result<(), InterpreterError> {
+    let mut values: Vec<Value> = (b..=c).map(|i| frame.load(i)).collect();
+
+    while values.len() > 1 {
+        let top = values.len() - 1;
+
+        if !is_concatenable(&values[top - 1]) || !is_concatenable(&values[top]) {
+            let rhs = values.pop().unwrap_or_default();
+            let lhs = values.pop().unwrap_or_default();
+            values.push(metatable::concat(env, lhs, rhs)?);
+            continue;
+        }
+
+        let mut first = top - 1;
+        while first > 0 && is_concatenable(&values[first - 1]) {
+            first -= 1;
+        }
+
+        let pieces: Vec<LuaStr> = values.drain(first..).filter_map(|v| v.to_lua_string()).collect();
+        let total_length = pieces.iter().map(|s| s.len()).sum();
+        env.budget.check_string_length(total_length)?;
+        let mut res = Vec::with_capacity(total_length);
+        for piece in &pieces {
+            res.extend_from_slice(piece);
+        }
+        values.push(Value::LuaString(Rc::from(res)));
+    }
+
+    frame.store(a, values.pop().unwrap_or_default());
+    Ok(())
+}
+
+fn test_operator(frame: &CallFrame, pc: &mut usize, a: usize, c: usize) {
     let register_a = frame.load(a);
     if (c > 0) != register_a.to_boolean() {
         *pc += 1;
     }
 }
 
-fn testset_operator(frame: &mut CallFrame<'_>, pc: &mut usize, a: usize, b: usize, c: usize) {
+fn testset_operator(frame: &mut CallFrame, pc: &mut usize, a: usize, b: usize, c: usize) {
     let register_b = frame.load(b);
     if (c != 0) == register_b.to_boolean() {
         frame.store(a, register_b);
@@ -134,55 +485,65 @@ fn testset_operator(frame: &mut CallFrame<'_>, pc: &mut usize, a: usize, b: usiz
     }
 }
 
-/// store in register a the bth upvalue of the upvalue list passed in argument
-fn get_upvalue<'frm>(frame: &mut CallFrame<'frm>, upvalues: &Vec<Value<'frm>>, a : usize, b: usize) {
-    let upval = upvalues[b];
+/// store in register a the bth upvalue of the closure being evaluated
+fn get_upvalue(frame: &mut CallFrame, closure: &Closure, a : usize, b: usize) {
+    let upval = closure.upvalues.borrow()[b].borrow().clone();
     frame.store(a, upval);
 }
 
-/// changes the bth value stored in the upvalue list by the value in register a 
-fn set_upvalue<'frm>(frame: &CallFrame<'frm>, upvalues: &mut Vec<Value<'frm>>, a: usize, b: usize) {
-    upvalues[b] = frame.load(a);
+/// changes the bth upvalue of the closure being evaluated by the value in register a
+fn set_upvalue(frame: &CallFrame, closure: &Closure, a: usize, b: usize) {
+    *closure.upvalues.borrow()[b].borrow_mut() = frame.load(a);
 }
 
-/* evaluates an arithmetic comparison instruction
+/* evaluates a comparison instruction
  * skips the next instruction if the result is equal to the boolean in register a
  * func : Function to access the constant list
  * instr : instruction to evaluate
  * frame : current stack frame
  * pc : program counter pointing to the next instruction
 */
-fn comparison_operator<'frm>(func : &'frm Function, instr: &Instruction, frame: &mut CallFrame<'frm>, a : usize, b : usize, c : usize, pc : &mut usize) {
+#[allow(clippy::too_many_arguments)]
+fn comparison_operator(
+    func : &Function,
+    instr: &Instruction,
+    frame: &mut CallFrame,
+    env : &mut GlobalEnvironment,
+    a : usize,
+    b : usize,
+    c : usize,
+    pc : &mut usize)
+    -> Result<(), InterpreterError> {
     let rk_b = get_rk(func, frame, b);
     let rk_c = get_rk(func, frame, c);
     let boolean_a = a > 0;
-    let number_b = rk_b.get_number().unwrap();
-    let number_c = rk_c.get_number().unwrap();
-    let order = number_b.total_cmp(&number_c);
-    match instr {
-        Instruction::Le(_, _, _) => { if order.is_le() != boolean_a { *pc += 1; } }
-        Instruction::Lt(_, _, _) => { if order.is_lt() != boolean_a { *pc += 1; } }
+    let res = match instr {
+        Instruction::Le(_, _, _) => { metatable::less_equal(env, rk_b, rk_c)? }
+        Instruction::Lt(_, _, _) => { metatable::less_than(env, rk_b, rk_c)? }
         _ => panic!("Should not call comparison operator")
-    }
+    };
+    if res != boolean_a { *pc += 1; }
+    Ok(())
 }
 
-/// test if the values in register b and c are equal 
+/// test if the values in register b and c are equal
 /// and skips the next instruction if the result is equal to the boolean in register a
-fn equality<'frm>(func: &'frm Function, frame: &mut CallFrame<'frm>, pc: &mut usize, a: usize, b: usize, c: usize) {
+fn equality(func: &Function, frame: &mut CallFrame, env: &mut GlobalEnvironment, pc: &mut usize, a: usize, b: usize, c: usize) -> Result<(), InterpreterError> {
     let rk_b = get_rk(func, frame, b);
     let rk_c = get_rk(func, frame, c);
     let boolean_a = a > 0;
-    if (rk_b == rk_c) != boolean_a { *pc += 1; }
+    if metatable::equals(env, rk_b, rk_c)? != boolean_a { *pc += 1; }
+    Ok(())
 }
 
 /* Adds the resulting values of the current function in the return_values vector
  * a : register number of the first value to return
  * b : if 0 then return all values from a to the top of the stack else return b-1 values
-*/ 
-fn return_instruction<'frm>(frame : &mut CallFrame<'frm>, a : usize, b : usize, return_values: &mut Vec<Value<'frm>>) -> Result<(), InterpreterError> {
-    
+*/
+fn return_instruction(frame : &mut CallFrame, a : usize, b : usize, return_values: &mut Vec<Value>) -> Result<(), InterpreterError> {
+
     let max_index = if b == 0 {
-        frame.len() - a
+        frame.len().saturating_sub(a)
     } else {
         b - 1
     };
@@ -202,31 +563,34 @@ fn jmp_instruction(pc: &mut usize, increment : isize) {
     }
 }
 
-/// Instantiate a closure by putting the bth function of the function list of the current function in register a 
+/// Instantiate a closure by putting the bth function of the function list of the current function in register a
 /// and instantiating the upvalue list of the closure
-fn closure_instruction<'cur>(
-    func: &'cur Function, 
-    frame : &mut CallFrame<'cur>,
-    upvalues : &mut Vec<Vec<Value<'cur>>>,
+fn closure_instruction(
+    closure: &Closure,
+    frame : &mut CallFrame,
     pc : &mut usize,
-    a : usize, 
-    b : usize ) 
+    a : usize,
+    b : usize )
     -> Result<(), InterpreterError> {
-    let next_func = &func.func_list[b];
-    frame.store(a, Value::LuaFunction(next_func));
+    let func = &closure.proto;
+    // the new closure shares the environment of the current one
+    let next_closure = Rc::new(Closure::new(Rc::clone(&func.func_list[b]), closure.env.borrow().clone()));
+    // the closure is stored first as a local function can capture itself
+    frame.store(a, Value::LuaFunction(Rc::clone(&next_closure)));
 
     // There is one Move or GetUPVal instruction following the closure per upvalue
     // We evaluate these instruction here because they behave differently than they normally do
-    upvalues[next_func.identifier] = Vec::with_capacity(next_func.up_values as usize);
-    for _ in 0..next_func.up_values {
+    let mut upvalues = next_closure.upvalues.borrow_mut();
+    for _ in 0..next_closure.proto.up_values {
         *pc += 1;
         match func.instr_list[*pc - 1] {
-            Instruction::Move(_, reg_b, _) => { 
-                upvalues[next_func.identifier].push(frame.load(reg_b));
+            // the register is shared between the current frame and the closure
+            Instruction::Move(_, reg_b, _) => {
+                upvalues.push(frame.capture(reg_b));
             }
-            Instruction::GetUpVal(_, reg_b, _) => { 
-                let upval = upvalues[func.identifier][reg_b];
-                upvalues[next_func.identifier].push(upval);
+            Instruction::GetUpVal(_, reg_b, _) => {
+                let upval = Rc::clone(&closure.upvalues.borrow()[reg_b]);
+                upvalues.push(upval);
             }
             _ => { return Err(InterpreterError::ClosureError) }
         }
@@ -236,237 +600,608 @@ fn closure_instruction<'cur>(
     Ok(())
 }
 
-fn get_global<'frm>(
-    func : &'frm Function, 
-    frame: &mut CallFrame<'frm>, 
-    env : &GlobalEnvironment<'frm>, 
-    a : usize, 
-    b : usize) 
+/// store in register a the global variable named by the bth constant, looked up in the environment of the closure
+fn get_global(
+    closure : &Closure,
+    frame: &mut CallFrame,
+    env : &mut GlobalEnvironment,
+    a : usize,
+    b : usize)
     -> Result<(), InterpreterError> {
-    let rk_b = func.const_list[b].as_value();
-    let val = env.get_global(rk_b.get_string().unwrap()).map_err(
-        |err| {
-            InterpreterError::GlobalError { heap_error: err }
-        }
-    );
-    frame.store(a, val?);
+    let rk_b = closure.proto.const_list[b].get_string().ok_or(InterpreterError::GlobalNameError)?;
+    // the environment is a regular table, its metamethods are called
+    let globals = Value::Table(closure.env.borrow().clone());
+    let val = metatable::index(env, globals, Value::LuaString(rk_b))?;
+    frame.store(a, val);
     Ok(())
 }
 
-fn set_global<'frm>(
-    func : &'frm Function, 
-    frame: &mut CallFrame<'frm>, 
-    env : &mut GlobalEnvironment<'frm>, 
-    a : usize, 
-    b : usize) 
+/// stores register a in the global variable named by the bth constant, in the environment of the closure
+fn set_global(
+    closure : &Closure,
+    frame: &mut CallFrame,
+    env : &mut GlobalEnvironment,
+    a : usize,
+    b : usize)
     -> Result<(), InterpreterError> {
     let register_a = frame.load(a);
-    let rk_b = func.const_list[b].as_value();
-    env.insert_global(rk_b.get_string().unwrap(), register_a);
+    let rk_b = closure.proto.const_list[b].get_string().ok_or(InterpreterError::GlobalNameError)?;
+    let globals = Value::Table(closure.env.borrow().clone());
+    metatable::new_index(env, globals, Value::LuaString(rk_b), register_a)?;
+
+    Ok(())
+}
+
+/// converts the size of a table encoded as a "floating point byte" (eeeeexxx)
+fn fb2int(x : usize) -> usize {
+    if x < 8 {
+        x
+    } else {
+        ((x & 7) + 8) << ((x >> 3) - 1)
+    }
+}
+
+/// creates a new table in register a, b and c are the sizes of its array and hash parts
+fn new_table(frame: &mut CallFrame, a: usize, b: usize, c: usize) {
+    let table = Table::with_capacity(fb2int(b), fb2int(c));
+    frame.store(a, Value::Table(Rc::new(RefCell::new(table))));
+}
+
+/// store in register a the value R(B)[RK(C)]
+fn get_table(func: &Function, frame: &mut CallFrame, env: &mut GlobalEnvironment, a: usize, b: usize, c: usize) -> Result<(), InterpreterError> {
+    let table = frame.load(b);
+    let key = get_rk(func, frame, c);
+    let val = metatable::index(env, table, key)?;
+    frame.store(a, val);
+    Ok(())
+}
+
+/// performs R(A)[RK(B)] = RK(C)
+fn set_table(func: &Function, frame: &mut CallFrame, env: &mut GlobalEnvironment, a: usize, b: usize, c: usize) -> Result<(), InterpreterError> {
+    let table = frame.load(a);
+    let key = get_rk(func, frame, b);
+    let val = get_rk(func, frame, c);
+    metatable::new_index(env, table, key, val)
+}
+
+/// prepares a method call: register a + 1 receives the object in register b
+/// and register a the method found by indexing the object with RK(C)
+fn self_instruction(func: &Function, frame: &mut CallFrame, env: &mut GlobalEnvironment, a: usize, b: usize, c: usize) -> Result<(), InterpreterError> {
+    let object = frame.load(b);
+    let key = get_rk(func, frame, c);
+    frame.store(a + 1, object.clone());
+    let method = metatable::index(env, object, key)?;
+    frame.store(a, method);
+    Ok(())
+}
+
+/* stores the values of the registers following register a in the table in register a
+ * b : number of values to store, if 0 all values up to the top of the stack are stored
+ * c : block number, values of the block c start at index (c - 1) * FIELDS_PER_FLUSH + 1
+ */
+fn set_list(func: &Function, frame: &mut CallFrame, pc: &mut usize, a: usize, b: usize, c: usize) -> Result<(), InterpreterError> {
+    // when c is 0 the block number doesn't fit in C, it is stored in the next instruction as a raw integer
+    let c = match (c, func.instr_list.get(*pc)) {
+        (0, Some(Instruction::ExtraArg(block))) => {
+            *pc += 1;
+            *block
+        }
+        _ => { c }
+    };
+
+    let nb_values = if b == 0 { frame.len().saturating_sub(a + 1) } else { b };
+    let register_a = frame.load(a);
+    let table = register_a.get_table().ok_or(InterpreterError::IndexError { typ: register_a.get_type() })?;
+    let mut table = table.borrow_mut();
+    let offset = c.saturating_sub(1) * FIELDS_PER_FLUSH;
+    for i in 1..=nb_values {
+        table.set(Value::Number((offset + i) as f64), frame.load(a + i))?;
+    }
 
     Ok(())
 }
 
-fn for_prep(frame: &mut CallFrame<'_>, pc: &mut usize, a: usize, b: isize) {
-    let register_a = frame.load(a).get_number().unwrap();
-    let stepping_value = frame.load(a + 2).get_number().unwrap();
+/// returns the number in register r used by a numeric for loop, strings are converted
+fn for_operand(frame: &CallFrame, r: usize, operand: &'static str) -> Result<f64, InterpreterError> {
+    frame.load(r).get_number().ok_or(InterpreterError::ForLoopError { operand })
+}
+
+fn for_prep(frame: &mut CallFrame, pc: &mut usize, a: usize, b: isize) -> Result<(), InterpreterError> {
+    let register_a = for_operand(frame, a, "initial value")?;
+    let limit = for_operand(frame, a + 1, "limit")?;
+    let stepping_value = for_operand(frame, a + 2, "step")?;
     frame.store(a, Value::Number(register_a - stepping_value));
+    frame.store(a + 1, Value::Number(limit));
+    frame.store(a + 2, Value::Number(stepping_value));
     jmp_instruction(pc, b);
+    Ok(())
 }
 
-fn for_loop(frame: &mut CallFrame<'_>, pc: &mut usize, a: usize, b: isize) {
-    let number_a = frame.load(a).get_number().unwrap();
-    let limit = frame.load(a + 1).get_number().unwrap();
-    let stepping_value = frame.load(a + 2).get_number().unwrap();
-    frame.store(a, Value::Number(number_a + stepping_value));
-    
-    let cmp_operator = if stepping_value < 0. { f64::gt } else { f64::lt };
+fn for_loop(frame: &mut CallFrame, pc: &mut usize, a: usize, b: isize) -> Result<(), InterpreterError> {
+    let number_a = for_operand(frame, a, "initial value")?;
+    let limit = for_operand(frame, a + 1, "limit")?;
+    let stepping_value = for_operand(frame, a + 2, "step")?;
+    let number_a = number_a + stepping_value;
+    frame.store(a, Value::Number(number_a));
+
+    let cmp_operator = if stepping_value < 0. { f64::ge } else { f64::le };
 
     if cmp_operator(&number_a, &limit) {
         jmp_instruction(pc, b);
         frame.store(a + 3, frame.load(a));
     }
+    Ok(())
 }
 
-fn call_instruction<'frm>(
-    frame: &mut CallFrame<'frm>, 
-    env : &mut GlobalEnvironment<'frm>, 
-    upvalues : &mut Vec<Vec<Value<'frm>>>,
-    a : usize, 
-    b : usize, 
-    c : usize) 
-    -> Result<(), InterpreterError> {
+/* step of a generic for loop, calls the iterator in register a with the state and control variable in registers a + 1 and a + 2
+ * the c first results are stored from register a + 3, the loop ends when the first one is nil
+ * otherwise it becomes the new control variable and the following jump instruction loops back
+ */
+fn tforloop_instruction(frame: &mut CallFrame, env: &mut GlobalEnvironment, pc: &mut usize, a: usize, c: usize) -> Result<(), InterpreterError> {
+    let iterator = frame.load(a);
+    let args = vec![frame.load(a + 1), frame.load(a + 2)];
+    let mut results = call_value(env, iterator, args)?.into_iter();
+    for i in 0..c {
+        frame.store(a + 3 + i, results.next().unwrap_or_default());
+    }
+
+    let control = frame.load(a + 3);
+    if control.is_nil() {
+        *pc += 1;
+    } else {
+        frame.store(a + 2, control);
+    }
+    Ok(())
+}
+
+/// Allocates the stack frame of a function called with the given arguments
+fn prepare_frame(proto : &Function, mut args : Vec<Value>) -> Result<CallFrame, InterpreterError> {
+    let mut new_frame = CallFrame::with_capacity(proto.stack);
+    // Adding the arguments of the called function in the stack frame of the next function
+    // the parameters of the function are its first registers
+    let nb_params = proto.args as usize;
+    let extra_args = if args.len() > nb_params { args.split_off(nb_params) } else { Vec::new() };
+    for (i, arg) in args.into_iter().enumerate() {
+        new_frame.store(i, arg);
+    }
+
+    // the extra arguments are kept for the VarArg instruction
+    if proto.is_vararg() {
+        if proto.needs_arg() {
+            new_frame.store(nb_params, arg_table(&extra_args)?);
+        }
+        new_frame.varargs = extra_args;
+    }
+
+    Ok(new_frame)
+}
+
+/// Calls a function value with the given arguments and returns its results
+/// values that are not functions are called through their __call metamethod
+pub fn call_value(env : &mut GlobalEnvironment, func_val : Value, args : Vec<Value>) -> Result<Vec<Value>, InterpreterError> {
+    enter_nested_call(env)?;
+    let res = dispatch_call(env, func_val, args);
+    env.nested_calls -= 1;
+    res
+}
+
+/* counts an evaluation nested on the stack of the host, the caller decrements env.nested_calls when it ends
+ * raises a C stack overflow when there are too many of them
+ */
+pub fn enter_nested_call(env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let limit = if env.handling_errors > 0 { MAX_NESTED_CALLS + MAX_NESTED_CALLS / 8 } else { MAX_NESTED_CALLS };
+    if env.nested_calls >= limit {
+        return Err(InterpreterError::CStackOverflowError);
+    }
+    env.nested_calls += 1;
+    Ok(())
+}
+
+/* calls a function value without counting a nested call, used by the call instructions:
+ * a runtime function called by a lua function only nests the calls it makes itself
+ */
+fn dispatch_call(env : &mut GlobalEnvironment, func_val : Value, mut args : Vec<Value>) -> Result<Vec<Value>, InterpreterError> {
 
     let mut returned_values = Vec::new();
-    let func_val = frame.load(a);
 
     match func_val {
         // evaluating function implemented in the program
         Value::LuaFunction(next_func) => {
-            let mut new_frame = CallFrame::with_capacity(next_func.stack);
-            // Adding the arguments of the called function in the stack frame of the next function
-            // if b == 0 we add of the values from register a + 1 to the top of stack frame 
-            let max_index = if b == 0 { frame.len() - a } else { b };
-            for i in 1..max_index {
-                new_frame.store(i - 1, frame.load(a + i));
-            };
-
-            eval_sequence(next_func, new_frame, env, upvalues, &mut returned_values)?;
-        } 
+            return eval_sequence(env, next_func, args);
+        }
         // evaluating function from the runtime library
         Value::RuntimeFunction(next_func) => {
-            let stack_size= if b > 0 { b-1 } else { frame.len() - a - 1 };  
-            let mut new_frame = CallFrame::with_capacity(stack_size as u8);
-            // Adding the arguments of the called function in the stack frame of the next function
-            for i in 0..stack_size {
-                new_frame.store(i, frame.load(a + i + 1));
-            };
-            (next_func)(new_frame, &mut returned_values);
+            env.call_stack.push(CallInfo::Runtime { func: func_val });
+            let res = (next_func)(CallFrame::from_arguments(args), &mut returned_values, env)
+                .map_err(|err| raise_error(env, err));
+            env.call_stack.pop();
+            res?;
+        }
+        Value::RuntimeClosure(ref next_func) => {
+            let next_func = Rc::clone(next_func);
+            env.call_stack.push(CallInfo::Runtime { func: func_val });
+            let res = (next_func.0)(CallFrame::from_arguments(args), &mut returned_values, env)
+                .map_err(|err| raise_error(env, err));
+            env.call_stack.pop();
+            res?;
+        }
+        _ => {
+            // the handler is not looked up again: a value whose __call is not a function can't be called
+            let handler = metatable::get_metamethod(env, &func_val, MetaMethod::Call);
+            if !matches!(handler, Value::LuaFunction(_) | Value::RuntimeFunction(_) | Value::RuntimeClosure(_)) {
+                return Err(InterpreterError::CallError { typ: func_val.get_type() });
+            }
+            // the called object is the first argument of its handler
+            args.insert(0, func_val);
+            return dispatch_call(env, handler, args);
+        }
+    }
+
+    Ok(returned_values)
+}
+
+/// builds the table arg containing the extra arguments of a vararg function and their number in the field n
+fn arg_table(extra_args : &[Value]) -> Result<Value, InterpreterError> {
+    let mut table = Table::with_capacity(extra_args.len(), 1);
+    for (i, arg) in extra_args.iter().enumerate() {
+        table.set(Value::Number((i + 1) as f64), arg.clone())?;
+    }
+    table.set(Value::from_string("n"), Value::Number(extra_args.len() as f64))?;
+    Ok(Value::Table(Rc::new(RefCell::new(table))))
+}
+
+/* copies the extra arguments of the current function in the registers starting from a
+ * b : if 0 all the extra arguments are copied and the top of the stack is set after the last one
+ *     else b - 1 values are copied, completed with nil
+ */
+fn vararg_instruction(frame: &mut CallFrame, a: usize, b: usize) {
+    let varargs = std::mem::take(&mut frame.varargs);
+    if b == 0 {
+        for (i, val) in varargs.iter().enumerate() {
+            frame.store(a + i, val.clone());
+        }
+        frame.set_length(a + varargs.len());
+    } else {
+        for i in 0..b - 1 {
+            frame.store(a + i, varargs.get(i).cloned().unwrap_or_default());
         }
-        _ => return Err(InterpreterError::NotCallableError)
     }
+    frame.varargs = varargs;
+}
+
+/// returns the arguments of a call, if b == 0 the arguments are all the values from register a + 1 to the top of the stack frame
+fn call_arguments(frame: &CallFrame, a : usize, b : usize) -> Vec<Value> {
+    let max_index = if b == 0 { frame.len() } else { a + b };
+    (a + 1..max_index).map(|i| frame.load(i)).collect()
+}
+
+/* stores the results of a call in the registers starting from a, missing values are replaced by nil
+ * c : if 0 all the results are stored and the top of the stack frame is set after the last one
+ *     else c - 1 values are stored
+ */
+fn store_results(frame: &mut CallFrame, a : usize, c : usize, returned_values : Vec<Value>) {
     // Call instruction manipulates the top of the stack frame
     // Basically Call is supposed to pop the argument of the called function
     // from the stack frame.
-    frame.set_length(a + 1);
+    frame.set_length(a);
 
-    // Adding the returned values in the current stack frame
-    let max_index = if c == 0 { returned_values.len() } else { c - 1 };
-    for i in 0..max_index {
-        frame.store(i + a, returned_values[i]);
+    if c == 0 {
+        for (i, val) in returned_values.into_iter().enumerate() {
+            frame.store(i + a, val);
+        }
+    } else {
+        let mut returned_values = returned_values.into_iter();
+        for i in 0..c - 1 {
+            frame.store(i + a, returned_values.next().unwrap_or_default());
+        }
     }
+}
 
+/// a called value whose __call handler is a lua function is replaced by the handler,
+/// the called object is the first argument of its handler
+fn resolve_call(env : &GlobalEnvironment, func_val : Value, mut args : Vec<Value>) -> (Value, Vec<Value>) {
+    if matches!(func_val, Value::LuaFunction(_) | Value::RuntimeFunction(_) | Value::RuntimeClosure(_)) {
+        return (func_val, args);
+    }
+    let handler = metatable::get_metamethod(env, &func_val, MetaMethod::Call);
+    if let Value::LuaFunction(_) = handler {
+        args.insert(0, func_val);
+        return (handler, args);
+    }
+    (func_val, args)
+}
+
+// Calls to lua functions are handed to eval_closure which evaluates the called function
+// in the same loop, the results are stored when it returns
+fn call_instruction(
+    frame: &mut CallFrame,
+    env : &mut GlobalEnvironment,
+    call : &mut Option<LuaCall>,
+    a : usize,
+    b : usize,
+    c : usize)
+    -> Result<(), InterpreterError> {
+
+    let (func_val, args) = resolve_call(env, frame.load(a), call_arguments(frame, a, b));
+    match func_val {
+        Value::LuaFunction(next_func) => { *call = Some(LuaCall::Call(next_func, args)); }
+        _ => {
+            let returned_values = dispatch_call(env, func_val, args)?;
+            store_results(frame, a, c, returned_values);
+        }
+    }
     Ok(())
 }
 
-// used to similate tail recursion
-// Tail recursion optimization does not work with this implementation
-fn tailcall_instruction<'frm>(
-    frame: &mut CallFrame<'frm>,
-    env : &mut GlobalEnvironment<'frm>,
-    upvalues : &mut Vec<Vec<Value<'frm>>>,
-    return_values : &mut Vec<Value<'frm>>,
+// Tail calls to lua functions are handed to eval_closure which reuses the activation
+// of the current function so that tail recursion does not grow the stack
+fn tailcall_instruction(
+    frame: &mut CallFrame,
+    env : &mut GlobalEnvironment,
+    return_values : &mut Vec<Value>,
+    call : &mut Option<LuaCall>,
     pc : &mut usize,
     a : usize,
     b : usize
 ) -> Result<(), InterpreterError> {
-    let func_val = frame.load(a);
+    let (func_val, args) = resolve_call(env, frame.load(a), call_arguments(frame, a, b));
+    // Call instruction manipulates the top of the stack frame
+    // Basically Call is supposed to pop the argument of the called function
+    // from the stack frame.
+    frame.set_length(a + 1);
     match func_val {
-        Value::LuaFunction(next_func) => {
-            let mut new_frame = CallFrame::with_capacity(next_func.stack);
-            // Adding the arguments of the called function in its stack frame
-            // if b == 0, we add all values from regiter a + 1 to the top of the stack       
-            let max_index = if b == 0 { frame.len() - a } else { b };
-            for i in 1..max_index {
-                new_frame.store(i - 1, frame.load(a + i));
-            };
-            // Call instruction manipulates the top of the stack frame
-            // Basically Call is supposed to pop the argument of the called function
-            // from the stack frame. 
-            frame.set_length(a + 1);
+        Value::LuaFunction(next_func) => { *call = Some(LuaCall::TailCall(next_func, args)); }
+        _ => {
+            return_values.extend(dispatch_call(env, func_val, args)?);
             // A tail Call is always followed by two return instruction
             // We skip the first one as it tries to add values from the register
-            // into return_values even though we do not put the returned_values 
+            // into return_values even though we do not put the returned_values
             // in the stack frame
             *pc = usize::MAX;
-            eval_sequence(next_func, new_frame, env, upvalues, return_values)
         }
-        _ => { return Err(InterpreterError::TailCallError) }
     }
+    Ok(())
 }
 
 /* function treating one instruction of the function passed in argument and returning the results of the function through a vector
- * func : reference to the function we are evaluating
+ * closure : closure we are evaluating, it gives access to the function and its upvalues
  * frame : stack frame of the current function
  * env : Table containing the global variables
- * upvalues : Vector of the upvalues of each function
  * pc : program counter
  * result : Vector through which we return the result of the function
+ * call : lua function called by the instruction, evaluated by eval_closure
  */
-fn eval_instruction<'frm>(
-    func : &'frm Function, 
-    instr : &Instruction, 
-    frame : &mut CallFrame<'frm>, 
-    env : &mut GlobalEnvironment<'frm>,
-    upvalues : &mut Vec<Vec<Value<'frm>>>,
-    pc : &mut usize, 
-    return_values : &mut Vec<Value<'frm>>) 
+fn eval_instruction(
+    closure : &Closure,
+    instr : &Instruction,
+    frame : &mut CallFrame,
+    env : &mut GlobalEnvironment,
+    pc : &mut usize,
+    return_values : &mut Vec<Value>,
+    call : &mut Option<LuaCall>)
    -> Result<(), InterpreterError> {
 
+    let func = closure.proto.as_ref();
+
     match *instr {
         Instruction::Move(a, b, _) => { move_operation(frame, a, b) }
         Instruction::LoadK(a, b) => { load_k(func, frame, a, b) }
         Instruction::LoadBool(a, b, c) => { load_bool(frame, a, b, c, pc) }
         Instruction::LoadNil(a, b, _) => { load_nil(frame, a, b) }
-        Instruction::Add(a, b, c) => { arithmetic_operation(func, instr, frame, a, b, c)? }
-        Instruction::Sub(a, b, c) => { arithmetic_operation(func, instr, frame, a, b, c)? }
-        Instruction::Mul(a, b, c) => { arithmetic_operation(func, instr, frame, a, b, c)? }
-        Instruction::Div(a, b, c) => { arithmetic_operation(func, instr, frame, a, b, c)? }
-        Instruction::Mod(a, b, c) => { arithmetic_operation(func, instr, frame, a, b, c)? }
-        Instruction::Pow(a, b, c) => { arithmetic_operation(func, instr, frame, a, b, c)? }
-        Instruction::Unm(a, b, _) => { minus_operator(frame, a, b); }
+        Instruction::Add(a, b, c) => { arithmetic_operation(func, instr, frame, env, a, b, c)? }
+        Instruction::Sub(a, b, c) => { arithmetic_operation(func, instr, frame, env, a, b, c)? }
+        Instruction::Mul(a, b, c) => { arithmetic_operation(func, instr, frame, env, a, b, c)? }
+        Instruction::Div(a, b, c) => { arithmetic_operation(func, instr, frame, env, a, b, c)? }
+        Instruction::Mod(a, b, c) => { arithmetic_operation(func, instr, frame, env, a, b, c)? }
+        Instruction::Pow(a, b, c) => { arithmetic_operation(func, instr, frame, env, a, b, c)? }
+        Instruction::Unm(a, b, _) => { minus_operator(frame, env, a, b)?; }
         Instruction::Not(a, b, _) => { not_operator(frame, a, b); }
-        Instruction::Len(a, b, _) => { len_operator(frame, a, b); }
-        Instruction::Eq(a, b, c) => { equality(func, frame, pc, a, b, c); }
-        Instruction::Le(a, b, c) => { comparison_operator(func, instr, frame, a, b, c, pc); }
-        Instruction::Lt(a, b, c) => { comparison_operator(func, instr, frame, a, b, c, pc); }
-        Instruction::Jmp(_, b) => { jmp_instruction(pc, b as isize) }
-        Instruction::GetGlobal(a, b) => { get_global(func, frame, env, a, b)? }
-        Instruction::SetGlobal(a, b) => { set_global(func, frame, env, a, b)? }
-        Instruction::GetUpVal(a, b, _) => { get_upvalue(frame, &upvalues[func.identifier], a, b) }
-        Instruction::SetUpVal(a, b, _) => { set_upvalue(frame, &mut upvalues[func.identifier], a, b); }
+        Instruction::Len(a, b, _) => { len_operator(frame, env, a, b)?; }
+        Instruction::Concat(a, b, c) => { concat_operator(frame, env, a, b, c)?; }
+        Instruction::Eq(a, b, c) => { equality(func, frame, env, pc, a, b, c)?; }
+        Instruction::Le(a, b, c) => { comparison_operator(func, instr, frame, env, a, b, c, pc)?; }
+        Instruction::Lt(a, b, c) => { comparison_operator(func, instr, frame, env, a, b, c, pc)?; }
+        Instruction::Jmp(_, b) => { jmp_instruction(pc, b) }
+        Instruction::GetGlobal(a, b) => { get_global(closure, frame, env, a, b)? }
+        Instruction::SetGlobal(a, b) => { set_global(closure, frame, env, a, b)? }
+        Instruction::GetUpVal(a, b, _) => { get_upvalue(frame, closure, a, b) }
+        Instruction::SetUpVal(a, b, _) => { set_upvalue(frame, closure, a, b); }
+        Instruction::NewTable(a, b, c) => { new_table(frame, a, b, c) }
+        Instruction::GetTable(a, b, c) => { get_table(func, frame, env, a, b, c)? }
+        Instruction::SetTable(a, b, c) => { set_table(func, frame, env, a, b, c)? }
+        Instruction::SetList(a, b, c) => { set_list(func, frame, pc, a, b, c)? }
+        Instruction::SelF(a, b, c) => { self_instruction(func, frame, env, a, b, c)? }
         Instruction::Test(a, _, c) => { test_operator(frame, pc, a, c); }
         Instruction::TestSet(a, b, c) => { testset_operator(frame, pc, a, b, c); }
-        Instruction::Call(a, b, c) => { call_instruction(frame, env, upvalues, a, b, c)? } 
-        Instruction::TailCall(a, b, _) => { tailcall_instruction(frame, env, upvalues, return_values, pc, a, b)? }
-        Instruction::Closure(a, b) => { closure_instruction(func, frame, upvalues, pc, a, b)? }
+        Instruction::Call(a, b, c) => { call_instruction(frame, env, call, a, b, c)? }
+        Instruction::TailCall(a, b, _) => { tailcall_instruction(frame, env, return_values, call, pc, a, b)? }
+        Instruction::Closure(a, b) => { closure_instruction(closure, frame, pc, a, b)? }
+        Instruction::Close(a, _, _) => { frame.close_upvalues(a) }
+        Instruction::VarArg(a, b, _) => { vararg_instruction(frame, a, b) }
         Instruction::Return(a, b, _) => { return_instruction(frame, a, b, return_values)?; *pc = usize::MAX; }
-        Instruction::ForLoop(a, b) => { for_loop(frame, pc, a, b); }
-        Instruction::ForPrep(a, b) => { for_prep(frame, pc, a, b); }
-        _ => { panic!("Not implemented {}", pc) }
+        Instruction::ForLoop(a, b) => { for_loop(frame, pc, a, b)?; }
+        Instruction::ForPrep(a, b) => { for_prep(frame, pc, a, b)?; }
+        Instruction::TForLoop(a, _, c) => { tforloop_instruction(frame, env, pc, a, c)? }
+        // skipped by the SetList it belongs to
+        Instruction::ExtraArg(_) => {}
     }
 
     Ok(())
 }
 
-/* function evaluating the function passed in argument and returning the results of the function through a vector
- * func : reference to the function we are evaluating
- * frame : stack frame of the current function
- * env : Table containing the global variables
- * upvalues : Vector of the upvalues of each function
- * result : Vector through which we return the result of the function
+/// evaluates a call of a lua function and returns its results
+fn eval_sequence(env : &mut GlobalEnvironment, closure : Rc<Closure>, args : Vec<Value>) -> Result<Vec<Value>, InterpreterError> {
+    let mut activations = vec![Activation::new(env, closure, args)?];
+    match eval_closure(env, &mut activations, false)? {
+        Completion::Return(values) => { Ok(values) }
+        // only the activations of a coroutine are suspended
+        Completion::Yield(_) => { unreachable!() }
+    }
+}
+
+/// the value is a table or has a handler for the event, it can't cause an index error
+fn is_indexable(env : &GlobalEnvironment, val : &Value, event : MetaMethod) -> bool {
+    matches!(val, Value::Table(_)) || !metatable::get_metamethod(env, val, event).is_nil()
+}
+
+/* names the value responsible for an error raised by the instruction at index pc,
+ * as in "attempt to call a nil value (global 'foo')"
+ * only the values read from the registers of the instruction can be named
  */
-fn eval_sequence<'cur>(
-    func : &'cur Function, 
-    mut frame : CallFrame<'cur>, 
-    env : &mut GlobalEnvironment<'cur>,
-    upvalues : &mut Vec<Vec<Value<'cur>>>,
-    result : &mut Vec<Value<'cur>>) 
-    -> Result<(), InterpreterError> {
+fn name_culprit(proto : &Function, frame : &CallFrame, env : &GlobalEnvironment, pc : usize, err : InterpreterError) -> InterpreterError {
+    let culprit = match (&err, &proto.instr_list[pc]) {
+        // the iterator of a generic for is called from a copy, it is not named
+        (InterpreterError::CallError { typ }, Instruction::Call(a, _, _) | Instruction::TailCall(a, _, _)) => {
+            // a __call handler that can't be called is not named
+            Some(*a).filter(|&a| frame.load(a).get_type() == *typ)
+        }
+        (InterpreterError::IndexError { .. }, Instruction::GetTable(_, b, _) | Instruction::SelF(_, b, _)) => {
+            Some(*b).filter(|&b| !is_indexable(env, &frame.load(b), MetaMethod::Index))
+        }
+        (InterpreterError::IndexError { .. }, Instruction::SetTable(a, _, _)) => {
+            Some(*a).filter(|&a| !is_indexable(env, &frame.load(a), MetaMethod::NewIndex))
+        }
+        (InterpreterError::ArithmeticError { .. },
+         Instruction::Add(_, b, c) | Instruction::Sub(_, b, c) | Instruction::Mul(_, b, c)
+         | Instruction::Div(_, b, c) | Instruction::Mod(_, b, c) | Instruction::Pow(_, b, c)) => {
+            // the first operand that is not a number, constants are not named
+            [*b, *c].into_iter()
+                .find(|&r| get_rk(proto, frame, r).get_number().is_none())
+                .filter(|&r| r < 256)
+        }
+        (InterpreterError::ArithmeticError { .. }, Instruction::Unm(_, b, _))
+        | (InterpreterError::LengthError { .. }, Instruction::Len(_, b, _)) => {
+            Some(*b)
+        }
+        (InterpreterError::ConcatError { typ }, Instruction::Concat(_, b, c)) => {
+            (*b..=*c).rev().find(|&r| {
+                let val = frame.load(r);
+                !is_concatenable(&val) && val.get_type() == *typ
+            })
+        }
+        _ => { None }
+    };
 
-    let mut pc = 0;
+    match culprit.and_then(|r| debug_info::object_name(proto, pc, r)) {
+        Some((kind, name)) => { InterpreterError::NamedError { error: Box::new(err), kind, name } }
+        None => { err }
+    }
+}
 
-    while pc < func.instr_list.len() {
-        pc += 1;
-        eval_instruction(&func, &func.instr_list[pc - 1], &mut frame, env, upvalues, &mut pc, result)?;
+/// raises an error of the function on top of the activations and removes them from the call stack
+fn unwind(env : &mut GlobalEnvironment, activations : &mut Vec<Activation>, err : InterpreterError) -> InterpreterError {
+    // the functions are still on the call stack when the error is raised
+    let err = raise_error(env, err);
+    for _ in activations.drain(..) {
+        env.call_stack.pop();
     }
+    err
+}
 
+/// evaluates the function on top of the activations until it returns or calls a lua function
+fn eval_activation(
+    activation : &mut Activation,
+    env : &mut GlobalEnvironment,
+    result : &mut Vec<Value>,
+    call : &mut Option<LuaCall>)
+    -> Result<(), InterpreterError> {
+
+    let closure = Rc::clone(&activation.closure);
+    let instr_list = &closure.proto.instr_list;
+
+    while activation.pc < instr_list.len() && call.is_none() {
+        env.budget.step()?;
+        let current = activation.pc;
+        activation.pc += 1;
+        env.call_stack.set_pc(activation.depth, activation.pc);
+        eval_instruction(&closure, &instr_list[current], &mut activation.frame, env, &mut activation.pc, result, call)
+            .map_err(|err| name_culprit(&closure.proto, &activation.frame, env, current, err))?;
+    }
     Ok(())
 }
 
-/// Instantiates the global environment and the local environment for the first function
-/// and evaluates the function passed in argument
-pub fn eval_program(mut main : Function) -> Result<(), Box<dyn Error>> {
+/* evaluation loop of the lua functions, a lua function called by the function on top of the activations is pushed above it
+ * so that lua calls do not grow the rust stack and a coroutine can be suspended with all the functions it called
+ * coroutine : the activations belong to a coroutine, a call to coroutine.yield by the function on top suspends them
+ * the loop ends when the first activation returns
+ */
+pub fn eval_closure(env : &mut GlobalEnvironment, activations : &mut Vec<Activation>, coroutine : bool) -> Result<Completion, InterpreterError> {
+    let mut result = Vec::new();
+    let mut call = None;
+
+    loop {
+        let activation = activations.last_mut().expect("the loop ends with the first activation");
+        let depth = activation.depth;
+
+        if let Err(err) = eval_activation(activation, env, &mut result, &mut call) {
+            let err = match err {
+                // the call is completed by the values given to the next resume
+                InterpreterError::Yield { values, depth: yield_depth } if coroutine && yield_depth == depth + 1 => {
+                    return Ok(Completion::Yield(values));
+                }
+                // the runtime functions and the evaluation loop called between the coroutine and coroutine.yield can't be suspended
+                err @ InterpreterError::Yield { .. } => {
+                    InterpreterError::RaisedError { value: Value::from_string(&err.to_string()) }
+                }
+                err => { err }
+            };
+            return Err(unwind(env, activations, err));
+        }
+
+        match call.take() {
+            Some(LuaCall::Call(next_func, args)) => {
+                match Activation::new(env, next_func, args) {
+                    Ok(next) => { activations.push(next); }
+                    Err(err) => { return Err(unwind(env, activations, err)) }
+                }
+            }
+            // the called function replaces the current one
+            Some(LuaCall::TailCall(next_func, args)) => {
+                match prepare_frame(&next_func.proto, args) {
+                    Ok(frame) => {
+                        let activation = activations.last_mut().expect("the loop ends with the first activation");
+                        activation.frame = frame;
+                        activation.pc = 0;
+                        env.call_stack.set_closure(activation.depth, Rc::clone(&next_func));
+                        activation.closure = next_func;
+                    }
+                    Err(err) => { return Err(unwind(env, activations, err)) }
+                }
+            }
+            None => {
+                activations.pop();
+                env.call_stack.pop();
+                let values = std::mem::take(&mut result);
+                match activations.last_mut() {
+                    Some(caller) => { caller.complete_call(values); }
+                    None => { return Ok(Completion::Return(values)) }
+                }
+            }
+        }
+    }
+}
 
-    let frame : CallFrame<'_> = CallFrame::with_capacity(main.stack);
-    let mut global_environement = GlobalEnvironment::new();
-    let mut result: Vec<Value<'_>> = Vec::new();
+/* runs the main function of a chunk, math.random starts from the given seed
+ * the io and os libraries only contain the functions allowed by the capabilities
+ * print and io.write write to the output, io.stderr to the error output
+ * the limits apply to the whole run
+ */
+pub fn eval_program(main : Function, random_seed : u64, capabilities : Capabilities, limits : Limits, output : Output, error_output : Output) -> Result<(), Box<dyn Error>> {
+
+    let mut global_environement = GlobalEnvironment::new(&capabilities);
+    global_environement.random = Random::new(random_seed);
+    global_environement.budget = Budget::new(limits)?;
+    global_environement.output.redirect(output);
+    global_environement.error_output.redirect(error_output);
 
-    // We assign an id to each function that we use the find the upvalue list of each function during the interpretation
-    let nb_upvalues = main.assign_upval_id();
-    let mut upvalues_lists = vec![Vec::new(); nb_upvalues + 1];
+    let main = Rc::new(Closure::new(Rc::new(main), global_environement.globals()));
 
-    eval_sequence(&main, frame, &mut global_environement, &mut upvalues_lists, &mut result)?;
+    // errors that are not caught by the script are given with the traceback of the call stack
+    global_environement.error_handlers.push(Value::RuntimeFunction(runtime_library::traceback_lua));
+
+    let res = eval_sequence(&mut global_environement, main, Vec::new());
+    let _ = global_environement.output.flush();
+    res?;
 
     Ok(())
-}
\ No newline at end of file
+}
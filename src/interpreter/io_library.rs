@@ -0,0 +1,474 @@
+use std::any::TypeId;
+use std::cell::{RefCell, RefMut};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+use crate::interpreter::{call_frame::CallFrame, global_environment::GlobalEnvironment, object::{str_to_number, LuaStr, TableRef, Value}};
+use crate::interpreter::interpreter::{call_value, InterpreterError};
+use crate::interpreter::metatable;
+use crate::interpreter::output::Output;
+use crate::interpreter::runtime_library::{check_string, type_error, RuntimeClosure};
+use crate::interpreter::sandbox::Capabilities;
+use crate::interpreter::table::Table;
+use crate::interpreter::userdata::UserData;
+
+#[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum IoLibraryError {
+    #[error("attempt to use a closed file")]
+    ClosedFileError,
+    #[error("file is already closed")]
+    AlreadyClosedError,
+    #[error("the file system is not available")]
+    FileSystemError,
+    #[error("the standard input and output are not available")]
+    StandardIoError
+}
+
+// error numbers of the C library, used when an operation is not supported by a stream
+const EBADF : i32 = 9;
+const EINVAL : i32 = 22;
+const ESPIPE : i32 = 29;
+
+enum Stream {
+    Stdin,
+    // standard output or error of the state
+    Output(Output),
+    File(BufReader<File>)
+}
+
+impl Stream {
+
+    fn read<T>(&mut self, f : impl FnOnce(&mut dyn BufRead) -> io::Result<T>) -> io::Result<T> {
+        match self {
+            Stream::Stdin => { f(&mut io::stdin().lock()) }
+            Stream::File(reader) => { f(reader) }
+            _ => { Err(io::Error::from_raw_os_error(EBADF)) }
+        }
+    }
+
+    fn write(&mut self, data : &[u8]) -> io::Result<()> {
+        match self {
+            Stream::Output(output) => { output.write_all(data) }
+            Stream::File(reader) => {
+                // the file is moved back to the position of the reads before writing
+                if !reader.buffer().is_empty() {
+                    let position = reader.stream_position()?;
+                    reader.seek(SeekFrom::Start(position))?;
+                }
+                reader.get_mut().write_all(data)
+            }
+            Stream::Stdin => { Err(io::Error::from_raw_os_error(EBADF)) }
+        }
+    }
+
+    fn seek(&mut self, position : SeekFrom) -> io::Result<u64> {
+        match self {
+            Stream::File(reader) => { reader.seek(position) }
+            _ => { Err(io::Error::from_raw_os_error(ESPIPE)) }
+        }
+    }
+
+}
+
+/// File handle of the scripts, the stream is dropped when the file is closed
+struct LuaFile {
+    stream   : Option<Stream>,
+    standard : bool
+}
+
+impl LuaFile {
+
+    fn stream(&mut self) -> Result<&mut Stream, IoLibraryError> {
+        self.stream.as_mut().ok_or(IoLibraryError::ClosedFileError)
+    }
+
+}
+
+/// returns the file held by a handle, the functions of the library don't borrow it twice
+fn lua_file(handle : &UserData) -> RefMut<'_, LuaFile> {
+    handle.borrow_mut::<LuaFile>().expect("file handles hold a file")
+}
+
+/// message of an io error without the error number added by the standard library
+fn error_message(err : &io::Error) -> String {
+    let msg = err.to_string();
+    match msg.rfind(" (os error ") {
+        Some(i) => { String::from(&msg[..i]) }
+        None => { msg }
+    }
+}
+
+/// returns nil, the message and the error number of a failed operation, as the functions of the io library
+fn push_error(returned_values : &mut Vec<Value>, err : &io::Error, file_name : Option<&[u8]>) {
+    let mut msg = Vec::new();
+    if let Some(name) = file_name {
+        msg.extend_from_slice(name);
+        msg.extend_from_slice(b": ");
+    }
+    msg.extend_from_slice(error_message(err).as_bytes());
+    returned_values.push(Value::Nil);
+    returned_values.push(Value::from_bytes(&msg));
+    returned_values.push(Value::Number(err.raw_os_error().unwrap_or(0) as f64));
+}
+
+/// opens a file with a mode of fopen: "r", "w", "a", "r+", "w+" or "a+" followed by an optional 'b'
+fn open_file(name : &[u8], mode : &[u8]) -> io::Result<File> {
+    let mode = mode.strip_suffix(b"b").unwrap_or(mode);
+    let mut options = OpenOptions::new();
+    match mode {
+        b"r" => { options.read(true) }
+        b"w" => { options.write(true).create(true).truncate(true) }
+        b"a" => { options.append(true).create(true) }
+        b"r+" => { options.read(true).write(true) }
+        b"w+" => { options.read(true).write(true).create(true).truncate(true) }
+        b"a+" => { options.read(true).append(true).create(true) }
+        _ => { return Err(io::Error::from_raw_os_error(EINVAL)) }
+    };
+    options.open(String::from_utf8_lossy(name).as_ref())
+}
+
+/// reads a line without its end of line, None at the end of the stream
+fn read_line(input : &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if input.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+/// reads at most n bytes, None at the end of the stream
+fn read_chars(input : &mut dyn BufRead, n : u64) -> io::Result<Option<Vec<u8>>> {
+    if n == 0 {
+        // tests the end of the stream
+        return Ok(if input.fill_buf()?.is_empty() { None } else { Some(Vec::new()) });
+    }
+    let mut res = Vec::new();
+    input.take(n).read_to_end(&mut res)?;
+    Ok(if res.is_empty() { None } else { Some(res) })
+}
+
+/// next byte of the stream without reading it, None at the end of the stream
+fn peek(input : &mut dyn BufRead) -> io::Result<Option<u8>> {
+    Ok(input.fill_buf()?.first().copied())
+}
+
+/// reads the next byte into the token if it is accepted
+fn accept(input : &mut dyn BufRead, token : &mut Vec<u8>, accepted : impl Fn(u8) -> bool) -> io::Result<bool> {
+    match peek(input)? {
+        Some(c) if accepted(c) => {
+            token.push(c);
+            input.consume(1);
+            Ok(true)
+        }
+        _ => { Ok(false) }
+    }
+}
+
+/* reads a number after optional spaces, as fscanf with "%lf"
+ * only the bytes that can continue a number are read: a sign, a 0x prefix, digits, a point and an exponent
+ */
+fn read_number(input : &mut dyn BufRead) -> io::Result<Option<f64>> {
+    while accept(input, &mut Vec::new(), |c| c.is_ascii_whitespace())? {}
+
+    let mut token = Vec::new();
+    accept(input, &mut token, |c| c == b'+' || c == b'-')?;
+    let mut hex = false;
+    if accept(input, &mut token, |c| c == b'0')? {
+        hex = accept(input, &mut token, |c| c == b'x' || c == b'X')?;
+    }
+    let is_digit = |c : u8| if hex { c.is_ascii_hexdigit() } else { c.is_ascii_digit() };
+    while accept(input, &mut token, is_digit)? {}
+    if accept(input, &mut token, |c| c == b'.')? {
+        while accept(input, &mut token, is_digit)? {}
+    }
+    let exponent : &[u8] = if hex { b"pP" } else { b"eE" };
+    if accept(input, &mut token, |c| exponent.contains(&c))? {
+        accept(input, &mut token, |c| c == b'+' || c == b'-')?;
+        while accept(input, &mut token, |c| c.is_ascii_digit())? {}
+    }
+    Ok(str_to_number(&token))
+}
+
+/* reads the arguments from first with the formats "*l" (a line, the default), "*a" (the rest of the file),
+ * "*n" (a number) or a number of bytes, the values stop at the first one that cannot be read which is nil
+ */
+fn read(file : &UserData, frame : &CallFrame, first : usize, returned_values : &mut Vec<Value>) -> Result<(), InterpreterError> {
+    let mut file = lua_file(file);
+    let stream = file.stream()?;
+    let last = frame.len().max(first + 1);
+    let start = returned_values.len();
+
+    for i in first..last {
+        let format = frame.load(i);
+        let res = match format {
+            Value::Nil if i >= frame.len() => { stream.read(|input| Ok(read_line(input)?.map(|line| Value::from_bytes(&line)))) }
+            Value::Number(n) => { stream.read(|input| Ok(read_chars(input, n.max(0.) as u64)?.map(|s| Value::from_bytes(&s)))) }
+            _ => {
+                let option = match format.get_string() {
+                    Some(option) if option.first() == Some(&b'*') => { option.clone() }
+                    _ => { return Err(InterpreterError::ArgumentError { arg: i + 1, func: "read", msg: String::from("invalid option") }) }
+                };
+                match option.get(1) {
+                    Some(b'l') => { stream.read(|input| Ok(read_line(input)?.map(|line| Value::from_bytes(&line)))) }
+                    Some(b'n') => { stream.read(|input| Ok(read_number(input)?.map(Value::Number))) }
+                    Some(b'a') => {
+                        stream.read(|input| {
+                            let mut res = Vec::new();
+                            input.read_to_end(&mut res)?;
+                            Ok(Some(Value::from_bytes(&res)))
+                        })
+                    }
+                    _ => { return Err(InterpreterError::ArgumentError { arg: i + 1, func: "read", msg: String::from("invalid format") }) }
+                }
+            }
+        };
+
+        match res {
+            Ok(Some(val)) => { returned_values.push(val) }
+            Ok(None) => { returned_values.push(Value::Nil); break }
+            Err(err) => {
+                returned_values.truncate(start);
+                push_error(returned_values, &err, None);
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// writes the strings or numbers given from the argument first and returns the handle, or nil and an error message
+fn write(file : &UserData, handle : Value, frame : &CallFrame, first : usize, returned_values : &mut Vec<Value>) -> Result<(), InterpreterError> {
+    let mut file = lua_file(file);
+    let stream = file.stream()?;
+    for i in first..frame.len() {
+        let s = check_string(frame, i, "write")?;
+        if let Err(err) = stream.write(&s) {
+            push_error(returned_values, &err, None);
+            return Ok(());
+        }
+    }
+    returned_values.push(handle);
+    Ok(())
+}
+
+/// returns an iterator over the lines of the file, the file is closed at its end if close is true
+fn lines_iterator(file : Rc<UserData>, close : bool) -> Value {
+    RuntimeClosure::new_value(move |_, returned_values, _| {
+        let mut file = lua_file(&file);
+        let stream = file.stream.as_mut().ok_or(IoLibraryError::AlreadyClosedError)?;
+        match stream.read(read_line) {
+            Ok(Some(line)) => { returned_values.push(Value::from_bytes(&line)) }
+            Ok(None) => {
+                if close {
+                    file.stream = None;
+                }
+                returned_values.push(Value::Nil);
+            }
+            Err(err) => { return Err(InterpreterError::RaisedError { value: Value::from_string(&error_message(&err)) }) }
+        }
+        Ok(())
+    })
+}
+
+/// returns the handle passed as the argument number i (starting from 0) of the runtime function
+fn check_file(frame : &CallFrame, i : usize, func : &'static str) -> Result<Rc<UserData>, InterpreterError> {
+    match frame.load(i) {
+        Value::UserData(handle) if handle.is::<LuaFile>() => { Ok(handle) }
+        _ => { Err(type_error(frame, i, func, "FILE*")) }
+    }
+}
+
+/// file:read(...) reads the file with the given formats
+fn read_method(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let file = check_file(&frame, 0, "read")?;
+    read(&file, &frame, 1, returned_values)
+}
+
+/// file:write(...) writes the strings or numbers to the file
+fn write_method(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let file = check_file(&frame, 0, "write")?;
+    write(&file, frame.load(0), &frame, 1, returned_values)
+}
+
+/// file:lines() returns an iterator over the lines of the file, which stays open
+fn lines_method(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let file = check_file(&frame, 0, "lines")?;
+    lua_file(&file).stream()?;
+    returned_values.push(lines_iterator(file, false));
+    Ok(())
+}
+
+/// file:seek(whence, offset) moves to offset from "set" (the start), "cur" (the default) or "end", and returns the position
+fn seek_method(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let file = check_file(&frame, 0, "seek")?;
+    let mut file = lua_file(&file);
+    let stream = file.stream()?;
+    let whence = match frame.load(1) {
+        Value::Nil => { Value::from_string("cur") }
+        _ => { Value::LuaString(check_string(&frame, 1, "seek")?) }
+    };
+    let offset = match frame.load(2) {
+        Value::Nil => { 0 }
+        val => { val.get_number().ok_or_else(|| InterpreterError::ArgumentError { arg: 3, func: "seek", msg: format!("number expected, got {}", val.get_type()) })? as i64 }
+    };
+    let position = match whence.get_string().map(|s| &s[..]) {
+        Some(b"set") => { SeekFrom::Start(offset.max(0) as u64) }
+        Some(b"cur") => { SeekFrom::Current(offset) }
+        Some(b"end") => { SeekFrom::End(offset) }
+        _ => { return Err(InterpreterError::ArgumentError { arg: 2, func: "seek", msg: format!("invalid option '{}'", whence) }) }
+    };
+    match stream.seek(position) {
+        Ok(position) => { returned_values.push(Value::Number(position as f64)) }
+        Err(err) => { push_error(returned_values, &err, None) }
+    }
+    Ok(())
+}
+
+/// file:close() closes the file, the standard files cannot be closed
+fn close_method(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let file = check_file(&frame, 0, "close")?;
+    let mut file = lua_file(&file);
+    file.stream()?;
+    if file.standard {
+        returned_values.push(Value::Nil);
+        returned_values.push(Value::from_string("cannot close standard file"));
+    } else {
+        file.stream = None;
+        returned_values.push(Value::Boolean(true));
+    }
+    Ok(())
+}
+
+/// tostring(file) gives the address of the file, or tells that it is closed
+fn tostring_method(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let handle = check_file(&frame, 0, "tostring")?;
+    let text = match lua_file(&handle).stream {
+        Some(_) => { format!("file ({:p})", Rc::as_ptr(&handle)) }
+        None => { String::from("file (closed)") }
+    };
+    returned_values.push(Value::from_string(&text));
+    Ok(())
+}
+
+/// stores a value in a table with a string key
+fn set_field(table : &mut Table, name : &str, val : Value) {
+    table.set(Value::from_string(name), val).expect("field names are strings");
+}
+
+/// creates the userdata representing a file in the scripts, its methods are in the metatable registered by new_library
+fn new_handle(env : &GlobalEnvironment, stream : Stream, standard : bool) -> Rc<UserData> {
+    env.new_userdata(LuaFile { stream: Some(stream), standard })
+}
+
+/// io.open(filename, mode) opens a file with a mode of fopen ("r" by default) and returns its handle, or nil and an error message
+fn open_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let name = check_string(&frame, 0, "open")?;
+    let mode = match frame.load(1) {
+        Value::Nil => { LuaStr::from(&b"r"[..]) }
+        _ => { check_string(&frame, 1, "open")? }
+    };
+    match open_file(&name, &mode) {
+        Ok(f) => { returned_values.push(Value::UserData(new_handle(env, Stream::File(BufReader::new(f)), false))) }
+        Err(err) => { push_error(returned_values, &err, Some(&name)) }
+    }
+    Ok(())
+}
+
+/// io.close(file) closes the file, the standard output by default
+fn close_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment, stdout : &Value) -> Result<(), InterpreterError> {
+    let handle = match frame.load(0) {
+        Value::Nil => { stdout.clone() }
+        handle => { handle }
+    };
+    let close = metatable::index(env, handle.clone(), Value::from_string("close"))?;
+    returned_values.extend(call_value(env, close, vec![handle])?);
+    Ok(())
+}
+
+/* io.lines(filename) returns an iterator over the lines of the file, which is closed at its end
+ * without file name the lines are read from the standard input
+ */
+fn lines_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &GlobalEnvironment, capabilities : &Capabilities, stdin : &Option<Rc<UserData>>) -> Result<(), InterpreterError> {
+    match frame.load(0) {
+        Value::Nil => {
+            let stdin = stdin.as_ref().ok_or(IoLibraryError::StandardIoError)?;
+            returned_values.push(lines_iterator(Rc::clone(stdin), false));
+        }
+        _ => {
+            let name = check_string(&frame, 0, "lines")?;
+            if !capabilities.file_system {
+                return Err(IoLibraryError::FileSystemError.into());
+            }
+            let f = open_file(&name, b"r").map_err(|err| InterpreterError::ArgumentError {
+                arg: 1, func: "lines", msg: format!("{}: {}", String::from_utf8_lossy(&name), error_message(&err))
+            })?;
+            let file = new_handle(env, Stream::File(BufReader::new(f)), false);
+            returned_values.push(lines_iterator(file, true));
+        }
+    }
+    Ok(())
+}
+
+/* creates the io library with the functions allowed by the capabilities:
+ * io.read, io.write and the standard files with standard_io, io.open with file_system
+ * the handles are userdata sharing a metatable registered in the environment
+ */
+pub fn new_library(env : &mut GlobalEnvironment, capabilities : &Capabilities) -> TableRef {
+    let mut methods = Table::new();
+    set_field(&mut methods, "read", Value::RuntimeFunction(read_method));
+    set_field(&mut methods, "write", Value::RuntimeFunction(write_method));
+    set_field(&mut methods, "lines", Value::RuntimeFunction(lines_method));
+    set_field(&mut methods, "seek", Value::RuntimeFunction(seek_method));
+    set_field(&mut methods, "close", Value::RuntimeFunction(close_method));
+    let mut metatable = Table::new();
+    set_field(&mut metatable, "__index", Value::Table(Rc::new(RefCell::new(methods))));
+    set_field(&mut metatable, "__tostring", Value::RuntimeFunction(tostring_method));
+    env.userdata_metatables.insert(TypeId::of::<LuaFile>(), Rc::new(RefCell::new(metatable)));
+
+    let mut library = Table::new();
+
+    let mut stdin = None;
+    let mut stdout = Value::Nil;
+    if capabilities.standard_io {
+        let input = new_handle(env, Stream::Stdin, true);
+        let output = new_handle(env, Stream::Output(env.output.clone()), true);
+        set_field(&mut library, "stdin", Value::UserData(Rc::clone(&input)));
+        set_field(&mut library, "stdout", Value::UserData(Rc::clone(&output)));
+        set_field(&mut library, "stderr", Value::UserData(new_handle(env, Stream::Output(env.error_output.clone()), true)));
+
+        // io.read(...) reads the standard input with the formats of file:read
+        let file = Rc::clone(&input);
+        set_field(&mut library, "read", RuntimeClosure::new_value(move |frame, returned_values, _| {
+            read(&file, &frame, 0, returned_values)
+        }));
+        // io.write(...) writes the strings or numbers to the standard output
+        let file = Rc::clone(&output);
+        set_field(&mut library, "write", RuntimeClosure::new_value(move |frame, returned_values, _| {
+            write(&file, Value::UserData(Rc::clone(&file)), &frame, 0, returned_values)
+        }));
+
+        stdin = Some(input);
+        stdout = Value::UserData(output);
+    }
+
+    if capabilities.file_system {
+        set_field(&mut library, "open", Value::RuntimeFunction(open_lua));
+    }
+
+    if capabilities.standard_io || capabilities.file_system {
+        let allowed = *capabilities;
+        set_field(&mut library, "lines", RuntimeClosure::new_value(move |frame, returned_values, env| {
+            lines_lua(frame, returned_values, env, &allowed, &stdin)
+        }));
+        set_field(&mut library, "close", RuntimeClosure::new_value(move |frame, returned_values, env| {
+            close_lua(frame, returned_values, env, &stdout)
+        }));
+    }
+
+    Rc::new(RefCell::new(library))
+}
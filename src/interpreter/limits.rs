@@ -0,0 +1,287 @@
+use core::fmt;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::hint;
+use std::time::{Duration, Instant};
+
+use crate::interpreter::interpreter::InterpreterError;
+
+// Number of instructions between two checks of the time and memory limits
+const CHECK_INTERVAL : u64 = 1024;
+
+/** Resources a state can use while it runs a chunk or a function called by the host, None for no limit
+ * exceeding the call depth or the string length raises an error the scripts can catch with pcall,
+ * exceeding the instructions, the memory or the time stops the scripts: the error goes through pcall up to the host
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    /// instructions of lua functions executed
+    pub instructions  : Option<u64>,
+    /// bytes allocated on the thread running the scripts, measured by CountingAllocator which must be the global allocator
+    pub memory        : Option<usize>,
+    /// functions in the call stack
+    pub call_depth    : Option<usize>,
+    /// length in bytes of the strings built by concatenation and by the string and table libraries
+    pub string_length : Option<usize>,
+    /// time elapsed since the host started the run
+    pub time          : Option<Duration>
+}
+
+/// Limits a state can't enforce
+#[derive(thiserror::Error, Debug)]
+pub enum LimitsError {
+    #[error("the memory limit needs CountingAllocator as the global allocator")]
+    AllocatorError
+}
+
+/// Resource whose limit was exceeded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Instructions,
+    Memory,
+    CallDepth,
+    StringLength,
+    Time
+}
+
+impl Limit {
+
+    /// the scripts can't catch the error, it stops them
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Limit::Instructions | Limit::Memory | Limit::Time)
+    }
+
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Limit::Instructions => { "instruction" }
+            Limit::Memory => { "memory" }
+            Limit::CallDepth => { "call depth" }
+            Limit::StringLength => { "string length" }
+            Limit::Time => { "time" }
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/** Resources used by a state since the start of the current run, compared with its limits
+ * the instructions are counted down to the next check so that the evaluation loop only decrements a counter,
+ * the time and the memory are only read by the checks
+ * the loops of the runtime functions charge their work, the time and the memory are checked after every CHECK_INTERVAL units
+ */
+#[derive(Debug)]
+pub struct Budget {
+    limits    : Limits,
+    // instructions that can run before the next check
+    countdown : u64,
+    // instructions allowed between the last two checks
+    period    : u64,
+    // instructions executed before the last check
+    executed  : u64,
+    // work charged by the runtime functions since the last check of the time and the memory
+    work      : u64,
+    start     : Instant,
+    // bytes allocated by the thread when the run started
+    memory    : usize
+}
+
+impl Budget {
+
+    /// fails if there is a memory limit and CountingAllocator is not the global allocator
+    pub fn new(limits : Limits) -> Result<Self, LimitsError> {
+        if limits.memory.is_some() && !CountingAllocator::is_active() {
+            return Err(LimitsError::AllocatorError);
+        }
+        let mut res = Budget { limits, countdown: 0, period: 0, executed: 0, work: 0, start: Instant::now(), memory: 0 };
+        res.start();
+        Ok(res)
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// starts a run, the resources used by the previous ones are forgotten
+    pub fn start(&mut self) {
+        self.executed = 0;
+        self.work = 0;
+        self.start = Instant::now();
+        self.memory = CountingAllocator::allocated();
+        self.period = self.next_period();
+        self.countdown = self.period;
+    }
+
+    // instructions allowed before the next check, all of them without limits on the time or the memory
+    fn next_period(&self) -> u64 {
+        let remaining = self.limits.instructions.map_or(u64::MAX, |max| max.saturating_sub(self.executed));
+        if self.limits.time.is_some() || self.limits.memory.is_some() {
+            remaining.min(CHECK_INTERVAL)
+        } else {
+            remaining
+        }
+    }
+
+    /// counts an instruction about to be executed
+    #[inline]
+    pub fn step(&mut self) -> Result<(), InterpreterError> {
+        if self.countdown == 0 {
+            return self.check();
+        }
+        self.countdown -= 1;
+        Ok(())
+    }
+
+    // a limit exceeded once stays exceeded until the next run, the countdown is not restarted
+    #[cold]
+    #[inline(never)]
+    fn check(&mut self) -> Result<(), InterpreterError> {
+        self.executed = self.executed.saturating_add(self.period);
+        self.period = 0;
+        if self.limits.instructions.is_some_and(|max| self.executed >= max) {
+            return Err(InterpreterError::LimitError { limit: Limit::Instructions });
+        }
+        self.resources().check()?;
+        // the instruction being checked is the first one of the new period
+        self.period = self.next_period();
+        self.countdown = self.period.saturating_sub(1);
+        Ok(())
+    }
+
+    /// counts work done by a runtime function, such as an iteration of a loop, and checks the time and the memory from time to time
+    #[inline]
+    pub fn charge(&mut self, work : u64) -> Result<(), InterpreterError> {
+        self.work = self.work.saturating_add(work);
+        if self.work < CHECK_INTERVAL {
+            return Ok(());
+        }
+        self.work = 0;
+        self.resources().check()
+    }
+
+    /// limits on the time and the memory of the run, for the loops that can't borrow the budget
+    pub fn resources(&self) -> Resources {
+        Resources { start: self.start, time: self.limits.time, memory: self.memory, max_memory: self.limits.memory }
+    }
+
+    /// checks the depth of a function about to be called
+    pub fn check_call_depth(&self, depth : usize) -> Result<(), InterpreterError> {
+        match self.limits.call_depth {
+            Some(max) if depth >= max => { Err(InterpreterError::LimitError { limit: Limit::CallDepth }) }
+            _ => { Ok(()) }
+        }
+    }
+
+    /// checks the length of a string about to be built and the memory it needs
+    pub fn check_string_length(&self, len : usize) -> Result<(), InterpreterError> {
+        match self.limits.string_length {
+            Some(max) if len > max => { Err(InterpreterError::LimitError { limit: Limit::StringLength }) }
+            _ => { self.resources().check_allocation(len) }
+        }
+    }
+
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget::new(Limits::default()).expect("no limit")
+    }
+}
+
+/// Limits on the time and the memory of a run, copied from the budget
+#[derive(Clone, Copy, Debug)]
+pub struct Resources {
+    start      : Instant,
+    time       : Option<Duration>,
+    // bytes allocated by the thread when the run started
+    memory     : usize,
+    max_memory : Option<usize>
+}
+
+impl Resources {
+
+    /// checks the time elapsed and the memory used by the run
+    pub fn check(&self) -> Result<(), InterpreterError> {
+        if self.time.is_some_and(|max| self.start.elapsed() > max) {
+            return Err(InterpreterError::LimitError { limit: Limit::Time });
+        }
+        self.check_allocation(0)
+    }
+
+    /// checks that the memory used by the run leaves room for an allocation of size bytes
+    pub fn check_allocation(&self, size : usize) -> Result<(), InterpreterError> {
+        let Some(max) = self.max_memory else { return Ok(()) };
+        // the memory released by the scripts can make the thread use less than at the start
+        let memory = CountingAllocator::allocated().wrapping_sub(self.memory) as isize;
+        if (memory.max(0) as usize).saturating_add(size) > max {
+            return Err(InterpreterError::LimitError { limit: Limit::Memory });
+        }
+        Ok(())
+    }
+
+}
+
+thread_local! {
+    // bytes allocated and not yet released by the thread, wraps around when a thread releases memory allocated by another one
+    static ALLOCATED : Cell<usize> = const { Cell::new(0) };
+}
+
+fn count(allocated : usize, released : usize) {
+    // the counter is gone while the thread is destroyed
+    let _ = ALLOCATED.try_with(|n| n.set(n.get().wrapping_add(allocated).wrapping_sub(released)));
+}
+
+/** Global allocator of the system counting the bytes allocated by each thread, it measures the memory of the states for their limit
+ * the host program installs it with `#[global_allocator] static ALLOCATOR : CountingAllocator = CountingAllocator;`
+ */
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+
+    /// bytes allocated by the current thread and not released
+    pub fn allocated() -> usize {
+        ALLOCATED.try_with(Cell::get).unwrap_or(0)
+    }
+
+    /// tells if the allocator is the global allocator of the program
+    pub fn is_active() -> bool {
+        let before = CountingAllocator::allocated();
+        let probe = hint::black_box(Box::new(0u64));
+        let res = CountingAllocator::allocated() != before;
+        drop(probe);
+        res
+    }
+
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout : Layout) -> *mut u8 {
+        let res = System.alloc(layout);
+        if !res.is_null() {
+            count(layout.size(), 0);
+        }
+        res
+    }
+
+    unsafe fn alloc_zeroed(&self, layout : Layout) -> *mut u8 {
+        let res = System.alloc_zeroed(layout);
+        if !res.is_null() {
+            count(layout.size(), 0);
+        }
+        res
+    }
+
+    unsafe fn dealloc(&self, ptr : *mut u8, layout : Layout) {
+        System.dealloc(ptr, layout);
+        count(0, layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr : *mut u8, layout : Layout, new_size : usize) -> *mut u8 {
+        let res = System.realloc(ptr, layout, new_size);
+        if !res.is_null() {
+            count(new_size, layout.size());
+        }
+        res
+    }
+}
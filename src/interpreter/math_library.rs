@@ -0,0 +1,255 @@
+use crate::interpreter::{call_frame::CallFrame, global_environment::GlobalEnvironment, object::Value};
+use crate::interpreter::interpreter::InterpreterError;
+use crate::interpreter::runtime_library::check_number;
+
+/** Pseudo-random generator of math.random, xoshiro256** seeded with splitmix64
+ * it only uses integer arithmetic so that a seed gives the same sequence on every platform
+ */
+pub struct Random {
+    state : [u64; 4]
+}
+
+impl Random {
+
+    pub fn new(seed : u64) -> Self {
+        let mut x = seed;
+        let mut splitmix = || {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        Random { state: [splitmix(), splitmix(), splitmix(), splitmix()] }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let res = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        res
+    }
+
+    /// returns a number uniformly distributed in [0, 1)
+    pub fn next_float(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Random::new(0)
+    }
+}
+
+/// returns 2^exponent for an exponent in the range of normal numbers
+fn power_of_two(exponent : i32) -> f64 {
+    f64::from_bits(((exponent + 1023) as u64) << 52)
+}
+
+/// splits x into a mantissa in [0.5, 1) and a power of 2, as the C function frexp
+fn frexp(x : f64) -> (f64, i32) {
+    if x == 0. || !x.is_finite() {
+        return (x, 0);
+    }
+    // subnormal numbers are normalized first
+    let (x, offset) = if x.abs() < f64::MIN_POSITIVE { (x * power_of_two(54), -54) } else { (x, 0) };
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i32 - 1022;
+    let mantissa = f64::from_bits((bits & !(0x7ff << 52)) | (1022 << 52));
+    (mantissa, exponent + offset)
+}
+
+/// computes m * 2^e, as the C function ldexp
+fn ldexp(mut m : f64, mut e : i64) -> f64 {
+    // the multiplication is done in steps when 2^e is not representable
+    while e > 1023 && m.is_finite() && m != 0. {
+        m *= power_of_two(1023);
+        e -= 1023;
+    }
+    while e < -1022 && m.is_finite() && m != 0. {
+        m *= power_of_two(-1022);
+        e += 1022;
+    }
+    m * power_of_two(e.clamp(-1022, 1023) as i32)
+}
+
+/// common part of the functions of one number
+fn unary(frame : &CallFrame, returned_values : &mut Vec<Value>, func : &'static str, f : fn(f64) -> f64) -> Result<(), InterpreterError> {
+    let x = check_number(frame, 0, func)?;
+    returned_values.push(Value::Number(f(x)));
+    Ok(())
+}
+
+pub fn floor_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "floor", f64::floor)
+}
+
+pub fn ceil_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "ceil", f64::ceil)
+}
+
+pub fn abs_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "abs", f64::abs)
+}
+
+pub fn sqrt_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "sqrt", f64::sqrt)
+}
+
+pub fn sin_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "sin", f64::sin)
+}
+
+pub fn cos_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "cos", f64::cos)
+}
+
+pub fn tan_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "tan", f64::tan)
+}
+
+pub fn asin_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "asin", f64::asin)
+}
+
+pub fn acos_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "acos", f64::acos)
+}
+
+pub fn atan_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "atan", f64::atan)
+}
+
+pub fn exp_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "exp", f64::exp)
+}
+
+pub fn log_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "log", f64::ln)
+}
+
+pub fn log10_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    unary(&frame, returned_values, "log10", f64::log10)
+}
+
+/// math.atan2(y, x) returns the arc tangent of y / x using the signs of both numbers to find the quadrant
+pub fn atan2_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let y = check_number(&frame, 0, "atan2")?;
+    let x = check_number(&frame, 1, "atan2")?;
+    returned_values.push(Value::Number(y.atan2(x)));
+    Ok(())
+}
+
+pub fn pow_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let x = check_number(&frame, 0, "pow")?;
+    let y = check_number(&frame, 1, "pow")?;
+    returned_values.push(Value::Number(x.powf(y)));
+    Ok(())
+}
+
+/// math.fmod(x, y) returns the remainder of x / y rounding the quotient towards zero
+pub fn fmod_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let x = check_number(&frame, 0, "fmod")?;
+    let y = check_number(&frame, 1, "fmod")?;
+    returned_values.push(Value::Number(x % y));
+    Ok(())
+}
+
+/// math.modf(x) returns the integral part and the fractional part of x
+pub fn modf_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let x = check_number(&frame, 0, "modf")?;
+    let fractional = if x.is_infinite() { 0. } else { x - x.trunc() };
+    returned_values.push(Value::Number(x.trunc()));
+    returned_values.push(Value::Number(fractional));
+    Ok(())
+}
+
+/// math.frexp(x) returns m and e such that x = m * 2^e, where the absolute value of m is in [0.5, 1) or zero
+pub fn frexp_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let (mantissa, exponent) = frexp(check_number(&frame, 0, "frexp")?);
+    returned_values.push(Value::Number(mantissa));
+    returned_values.push(Value::Number(exponent as f64));
+    Ok(())
+}
+
+/// math.ldexp(m, e) returns m * 2^e, e is an integer
+pub fn ldexp_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let m = check_number(&frame, 0, "ldexp")?;
+    let e = check_number(&frame, 1, "ldexp")? as i64;
+    returned_values.push(Value::Number(ldexp(m, e)));
+    Ok(())
+}
+
+/// math.min(x, ...) returns the smallest of its arguments
+pub fn min_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let mut min = check_number(&frame, 0, "min")?;
+    for i in 1..frame.len() {
+        let x = check_number(&frame, i, "min")?;
+        if x < min {
+            min = x;
+        }
+    }
+    returned_values.push(Value::Number(min));
+    Ok(())
+}
+
+/// math.max(x, ...) returns the largest of its arguments
+pub fn max_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let mut max = check_number(&frame, 0, "max")?;
+    for i in 1..frame.len() {
+        let x = check_number(&frame, i, "max")?;
+        if x > max {
+            max = x;
+        }
+    }
+    returned_values.push(Value::Number(max));
+    Ok(())
+}
+
+/* math.random() returns a number in [0, 1), math.random(m) an integer in [1, m] and math.random(m, n) an integer in [m, n]
+ * the numbers come from the generator of the environment, which the embedder can seed
+ */
+pub fn random_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let r = env.random.next_float();
+    let res = match frame.len() {
+        0 => { r }
+        1 => {
+            let upper = check_number(&frame, 0, "random")? as i64;
+            if upper < 1 {
+                return Err(InterpreterError::ArgumentError { arg: 1, func: "random", msg: String::from("interval is empty") });
+            }
+            (r * upper as f64).floor() + 1.
+        }
+        2 => {
+            let lower = check_number(&frame, 0, "random")? as i64;
+            let upper = check_number(&frame, 1, "random")? as i64;
+            if lower > upper {
+                return Err(InterpreterError::ArgumentError { arg: 2, func: "random", msg: String::from("interval is empty") });
+            }
+            // the bounds are saturated, the width of the interval is computed without overflow
+            let width = upper as i128 - lower as i128 + 1;
+            if width > i64::MAX as i128 {
+                return Err(InterpreterError::ArgumentError { arg: 1, func: "random", msg: String::from("interval is too large") });
+            }
+            (r * width as f64).floor() + lower as f64
+        }
+        _ => { return Err(InterpreterError::RandomArgumentsError) }
+    };
+    returned_values.push(Value::Number(res));
+    Ok(())
+}
+
+/// math.randomseed(x) restarts the sequence of math.random from the seed x
+pub fn randomseed_lua(frame : CallFrame, _ : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let seed = check_number(&frame, 0, "randomseed")? as i64;
+    env.random = Random::new(seed as u64);
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use std::cmp::Ordering;
+
+use crate::interpreter::global_environment::GlobalEnvironment;
+use crate::interpreter::interpreter::{call_value, InterpreterError};
+use crate::interpreter::object::{TableRef, Value};
+
+// Number of __index or __newindex handlers followed before giving up, same limit as the reference implementation
+const MAX_TAG_LOOP : usize = 100;
+
+/// Events that can be intercepted by a metamethod
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MetaMethod {
+    Index,
+    NewIndex,
+    Call,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Unm,
+    Concat,
+    Len,
+    Eq,
+    Lt,
+    Le,
+    ToString,
+    Metatable,
+    Gc
+}
+
+impl MetaMethod {
+
+    /// key of the metamethod in the metatable
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetaMethod::Index => "__index",
+            MetaMethod::NewIndex => "__newindex",
+            MetaMethod::Call => "__call",
+            MetaMethod::Add => "__add",
+            MetaMethod::Sub => "__sub",
+            MetaMethod::Mul => "__mul",
+            MetaMethod::Div => "__div",
+            MetaMethod::Mod => "__mod",
+            MetaMethod::Pow => "__pow",
+            MetaMethod::Unm => "__unm",
+            MetaMethod::Concat => "__concat",
+            MetaMethod::Len => "__len",
+            MetaMethod::Eq => "__eq",
+            MetaMethod::Lt => "__lt",
+            MetaMethod::Le => "__le",
+            MetaMethod::ToString => "__tostring",
+            MetaMethod::Metatable => "__metatable",
+            MetaMethod::Gc => "__gc"
+        }
+    }
+
+}
+
+/// returns the metatable of a value if it has one
+pub fn get_metatable(env : &GlobalEnvironment, val : &Value) -> Option<TableRef> {
+    match val {
+        Value::Table(t) => { t.borrow().metatable.clone() }
+        Value::UserData(u) => { u.metatable() }
+        // strings share one metatable
+        Value::LuaString(_) => { env.string_metatable.clone() }
+        _ => { None }
+    }
+}
+
+/// returns the handler of the event in the metatable of the value, nil if there is none
+pub fn get_metamethod(env : &GlobalEnvironment, val : &Value, event : MetaMethod) -> Value {
+    match get_metatable(env, val) {
+        Some(mt) => { mt.borrow().get_str(event.name()) }
+        None => { Value::Nil }
+    }
+}
+
+/// calls a metamethod and only keeps its first result
+pub fn call_metamethod(env : &mut GlobalEnvironment, handler : Value, args : Vec<Value>) -> Result<Value, InterpreterError> {
+    let results = call_value(env, handler, args)?;
+    Ok(results.into_iter().next().unwrap_or_default())
+}
+
+/// evaluates obj[key] following the __index chain
+pub fn index(env : &mut GlobalEnvironment, mut obj : Value, key : Value) -> Result<Value, InterpreterError> {
+
+    for _ in 0..MAX_TAG_LOOP {
+        let handler = match &obj {
+            Value::Table(t) => {
+                let res = t.borrow().get(&key);
+                if !res.is_nil() {
+                    return Ok(res);
+                }
+                let handler = get_metamethod(env, &obj, MetaMethod::Index);
+                if handler.is_nil() {
+                    return Ok(Value::Nil);
+                }
+                handler
+            }
+            _ => {
+                let handler = get_metamethod(env, &obj, MetaMethod::Index);
+                if handler.is_nil() {
+                    return Err(InterpreterError::IndexError { typ: obj.get_type() });
+                }
+                handler
+            }
+        };
+
+        match handler {
+            Value::LuaFunction(_) | Value::RuntimeFunction(_) | Value::RuntimeClosure(_) => {
+                return call_metamethod(env, handler, vec![obj, key]);
+            }
+            // the lookup is repeated on the handler
+            _ => { obj = handler; }
+        }
+    }
+
+    Err(InterpreterError::MetaLoopError { event: "gettable" })
+}
+
+/// evaluates obj[key] = val following the __newindex chain
+pub fn new_index(env : &mut GlobalEnvironment, mut obj : Value, key : Value, val : Value) -> Result<(), InterpreterError> {
+
+    for _ in 0..MAX_TAG_LOOP {
+        let handler = match &obj {
+            Value::Table(t) => {
+                let handler = get_metamethod(env, &obj, MetaMethod::NewIndex);
+                // the handler is only used for keys absent from the table
+                if handler.is_nil() || !t.borrow().get(&key).is_nil() {
+                    t.borrow_mut().set(key, val)?;
+                    return Ok(());
+                }
+                handler
+            }
+            _ => {
+                let handler = get_metamethod(env, &obj, MetaMethod::NewIndex);
+                if handler.is_nil() {
+                    return Err(InterpreterError::IndexError { typ: obj.get_type() });
+                }
+                handler
+            }
+        };
+
+        match handler {
+            Value::LuaFunction(_) | Value::RuntimeFunction(_) | Value::RuntimeClosure(_) => {
+                call_value(env, handler, vec![obj, key, val])?;
+                return Ok(());
+            }
+            _ => { obj = handler; }
+        }
+    }
+
+    Err(InterpreterError::MetaLoopError { event: "settable" })
+}
+
+/// handles an arithmetic operation on operands that are not both numbers
+pub fn arithmetic(env : &mut GlobalEnvironment, event : MetaMethod, a : Value, b : Value) -> Result<Value, InterpreterError> {
+    let mut handler = get_metamethod(env, &a, event);
+    if handler.is_nil() {
+        handler = get_metamethod(env, &b, event);
+    }
+
+    if handler.is_nil() {
+        // the error names the first operand that can't be converted to a number
+        let culprit = if a.get_number().is_none() { a } else { b };
+        return Err(InterpreterError::ArithmeticError { typ: culprit.get_type() });
+    }
+
+    call_metamethod(env, handler, vec![a, b])
+}
+
+/// handles the concatenation of two values that are not both strings or numbers
+pub fn concat(env : &mut GlobalEnvironment, a : Value, b : Value) -> Result<Value, InterpreterError> {
+    let mut handler = get_metamethod(env, &a, MetaMethod::Concat);
+    if handler.is_nil() {
+        handler = get_metamethod(env, &b, MetaMethod::Concat);
+    }
+
+    if handler.is_nil() {
+        let culprit = match a {
+            Value::LuaString(_) | Value::Number(_) => { b }
+            _ => { a }
+        };
+        return Err(InterpreterError::ConcatError { typ: culprit.get_type() });
+    }
+
+    call_metamethod(env, handler, vec![a, b])
+}
+
+/// returns the handler for a comparison, both operands must share the same handler
+fn get_comparison_handler(env : &GlobalEnvironment, a : &Value, b : &Value, event : MetaMethod) -> Value {
+    let handler_a = get_metamethod(env, a, event);
+    if handler_a.is_nil() {
+        return Value::Nil;
+    }
+    let handler_b = get_metamethod(env, b, event);
+    if handler_a == handler_b { handler_a } else { Value::Nil }
+}
+
+fn comparison_error(a : &Value, b : &Value) -> InterpreterError {
+    let (typ_a, typ_b) = (a.get_type(), b.get_type());
+    let operands = if typ_a == typ_b {
+        format!("two {} values", typ_a)
+    } else {
+        format!("{} with {}", typ_a, typ_b)
+    };
+    InterpreterError::CompareError { operands }
+}
+
+/// orders numbers and strings, other values are compared through metamethods
+fn primitive_order(a : &Value, b : &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(n1), Value::Number(n2)) => { n1.partial_cmp(n2) }
+        (Value::LuaString(s1), Value::LuaString(s2)) => { Some(s1.cmp(s2)) }
+        _ => { None }
+    }
+}
+
+/// evaluates a < b
+pub fn less_than(env : &mut GlobalEnvironment, a : Value, b : Value) -> Result<bool, InterpreterError> {
+    match (&a, &b) {
+        (Value::Number(_), Value::Number(_)) | (Value::LuaString(_), Value::LuaString(_)) => {
+            return Ok(primitive_order(&a, &b).is_some_and(Ordering::is_lt));
+        }
+        _ => {}
+    }
+
+    if a.get_type() == b.get_type() {
+        let handler = get_comparison_handler(env, &a, &b, MetaMethod::Lt);
+        if !handler.is_nil() {
+            return Ok(call_metamethod(env, handler, vec![a, b])?.to_boolean());
+        }
+    }
+
+    Err(comparison_error(&a, &b))
+}
+
+/// evaluates a <= b, uses not (b < a) if there is no __le handler
+pub fn less_equal(env : &mut GlobalEnvironment, a : Value, b : Value) -> Result<bool, InterpreterError> {
+    match (&a, &b) {
+        (Value::Number(_), Value::Number(_)) | (Value::LuaString(_), Value::LuaString(_)) => {
+            return Ok(primitive_order(&a, &b).is_some_and(Ordering::is_le));
+        }
+        _ => {}
+    }
+
+    if a.get_type() == b.get_type() {
+        let handler = get_comparison_handler(env, &a, &b, MetaMethod::Le);
+        if !handler.is_nil() {
+            return Ok(call_metamethod(env, handler, vec![a, b])?.to_boolean());
+        }
+        let handler = get_comparison_handler(env, &b, &a, MetaMethod::Lt);
+        if !handler.is_nil() {
+            return Ok(!call_metamethod(env, handler, vec![b, a])?.to_boolean());
+        }
+    }
+
+    Err(comparison_error(&a, &b))
+}
+
+/// evaluates a == b, __eq is only called for two different tables
+pub fn equals(env : &mut GlobalEnvironment, a : Value, b : Value) -> Result<bool, InterpreterError> {
+    if a == b {
+        return Ok(true);
+    }
+
+    match (&a, &b) {
+        (Value::Table(_), Value::Table(_)) | (Value::UserData(_), Value::UserData(_)) => {
+            let handler = get_comparison_handler(env, &a, &b, MetaMethod::Eq);
+            if handler.is_nil() {
+                return Ok(false);
+            }
+            Ok(call_metamethod(env, handler, vec![a, b])?.to_boolean())
+        }
+        _ => { Ok(false) }
+    }
+}
+
+/// evaluates #val, as in lua 5.1 __len is not used for strings and tables
+pub fn length(env : &mut GlobalEnvironment, val : Value) -> Result<Value, InterpreterError> {
+    match &val {
+        Value::LuaString(s) => { Ok(Value::Number(s.len() as f64)) }
+        Value::Table(t) => { Ok(Value::Number(t.borrow().len() as f64)) }
+        _ => {
+            let handler = get_metamethod(env, &val, MetaMethod::Len);
+            if handler.is_nil() {
+                return Err(InterpreterError::LengthError { typ: val.get_type() });
+            }
+            call_metamethod(env, handler, vec![val, Value::Nil])
+        }
+    }
+}
@@ -1,7 +1,22 @@
 use core::fmt;
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::hash::{Hash, Hasher};
+use std::mem;
 use std::ops::{BitAnd, BitOr};
+use std::rc::Rc;
 
-use crate::{interpreter::runtime_library::RuntimeFunction, structure::function::Function};
+use crate::{interpreter::{printf::{self, FormatSpec}, coroutine_library::Coroutine, runtime_library::{RuntimeClosure, RuntimeFunction}, table::Table, userdata::UserData}, structure::function::Function};
+
+/// Lua strings are immutable sequences of bytes, they are not necessarily valid UTF-8
+pub type LuaStr = Rc<[u8]>;
+
+/// Tables are shared between every value referencing them
+pub type TableRef = Rc<RefCell<Table>>;
+
+/// Cell holding a variable captured by a closure
+/// the cell is shared with the stack frame while the variable is still in scope
+pub type UpValue = Rc<RefCell<Value>>;
 
 #[derive(PartialEq, Debug)]
 pub enum TypeLua{
@@ -10,33 +25,122 @@ pub enum TypeLua{
     String,
     Nil,
     Table,
-    Function
+    Function,
+    Thread,
+    UserData
+}
+
+impl fmt::Display for TypeLua {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TypeLua::Number => "number",
+            TypeLua::Boolean => "boolean",
+            TypeLua::String => "string",
+            TypeLua::Nil => "nil",
+            TypeLua::Table => "table",
+            TypeLua::Function => "function",
+            TypeLua::Thread => "thread",
+            TypeLua::UserData => "userdata"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Instance of a function prototype with the upvalues it captured when it was created
+/// and the table where its global variables are looked up
+#[derive(Debug)]
+pub struct Closure {
+    pub proto    : Rc<Function>,
+    pub upvalues : RefCell<Vec<UpValue>>,
+    pub env      : RefCell<TableRef>
+}
+
+impl Closure {
+
+    pub fn new(proto : Rc<Function>, env : TableRef) -> Self {
+        let capacity = proto.up_values as usize;
+        Closure {
+            proto,
+            upvalues : RefCell::new(Vec::with_capacity(capacity)),
+            env      : RefCell::new(env)
+        }
+    }
+
 }
 
-#[derive(Clone, Copy, Debug, Default)]
-pub enum Value<'gc> {
+#[derive(Clone, Debug, Default)]
+pub enum Value {
     Number(f64),
     Boolean(bool),
-    LuaFunction(&'gc Function),
-    LuaString(&'gc String),
-    RuntimeFunction(RuntimeFunction<'gc>),
+    LuaFunction(Rc<Closure>),
+    LuaString(LuaStr),
+    RuntimeFunction(RuntimeFunction),
+    RuntimeClosure(Rc<RuntimeClosure>),
+    Table(TableRef),
+    Thread(Rc<Coroutine>),
+    // rust value owned by the interpreter
+    UserData(Rc<UserData>),
+    // pointer given by the host, the interpreter only compares it
+    LightUserData(*mut c_void),
     #[default]
     Nil
 }
 
-impl <'gc> Value<'gc> {
+/// converts a string to a number following the rules of the lua lexer:
+/// surrounding spaces are ignored and hexadecimal integers are accepted
+pub fn str_to_number(s : &[u8]) -> Option<f64> {
+    let s = std::str::from_utf8(s).ok()?.trim_matches(|c: char| c.is_ascii_whitespace());
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s))
+    };
+
+    if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        let n = u64::from_str_radix(hex, 16).ok()? as f64;
+        return Some(if negative { -n } else { n });
+    }
+
+    s.parse::<f64>().ok()
+}
+
+/// converts a number to a string as the C format "%.14g" does
+pub fn number_to_string(n : f64) -> String {
+    let spec = FormatSpec { precision: Some(14), ..FormatSpec::new(b'g') };
+    printf::format_float(n, &spec)
+}
+
+impl Value {
+
+    pub fn from_string(s : &str) -> Self {
+        Self::LuaString(Rc::from(s.as_bytes()))
+    }
+
+    pub fn from_bytes(s : &[u8]) -> Self {
+        Self::LuaString(Rc::from(s))
+    }
+
+    pub fn new_table() -> Self {
+        Self::Table(Rc::new(RefCell::new(Table::new())))
+    }
 
     pub fn is_nil(&self) -> bool {
-        match *self {
-            Self::Nil => { true }
-            _         => { false }
-        }
+        matches!(self, Self::Nil)
     }
 
+    /// returns the number represented by the value, strings are converted as in arithmetic operations
     pub fn get_number(&self) -> Option<f64> {
-        // TODO handle string arithmetic op
         match self {
             Self::Number(res) => { Some(*res) }
+            Self::LuaString(s) => { str_to_number(s) }
+            _ => { None }
+        }
+    }
+
+    /// returns the bytes of a string or of the conversion of a number to a string
+    pub fn to_lua_string(&self) -> Option<LuaStr> {
+        match self {
+            Self::LuaString(s) => { Some(s.clone()) }
+            Self::Number(n) => { Some(Rc::from(number_to_string(*n).as_bytes())) }
             _ => { None }
         }
     }
@@ -49,23 +153,26 @@ impl <'gc> Value<'gc> {
     }
 
     pub fn to_boolean(&self) -> bool {
-        match *self {
-            Self::Boolean(b) => b,
-            Self::Number(_) | Self::LuaString(_) | Self::LuaFunction(_) | Self::RuntimeFunction(_) => true,
-            Self::Nil => false
+        !matches!(self, Self::Boolean(false) | Self::Nil)
+    }
+
+    pub fn get_function(&self) -> Option<&Rc<Closure>> {
+        match self {
+            Self::LuaFunction(f) => { Some(f) }
+            _ => { None }
         }
     }
 
-    pub fn get_function(&self) -> Option<&'gc Function> {
+    pub fn get_string(&self) -> Option<&LuaStr> {
         match self {
-            Self::LuaFunction(f) => { Some(*f) }
+            Self::LuaString(s) => { Some(s) }
             _ => { None }
         }
     }
 
-    pub fn get_string(&self) -> Option<&'gc String> {
+    pub fn get_table(&self) -> Option<&TableRef> {
         match self {
-            Self::LuaString(s) => { Some(*s) }
+            Self::Table(t) => { Some(t) }
             _ => { None }
         }
     }
@@ -79,6 +186,10 @@ impl <'gc> Value<'gc> {
             Self::LuaFunction(_) => { TypeLua::Function }
             Self::LuaString(_) => { TypeLua::String }
             Self::RuntimeFunction(_) => { TypeLua::Function }
+            Self::RuntimeClosure(_) => { TypeLua::Function }
+            Self::Table(_) => { TypeLua::Table }
+            Self::Thread(_) => { TypeLua::Thread }
+            Self::UserData(_) | Self::LightUserData(_) => { TypeLua::UserData }
         }
 
     }
@@ -86,24 +197,53 @@ impl <'gc> Value<'gc> {
 
 }
 
-impl <'gc> PartialEq<Value<'gc>> for Value<'gc> {
-    fn eq(&self, other: &Value<'gc>) -> bool {
-        match (*self, *other) {
+// Raw equality, metamethods are handled by the interpreter
+impl PartialEq<Value> for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
             (Self::Number(l0), Self::Number(r0)) => l0 == r0,
             (Self::Boolean(l0), Self::Boolean(r0)) => l0 == r0,
-            (Self::LuaFunction(l0), Self::LuaFunction(r0)) => l0 as *const _ == r0 as *const _,
+            (Self::LuaFunction(l0), Self::LuaFunction(r0)) => Rc::ptr_eq(l0, r0),
             (Self::LuaString(l0), Self::LuaString(r0)) => l0 == r0,
-            (Self::RuntimeFunction(l0), Self::RuntimeFunction(r0)) => l0 == r0,
+            (Self::RuntimeFunction(l0), Self::RuntimeFunction(r0)) => *l0 as usize == *r0 as usize,
+            (Self::RuntimeClosure(l0), Self::RuntimeClosure(r0)) => Rc::ptr_eq(l0, r0),
+            (Self::Table(l0), Self::Table(r0)) => Rc::ptr_eq(l0, r0),
+            (Self::Thread(l0), Self::Thread(r0)) => Rc::ptr_eq(l0, r0),
+            (Self::UserData(l0), Self::UserData(r0)) => Rc::ptr_eq(l0, r0),
+            (Self::LightUserData(l0), Self::LightUserData(r0)) => l0 == r0,
             (Self::Nil, Self::Nil) => true,
             _ => false
         }
     }
 }
 
-impl <'gc> BitAnd<Value<'gc>> for Value<'gc> {
-    type Output = Value<'gc>;
+// NaN is the only value that is not equal to itself, tables refuse it as a key
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            // 0 and -0 are the same key
+            Self::Number(n) => { if *n == 0. { 0u64.hash(state) } else { n.to_bits().hash(state) } }
+            Self::Boolean(b) => { b.hash(state) }
+            Self::LuaFunction(f) => { Rc::as_ptr(f).hash(state) }
+            Self::LuaString(s) => { s.hash(state) }
+            Self::RuntimeFunction(f) => { (*f as usize).hash(state) }
+            Self::RuntimeClosure(f) => { Rc::as_ptr(f).hash(state) }
+            Self::Table(t) => { Rc::as_ptr(t).hash(state) }
+            Self::Thread(co) => { Rc::as_ptr(co).hash(state) }
+            Self::UserData(u) => { Rc::as_ptr(u).hash(state) }
+            Self::LightUserData(p) => { p.hash(state) }
+            Self::Nil => {}
+        }
+    }
+}
+
+impl BitAnd<Value> for Value {
+    type Output = Value;
 
-    fn bitand(self, rhs: Value<'gc>) -> Self::Output {
+    fn bitand(self, rhs: Value) -> Self::Output {
         match (self, rhs) {
             (Self::Nil, _) | (_, Self::Nil) => Self::Nil,
             (Self::Boolean(b1), Self::Boolean(b2)) => Self::Boolean(b1 && b2),
@@ -118,10 +258,10 @@ impl <'gc> BitAnd<Value<'gc>> for Value<'gc> {
     }
 }
 
-impl <'gc> BitOr<Value<'gc>> for Value<'gc> {
-    type Output = Value<'gc>;
+impl BitOr<Value> for Value {
+    type Output = Value;
 
-    fn bitor(self, rhs: Value<'gc>) -> Self::Output {
+    fn bitor(self, rhs: Value) -> Self::Output {
         match (self, rhs) {
             (Self::Nil, v) | (v, Self::Nil) => v,
             (Self::Boolean(b1), Self::Boolean(b2)) => Self::Boolean(b1 || b2),
@@ -130,21 +270,26 @@ impl <'gc> BitOr<Value<'gc>> for Value<'gc> {
                 let bits2 = f64::to_bits(n2);
                 Self::Number(f64::from_bits(bits1 | bits2))
             } (a, _) => a
-        }        
+        }
     }
 }
 
-impl <'gc> fmt::Display for Value<'gc>{
+impl fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        
+
         match self {
             Self::Boolean(b) => { write!(f, "{}", *b) }
-            Self::Number(n) => { write!(f, "{}", *n) }
+            Self::Number(n) => { write!(f, "{}", number_to_string(*n)) }
             Self::Nil => { write!(f, "nil") }
-            Self::LuaString(s) => { write!(f, "{}", (*s).as_str()) } 
+            Self::LuaString(s) => { write!(f, "{}", String::from_utf8_lossy(s)) }
             // We display the location of functions in memory
-            Self::LuaFunction(adr) => { write!(f, "function: {:p}", *adr) }
-            Self::RuntimeFunction(adr) => { write!(f, "function: {}", *adr as usize) }
+            Self::LuaFunction(adr) => { write!(f, "function: {:p}", Rc::as_ptr(adr)) }
+            Self::RuntimeFunction(adr) => { write!(f, "function: {:p}", *adr as *const ()) }
+            Self::RuntimeClosure(adr) => { write!(f, "function: {:p}", Rc::as_ptr(adr)) }
+            Self::Table(adr) => { write!(f, "table: {:p}", Rc::as_ptr(adr)) }
+            Self::Thread(adr) => { write!(f, "thread: {:p}", Rc::as_ptr(adr)) }
+            Self::UserData(adr) => { write!(f, "userdata: {:p}", Rc::as_ptr(adr)) }
+            Self::LightUserData(adr) => { write!(f, "userdata: {:p}", *adr) }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,324 @@
+use std::cell::RefCell;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::interpreter::{call_frame::CallFrame, global_environment::GlobalEnvironment, object::{LuaStr, TableRef, Value}};
+use crate::interpreter::interpreter::InterpreterError;
+use crate::interpreter::runtime_library::{check_number, check_string, check_table, opt_integer, RuntimeClosure};
+use crate::interpreter::sandbox::Capabilities;
+use crate::interpreter::table::Table;
+
+#[derive(thiserror::Error, Debug)]
+pub enum OsLibraryError {
+    #[error("field '{field}' missing in date table")]
+    MissingFieldError {
+        field : &'static str
+    },
+    #[error("unable to generate a unique filename")]
+    TemporaryNameError
+}
+
+const DAY_NAMES : [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+const MONTH_NAMES : [&str; 12] = ["January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December"];
+
+/// Broken-down time, as the struct tm of C
+struct DateTime {
+    year    : i64,
+    // from 1 to 12
+    month   : i64,
+    day     : i64,
+    hour    : i64,
+    min     : i64,
+    sec     : i64,
+    // from 0 (Sunday) to 6
+    weekday : i64,
+    // from 0 (January 1st) to 365
+    yearday : i64
+}
+
+/// number of days between 1970-01-01 and the date of the proleptic Gregorian calendar
+fn days_from_civil(year : i64, month : i64, day : i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// date of the day number days after 1970-01-01
+fn civil_from_days(days : i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl DateTime {
+
+    /// splits a number of seconds since 1970-01-01 00:00:00 UTC
+    fn from_timestamp(time : i64) -> Self {
+        let days = time.div_euclid(86400);
+        let seconds = time.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(days);
+        DateTime {
+            year, month, day,
+            hour    : seconds / 3600,
+            min     : seconds % 3600 / 60,
+            sec     : seconds % 60,
+            weekday : (days + 4).rem_euclid(7),
+            yearday : days - days_from_civil(year, 1, 1)
+        }
+    }
+
+    /// returns the date formatted as strftime does in the C locale
+    fn format(&self, format : &[u8]) -> Vec<u8> {
+        let mut res = Vec::new();
+        let mut i = 0;
+        while i < format.len() {
+            if format[i] != b'%' || i + 1 == format.len() {
+                res.push(format[i]);
+                i += 1;
+                continue;
+            }
+            let hour12 = if self.hour % 12 == 0 { 12 } else { self.hour % 12 };
+            let text = match format[i + 1] {
+                b'a' => { String::from(&DAY_NAMES[self.weekday as usize][..3]) }
+                b'A' => { String::from(DAY_NAMES[self.weekday as usize]) }
+                b'b' | b'h' => { String::from(&MONTH_NAMES[self.month as usize - 1][..3]) }
+                b'B' => { String::from(MONTH_NAMES[self.month as usize - 1]) }
+                b'c' => { String::from_utf8_lossy(&self.format(b"%a %b %e %H:%M:%S %Y")).into_owned() }
+                b'C' => { format!("{:02}", self.year.div_euclid(100)) }
+                b'd' => { format!("{:02}", self.day) }
+                b'D' | b'x' => { String::from_utf8_lossy(&self.format(b"%m/%d/%y")).into_owned() }
+                b'e' => { format!("{:2}", self.day) }
+                b'F' => { String::from_utf8_lossy(&self.format(b"%Y-%m-%d")).into_owned() }
+                b'H' => { format!("{:02}", self.hour) }
+                b'I' => { format!("{:02}", hour12) }
+                b'j' => { format!("{:03}", self.yearday + 1) }
+                b'm' => { format!("{:02}", self.month) }
+                b'M' => { format!("{:02}", self.min) }
+                b'n' => { String::from("\n") }
+                b'p' => { String::from(if self.hour < 12 { "AM" } else { "PM" }) }
+                b'S' => { format!("{:02}", self.sec) }
+                b't' => { String::from("\t") }
+                b'T' | b'X' => { String::from_utf8_lossy(&self.format(b"%H:%M:%S")).into_owned() }
+                b'w' => { self.weekday.to_string() }
+                b'y' => { format!("{:02}", self.year.rem_euclid(100)) }
+                b'Y' => { self.year.to_string() }
+                b'Z' => { String::from("UTC") }
+                b'%' => { String::from("%") }
+                other => { format!("%{}", other as char) }
+            };
+            res.extend_from_slice(text.as_bytes());
+            i += 2;
+        }
+        res
+    }
+
+}
+
+/// current time in seconds since 1970-01-01 00:00:00 UTC
+fn now() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => { duration.as_secs() as i64 }
+        Err(err) => { -(err.duration().as_secs() as i64) }
+    }
+}
+
+/// returns nil, the message and the error number of a failed operation on a file
+fn push_error(returned_values : &mut Vec<Value>, err : &io::Error, file_name : &[u8]) {
+    let msg = err.to_string();
+    let msg = msg.rfind(" (os error ").map_or(&msg[..], |i| &msg[..i]);
+    let mut res = file_name.to_vec();
+    res.extend_from_slice(b": ");
+    res.extend_from_slice(msg.as_bytes());
+    returned_values.push(Value::Nil);
+    returned_values.push(Value::from_bytes(&res));
+    returned_values.push(Value::Number(err.raw_os_error().unwrap_or(0) as f64));
+}
+
+/// reads an integer field of the table given to os.time, the default is used when the field is absent
+fn date_field(table : &TableRef, field : &'static str, default : Option<i64>) -> Result<i64, InterpreterError> {
+    match table.borrow().get_str(field).get_number() {
+        Some(n) => { Ok(n as i64) }
+        None => { default.ok_or(InterpreterError::from(OsLibraryError::MissingFieldError { field })) }
+    }
+}
+
+/* os.time(t) returns the current time, or the time of the date described by the table t
+ * with the fields year, month, day, hour (12 by default), min and sec, out of range values are normalized
+ * dates are in UTC, there is no time zone
+ */
+pub fn time_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    if frame.load(0).is_nil() {
+        returned_values.push(Value::Number(now() as f64));
+        return Ok(());
+    }
+
+    let table = check_table(&frame, 0, "time")?;
+    let sec = date_field(&table, "sec", Some(0))?;
+    let min = date_field(&table, "min", Some(0))?;
+    let hour = date_field(&table, "hour", Some(12))?;
+    let day = date_field(&table, "day", None)?;
+    let month = date_field(&table, "month", None)?;
+    let year = date_field(&table, "year", None)?;
+
+    let year = year + (month - 1).div_euclid(12);
+    let month = (month - 1).rem_euclid(12) + 1;
+    let days = days_from_civil(year, month, 1) + day - 1;
+    returned_values.push(Value::Number((days * 86400 + hour * 3600 + min * 60 + sec) as f64));
+    Ok(())
+}
+
+/* os.date(format, time) formats the time (the current one by default) as strftime, "%c" by default
+ * the format "*t" returns a table with the fields of the date, dates are in UTC with or without the '!' prefix
+ */
+pub fn date_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let format = match frame.load(0) {
+        Value::Nil => { LuaStr::from(&b"%c"[..]) }
+        _ => { check_string(&frame, 0, "date")? }
+    };
+    let time = match frame.load(1) {
+        Value::Nil => { now() }
+        _ => { opt_integer(&frame, 1, "date", 0)? }
+    };
+    let date = DateTime::from_timestamp(time);
+    let format = format.strip_prefix(b"!").unwrap_or(&format);
+
+    if format.starts_with(b"*t") {
+        let mut table = Table::with_capacity(0, 9);
+        for (field, val) in [("sec", date.sec), ("min", date.min), ("hour", date.hour), ("day", date.day), ("month", date.month),
+            ("year", date.year), ("wday", date.weekday + 1), ("yday", date.yearday + 1)] {
+            table.set(Value::from_string(field), Value::Number(val as f64)).expect("field names are strings");
+        }
+        table.set(Value::from_string("isdst"), Value::Boolean(false)).expect("field names are strings");
+        returned_values.push(Value::Table(Rc::new(RefCell::new(table))));
+    } else {
+        returned_values.push(Value::from_bytes(&date.format(format)));
+    }
+    Ok(())
+}
+
+/// os.difftime(t2, t1) returns the number of seconds from the time t1 (0 by default) to the time t2
+pub fn difftime_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let end = check_number(&frame, 0, "difftime")?;
+    let start = match frame.load(1) {
+        Value::Nil => { 0. }
+        _ => { check_number(&frame, 1, "difftime")? }
+    };
+    returned_values.push(Value::Number(end - start));
+    Ok(())
+}
+
+/// os.getenv(name) returns the value of the environment variable, or nil if it is not defined
+pub fn getenv_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let name = check_string(&frame, 0, "getenv")?;
+    match std::env::var_os(String::from_utf8_lossy(&name).as_ref()) {
+        Some(val) => { returned_values.push(Value::from_bytes(val.as_encoded_bytes())) }
+        None => { returned_values.push(Value::Nil) }
+    }
+    Ok(())
+}
+
+/// os.remove(name) deletes the file or the empty directory, returns true or nil and an error message
+pub fn remove_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let name = check_string(&frame, 0, "remove")?;
+    let path = String::from_utf8_lossy(&name).into_owned();
+    let res = match fs::symlink_metadata(&path) {
+        Ok(metadata) if metadata.is_dir() => { fs::remove_dir(&path) }
+        _ => { fs::remove_file(&path) }
+    };
+    match res {
+        Ok(()) => { returned_values.push(Value::Boolean(true)) }
+        Err(err) => { push_error(returned_values, &err, &name) }
+    }
+    Ok(())
+}
+
+/// os.rename(old, new) renames the file, returns true or nil and an error message
+pub fn rename_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let old = check_string(&frame, 0, "rename")?;
+    let new = check_string(&frame, 1, "rename")?;
+    match fs::rename(String::from_utf8_lossy(&old).as_ref(), String::from_utf8_lossy(&new).as_ref()) {
+        Ok(()) => { returned_values.push(Value::Boolean(true)) }
+        Err(err) => { push_error(returned_values, &err, &old) }
+    }
+    Ok(())
+}
+
+/// os.tmpname() creates an empty file in the temporary directory and returns its name
+pub fn tmpname_lua(_ : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    const CHARS : &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    // the names are derived from the clock so that the generator of math.random is left untouched
+    let mut x = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64) ^ ((std::process::id() as u64) << 32);
+
+    for _ in 0..100 {
+        let mut name = String::from("lua_");
+        for _ in 0..6 {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            name.push(CHARS[(x >> 58) as usize % CHARS.len()] as char);
+        }
+        let path = std::env::temp_dir().join(name);
+        if OpenOptions::new().write(true).create_new(true).open(&path).is_ok() {
+            returned_values.push(Value::from_bytes(path.as_os_str().as_encoded_bytes()));
+            return Ok(());
+        }
+    }
+    Err(OsLibraryError::TemporaryNameError.into())
+}
+
+/// os.exit(code) ends the host process with the code, 0 by default
+pub fn exit_lua(frame : CallFrame, _ : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let code = opt_integer(&frame, 0, "exit", 0)?;
+    let _ = env.output.flush();
+    let _ = env.error_output.flush();
+    std::process::exit(code as i32)
+}
+
+/// stores a value in a table with a string key
+fn set_field(table : &mut Table, name : &str, val : Value) {
+    table.set(Value::from_string(name), val).expect("field names are strings");
+}
+
+/* creates the os library with the functions allowed by the capabilities:
+ * os.time, os.clock and os.date with time, os.getenv with environment,
+ * os.remove, os.rename and os.tmpname with file_system and os.exit with exit
+ */
+pub fn new_library(capabilities : &Capabilities) -> TableRef {
+    let mut library = Table::new();
+
+    if capabilities.time {
+        set_field(&mut library, "time", Value::RuntimeFunction(time_lua));
+        set_field(&mut library, "date", Value::RuntimeFunction(date_lua));
+        set_field(&mut library, "difftime", Value::RuntimeFunction(difftime_lua));
+        // os.clock() returns the time in seconds since the creation of the library, the processor time is not available
+        let start = Instant::now();
+        set_field(&mut library, "clock", RuntimeClosure::new_value(move |_, returned_values, _| {
+            returned_values.push(Value::Number(start.elapsed().as_secs_f64()));
+            Ok(())
+        }));
+    }
+    if capabilities.environment {
+        set_field(&mut library, "getenv", Value::RuntimeFunction(getenv_lua));
+    }
+    if capabilities.file_system {
+        set_field(&mut library, "remove", Value::RuntimeFunction(remove_lua));
+        set_field(&mut library, "rename", Value::RuntimeFunction(rename_lua));
+        set_field(&mut library, "tmpname", Value::RuntimeFunction(tmpname_lua));
+    }
+    if capabilities.exit {
+        set_field(&mut library, "exit", Value::RuntimeFunction(exit_lua));
+    }
+
+    Rc::new(RefCell::new(library))
+}
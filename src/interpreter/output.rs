@@ -0,0 +1,81 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/** Destination of the text written by the scripts, as the standard output of print and io.write
+ * the clones of an output share its writer, redirecting one of them redirects all of them
+ */
+#[derive(Clone)]
+pub struct Output {
+    writer : Rc<RefCell<Box<dyn Write>>>
+}
+
+impl Output {
+
+    pub fn new(writer : impl Write + 'static) -> Self {
+        Output { writer: Rc::new(RefCell::new(Box::new(writer))) }
+    }
+
+    pub fn stdout() -> Self {
+        Output::new(io::stdout())
+    }
+
+    pub fn stderr() -> Self {
+        Output::new(io::stderr())
+    }
+
+    /// replaces the writer of the output and of its clones, the text written before stays in the previous writer
+    pub fn redirect(&self, writer : impl Write + 'static) {
+        *self.writer.borrow_mut() = Box::new(writer);
+    }
+
+}
+
+impl Write for Output {
+    fn write(&mut self, buf : &[u8]) -> io::Result<usize> {
+        self.writer.borrow_mut().write(buf)
+    }
+
+    fn write_all(&mut self, buf : &[u8]) -> io::Result<()> {
+        self.writer.borrow_mut().write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.borrow_mut().flush()
+    }
+}
+
+/// Writer keeping the text in memory, to capture what the scripts write
+#[derive(Clone, Default)]
+pub struct OutputBuffer {
+    data : Rc<RefCell<Vec<u8>>>
+}
+
+impl OutputBuffer {
+
+    pub fn new() -> Self {
+        OutputBuffer::default()
+    }
+
+    /// returns the bytes written so far
+    pub fn contents(&self) -> Vec<u8> {
+        self.data.borrow().clone()
+    }
+
+    /// returns the bytes written so far and empties the buffer
+    pub fn take(&self) -> Vec<u8> {
+        self.data.take()
+    }
+
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf : &[u8]) -> io::Result<usize> {
+        self.data.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,432 @@
+use crate::interpreter::interpreter::InterpreterError;
+use crate::interpreter::limits::Resources;
+use crate::interpreter::object::Value;
+
+// maximum number of captures in a pattern, same limit as the reference implementation
+pub const MAX_CAPTURES : usize = 32;
+/* maximum number of nested calls of the matcher before a pattern is considered too complex, MAXCCALLS of Lua 5.2
+ * Lua 5.1 has no such limit and recurses until the C stack is exhausted, here a pattern with more than 200 items
+ * that can backtrack (captures, ?, *, + and -) raises an error; single characters are matched in a loop and never count
+ */
+const MAX_MATCH_DEPTH : usize = 200;
+// steps of the matcher between two checks of the time and the memory, a backtracking pattern can run for long
+const CHECK_INTERVAL : u64 = 1024;
+
+const L_ESC : u8 = b'%';
+// characters that make a pattern different from a plain string
+const SPECIALS : &[u8] = b"^$*+?.([%-";
+
+#[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum PatternError {
+    #[error("malformed pattern (ends with '%')")]
+    EndsWithEscapeError,
+    #[error("malformed pattern (missing ']')")]
+    MissingBracketError,
+    #[error("missing '[' after '%f' in pattern")]
+    FrontierError,
+    #[error("unbalanced pattern")]
+    UnbalancedError,
+    #[error("invalid capture index")]
+    CaptureIndexError,
+    #[error("invalid pattern capture")]
+    PatternCaptureError,
+    #[error("unfinished capture")]
+    UnfinishedCaptureError,
+    #[error("too many captures")]
+    TooManyCapturesError,
+    #[error("pattern too complex")]
+    ComplexityError
+}
+
+/// Length of a capture
+#[derive(Clone, Copy, PartialEq)]
+enum CaptureLength {
+    Unfinished,
+    // () captures the position in the subject
+    Position,
+    Closed(usize)
+}
+
+/** State of a match of a pattern against a subject, a port of the matcher of the reference implementation
+ * positions are indices in the subject and in the pattern, the end of the pattern plays the role of the C terminator
+ */
+pub struct MatchState<'a> {
+    src     : &'a [u8],
+    pat     : &'a [u8],
+    // number of captures started
+    level   : usize,
+    capture : [(usize, CaptureLength); MAX_CAPTURES],
+    depth   : usize,
+    // limits of the run and steps taken since the creation of the state
+    resources : Resources,
+    steps   : u64
+}
+
+/// the pattern has no special character, it can be searched as a plain string
+pub fn is_plain(pat : &[u8]) -> bool {
+    !pat.iter().any(|c| SPECIALS.contains(c))
+}
+
+/// returns the position of the first occurrence of needle in haystack
+pub fn find_plain(haystack : &[u8], needle : &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// character classes %a, %d... an upper case class is the complement of the lower case one
+fn match_class(c : u8, class : u8) -> bool {
+    let res = match class.to_ascii_lowercase() {
+        b'a' => { c.is_ascii_alphabetic() }
+        b'c' => { c.is_ascii_control() }
+        b'd' => { c.is_ascii_digit() }
+        b'l' => { c.is_ascii_lowercase() }
+        b'p' => { c.is_ascii_punctuation() }
+        // isspace also accepts the vertical tab
+        b's' => { c.is_ascii_whitespace() || c == 0x0b }
+        b'u' => { c.is_ascii_uppercase() }
+        b'w' => { c.is_ascii_alphanumeric() }
+        b'x' => { c.is_ascii_hexdigit() }
+        b'z' => { c == 0 }
+        _ => { return class == c }
+    };
+    if class.is_ascii_lowercase() { res } else { !res }
+}
+
+impl<'a> MatchState<'a> {
+
+    pub fn new(src : &'a [u8], pat : &'a [u8], resources : Resources) -> Self {
+        MatchState {
+            src,
+            pat,
+            level   : 0,
+            capture : [(0, CaptureLength::Unfinished); MAX_CAPTURES],
+            depth   : 0,
+            resources,
+            steps   : 0
+        }
+    }
+
+    /// counts a step of the matcher, the time and the memory are checked from time to time
+    fn step(&mut self) -> Result<(), InterpreterError> {
+        self.steps += 1;
+        if self.steps.is_multiple_of(CHECK_INTERVAL) {
+            self.resources.check()?;
+        }
+        Ok(())
+    }
+
+    /// character of the pattern at position p, 0 after the end as with C strings
+    fn pat_at(&self, p : usize) -> u8 {
+        self.pat.get(p).copied().unwrap_or(0)
+    }
+
+    /// character of the subject at position s, 0 after the end as with C strings
+    fn src_at(&self, s : usize) -> u8 {
+        self.src.get(s).copied().unwrap_or(0)
+    }
+
+    /// returns the position following the single character class starting at p
+    fn class_end(&self, mut p : usize) -> Result<usize, PatternError> {
+        let c = self.pat_at(p);
+        p += 1;
+        match c {
+            L_ESC => {
+                if p >= self.pat.len() {
+                    return Err(PatternError::EndsWithEscapeError);
+                }
+                Ok(p + 1)
+            }
+            b'[' => {
+                if self.pat_at(p) == b'^' {
+                    p += 1;
+                }
+                // looks for the closing ], the first character of the set can be ]
+                loop {
+                    if p >= self.pat.len() {
+                        return Err(PatternError::MissingBracketError);
+                    }
+                    let c = self.pat_at(p);
+                    p += 1;
+                    // skips escapes such as %]
+                    if c == L_ESC && p < self.pat.len() {
+                        p += 1;
+                    }
+                    if self.pat_at(p) == b']' {
+                        break;
+                    }
+                }
+                Ok(p + 1)
+            }
+            _ => { Ok(p) }
+        }
+    }
+
+    /// matches c against the set starting at p (on the [) and ending at ec (on the ])
+    fn match_bracket_class(&self, c : u8, mut p : usize, ec : usize) -> bool {
+        let mut sig = true;
+        if self.pat_at(p + 1) == b'^' {
+            sig = false;
+            p += 1;
+        }
+        p += 1;
+        while p < ec {
+            if self.pat_at(p) == L_ESC {
+                p += 1;
+                if match_class(c, self.pat_at(p)) {
+                    return sig;
+                }
+            } else if self.pat_at(p + 1) == b'-' && p + 2 < ec {
+                if self.pat_at(p) <= c && c <= self.pat_at(p + 2) {
+                    return sig;
+                }
+                p += 2;
+            } else if self.pat_at(p) == c {
+                return sig;
+            }
+            p += 1;
+        }
+        !sig
+    }
+
+    /// matches the character at position s of the subject against the class from p to ep
+    fn single_match(&self, s : usize, p : usize, ep : usize) -> bool {
+        if s >= self.src.len() {
+            return false;
+        }
+        let c = self.src[s];
+        match self.pat_at(p) {
+            b'.' => { true }
+            L_ESC => { match_class(c, self.pat_at(p + 1)) }
+            b'[' => { self.match_bracket_class(c, p, ep - 1) }
+            pc => { pc == c }
+        }
+    }
+
+    /// %bxy matches a balanced string starting with x and ending with y
+    fn match_balance(&self, s : usize, p : usize) -> Result<Option<usize>, PatternError> {
+        if p + 1 >= self.pat.len() {
+            return Err(PatternError::UnbalancedError);
+        }
+        if self.src_at(s) != self.pat[p] || s >= self.src.len() {
+            return Ok(None);
+        }
+
+        let (open, close) = (self.pat[p], self.pat[p + 1]);
+        let mut count = 1;
+        for i in s + 1..self.src.len() {
+            if self.src[i] == close {
+                count -= 1;
+                if count == 0 {
+                    return Ok(Some(i + 1));
+                }
+            } else if self.src[i] == open {
+                count += 1;
+            }
+        }
+        Ok(None)
+    }
+
+    /// matches as many repetitions of the class as possible, then backtracks
+    fn max_expand(&mut self, s : usize, p : usize, ep : usize) -> Result<Option<usize>, InterpreterError> {
+        let mut i = 0;
+        while self.single_match(s + i, p, ep) {
+            i += 1;
+        }
+        loop {
+            self.step()?;
+            if let Some(res) = self.do_match(s + i, ep + 1)? {
+                return Ok(Some(res));
+            }
+            if i == 0 {
+                return Ok(None);
+            }
+            i -= 1;
+        }
+    }
+
+    /// matches as few repetitions of the class as possible
+    fn min_expand(&mut self, mut s : usize, p : usize, ep : usize) -> Result<Option<usize>, InterpreterError> {
+        loop {
+            self.step()?;
+            if let Some(res) = self.do_match(s, ep + 1)? {
+                return Ok(Some(res));
+            }
+            if self.single_match(s, p, ep) {
+                s += 1;
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn start_capture(&mut self, s : usize, p : usize, what : CaptureLength) -> Result<Option<usize>, InterpreterError> {
+        if self.level >= MAX_CAPTURES {
+            return Err(PatternError::TooManyCapturesError.into());
+        }
+        self.capture[self.level] = (s, what);
+        self.level += 1;
+        let res = self.do_match(s, p)?;
+        if res.is_none() {
+            self.level -= 1;
+        }
+        Ok(res)
+    }
+
+    fn end_capture(&mut self, s : usize, p : usize) -> Result<Option<usize>, InterpreterError> {
+        // the capture to close is the last one still open
+        let l = (0..self.level).rev()
+            .find(|&l| self.capture[l].1 == CaptureLength::Unfinished)
+            .ok_or(PatternError::PatternCaptureError)?;
+        self.capture[l].1 = CaptureLength::Closed(s - self.capture[l].0);
+        let res = self.do_match(s, p)?;
+        if res.is_none() {
+            self.capture[l].1 = CaptureLength::Unfinished;
+        }
+        Ok(res)
+    }
+
+    /// %1 to %9 match the same string as a previous capture
+    fn match_capture(&self, s : usize, l : u8) -> Result<Option<usize>, PatternError> {
+        let l = l.checked_sub(b'1').ok_or(PatternError::CaptureIndexError)? as usize;
+        let (start, len) = match self.capture.get(l) {
+            Some(&(start, CaptureLength::Closed(len))) if l < self.level => { (start, len) }
+            // a position capture never matches
+            Some(&(_, CaptureLength::Position)) if l < self.level => { return Ok(None) }
+            _ => { return Err(PatternError::CaptureIndexError) }
+        };
+        if self.src.len() - s >= len && self.src[start..start + len] == self.src[s..s + len] {
+            Ok(Some(s + len))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// matches the pattern from position p against the subject from position s, returns the end of the match
+    pub fn do_match(&mut self, s : usize, p : usize) -> Result<Option<usize>, InterpreterError> {
+        self.depth += 1;
+        if self.depth > MAX_MATCH_DEPTH {
+            return Err(PatternError::ComplexityError.into());
+        }
+        let res = self.match_items(s, p);
+        self.depth -= 1;
+        res
+    }
+
+    fn match_items(&mut self, mut s : usize, mut p : usize) -> Result<Option<usize>, InterpreterError> {
+        loop {
+            self.step()?;
+            if p >= self.pat.len() {
+                return Ok(Some(s));
+            }
+
+            match self.pat[p] {
+                b'(' => {
+                    if self.pat_at(p + 1) == b')' {
+                        return self.start_capture(s, p + 2, CaptureLength::Position);
+                    }
+                    return self.start_capture(s, p + 1, CaptureLength::Unfinished);
+                }
+                b')' => {
+                    return self.end_capture(s, p + 1);
+                }
+                L_ESC if self.pat_at(p + 1) == b'b' => {
+                    match self.match_balance(s, p + 2)? {
+                        Some(end) => { s = end; p += 4; continue }
+                        None => { return Ok(None) }
+                    }
+                }
+                L_ESC if self.pat_at(p + 1) == b'f' => {
+                    p += 2;
+                    if self.pat_at(p) != b'[' {
+                        return Err(PatternError::FrontierError.into());
+                    }
+                    let ep = self.class_end(p)?;
+                    let previous = if s == 0 { 0 } else { self.src[s - 1] };
+                    if self.match_bracket_class(previous, p, ep - 1) || !self.match_bracket_class(self.src_at(s), p, ep - 1) {
+                        return Ok(None);
+                    }
+                    p = ep;
+                    continue;
+                }
+                L_ESC if self.pat_at(p + 1).is_ascii_digit() => {
+                    match self.match_capture(s, self.pat_at(p + 1))? {
+                        Some(end) => { s = end; p += 2; continue }
+                        None => { return Ok(None) }
+                    }
+                }
+                // $ only anchors at the end of the pattern
+                b'$' if p + 1 == self.pat.len() => {
+                    return Ok(if s == self.src.len() { Some(s) } else { None });
+                }
+                _ => {}
+            }
+
+            let ep = self.class_end(p)?;
+            let m = self.single_match(s, p, ep);
+            match self.pat_at(ep) {
+                b'?' if ep < self.pat.len() => {
+                    if m {
+                        if let Some(res) = self.do_match(s + 1, ep + 1)? {
+                            return Ok(Some(res));
+                        }
+                    }
+                    p = ep + 1;
+                }
+                b'*' if ep < self.pat.len() => {
+                    return self.max_expand(s, p, ep);
+                }
+                b'+' if ep < self.pat.len() => {
+                    return if m { self.max_expand(s + 1, p, ep) } else { Ok(None) };
+                }
+                b'-' if ep < self.pat.len() => {
+                    return self.min_expand(s, p, ep);
+                }
+                _ => {
+                    if !m {
+                        return Ok(None);
+                    }
+                    s += 1;
+                    p = ep;
+                }
+            }
+        }
+    }
+
+    /// subject of the match
+    pub fn src(&self) -> &'a [u8] {
+        self.src
+    }
+
+    /// forgets the captures of a previous attempt
+    pub fn reset(&mut self) {
+        self.level = 0;
+        self.depth = 0;
+    }
+
+    /* returns the capture i, the whole match from s to e stands for the capture 0 of a pattern without captures
+     * position captures are numbers, the other captures are strings
+     */
+    pub fn get_capture(&self, i : usize, whole : Option<(usize, usize)>) -> Result<Value, PatternError> {
+        if i >= self.level {
+            return match whole {
+                Some((s, e)) if i == 0 => { Ok(Value::from_bytes(&self.src[s..e])) }
+                _ => { Err(PatternError::CaptureIndexError) }
+            };
+        }
+        match self.capture[i] {
+            (_, CaptureLength::Unfinished) => { Err(PatternError::UnfinishedCaptureError) }
+            (start, CaptureLength::Position) => { Ok(Value::Number((start + 1) as f64)) }
+            (start, CaptureLength::Closed(len)) => { Ok(Value::from_bytes(&self.src[start..start + len])) }
+        }
+    }
+
+    /// returns all the captures, or the whole match if the pattern has no capture
+    pub fn get_captures(&self, whole : Option<(usize, usize)>) -> Result<Vec<Value>, PatternError> {
+        let nb_captures = if self.level == 0 && whole.is_some() { 1 } else { self.level };
+        (0..nb_captures).map(|i| self.get_capture(i, whole)).collect()
+    }
+
+}
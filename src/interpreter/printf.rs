@@ -0,0 +1,236 @@
+/* Conversions of the C printf function, used by string.format and to convert numbers to strings
+ * the output is the one of the glibc implementation
+ */
+
+// flags of a conversion specification
+const FLAGS : &[u8] = b"-+ #0";
+
+#[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum FormatError {
+    #[error("invalid format (repeated flags)")]
+    RepeatedFlagsError,
+    #[error("invalid format (width or precision too long)")]
+    TooLongError,
+    #[error("invalid option '%{}' to 'format'", option_name(*.option))]
+    InvalidOptionError {
+        option : u8
+    }
+}
+
+/// a '%' at the end of the format is followed by the C terminator, which is not shown
+fn option_name(option : u8) -> String {
+    if option == 0 { String::new() } else { char::from(option).to_string() }
+}
+
+/// Conversion specification of printf: %[flags][width][.precision]conversion
+#[derive(Default)]
+pub struct FormatSpec {
+    pub left_justify : bool,
+    pub plus_sign    : bool,
+    pub space_sign   : bool,
+    pub alternate    : bool,
+    pub zero_pad     : bool,
+    pub width        : usize,
+    pub precision    : Option<usize>,
+    pub conversion   : u8
+}
+
+impl FormatSpec {
+
+    pub fn new(conversion : u8) -> Self {
+        FormatSpec { conversion, ..FormatSpec::default() }
+    }
+
+    /* reads the specification following a '%' at the start of fmt, and returns it with its length
+     * as in the reference implementation the width and the precision have at most 2 digits
+     */
+    pub fn parse(fmt : &[u8]) -> Result<(Self, usize), FormatError> {
+        let mut spec = FormatSpec::default();
+        let mut i = 0;
+
+        while i < fmt.len() && FLAGS.contains(&fmt[i]) {
+            match fmt[i] {
+                b'-' => { spec.left_justify = true }
+                b'+' => { spec.plus_sign = true }
+                b' ' => { spec.space_sign = true }
+                b'#' => { spec.alternate = true }
+                _ => { spec.zero_pad = true }
+            }
+            i += 1;
+        }
+        if i > FLAGS.len() {
+            return Err(FormatError::RepeatedFlagsError);
+        }
+
+        let read_digits = |i : &mut usize| {
+            let mut n = 0;
+            while *i < fmt.len() && fmt[*i].is_ascii_digit() {
+                n = n * 10 + (fmt[*i] - b'0') as usize;
+                *i += 1;
+            }
+            n
+        };
+
+        let start = i;
+        spec.width = read_digits(&mut i);
+        if i - start > 2 {
+            return Err(FormatError::TooLongError);
+        }
+        if fmt.get(i) == Some(&b'.') {
+            i += 1;
+            let start = i;
+            spec.precision = Some(read_digits(&mut i));
+            if i - start > 2 {
+                return Err(FormatError::TooLongError);
+            }
+        }
+
+        // the conversion is checked by the caller
+        spec.conversion = fmt.get(i).copied().unwrap_or(0);
+        Ok((spec, i + 1))
+    }
+
+    /// sign written before a positive number
+    fn positive_sign(&self) -> &'static str {
+        if self.plus_sign {
+            "+"
+        } else if self.space_sign {
+            " "
+        } else {
+            ""
+        }
+    }
+
+}
+
+/// pads the text with spaces up to the width of the specification
+pub fn pad(text : &[u8], spec : &FormatSpec) -> Vec<u8> {
+    let padding = spec.width.saturating_sub(text.len());
+    let mut res = Vec::with_capacity(text.len() + padding);
+    if spec.left_justify {
+        res.extend_from_slice(text);
+        res.resize(text.len() + padding, b' ');
+    } else {
+        res.resize(padding, b' ');
+        res.extend_from_slice(text);
+    }
+    res
+}
+
+/// pads a number made of a sign, a prefix (0x) and digits, zeros are inserted after the prefix with the 0 flag
+fn pad_number(sign : &str, prefix : &str, digits : &str, spec : &FormatSpec, zero_pad : bool) -> String {
+    let len = sign.len() + prefix.len() + digits.len();
+    if zero_pad && !spec.left_justify && len < spec.width {
+        let zeros = "0".repeat(spec.width - len);
+        return format!("{}{}{}{}", sign, prefix, zeros, digits);
+    }
+    let text = format!("{}{}{}", sign, prefix, digits);
+    String::from_utf8(pad(text.as_bytes(), spec)).unwrap_or(text)
+}
+
+/// applies the precision of an integer conversion: the minimum number of digits
+fn integer_digits(digits : String, spec : &FormatSpec) -> String {
+    match spec.precision {
+        // with a precision of 0 the value 0 has no digit
+        Some(0) if digits == "0" => { String::new() }
+        Some(precision) if digits.len() < precision => { format!("{}{}", "0".repeat(precision - digits.len()), digits) }
+        _ => { digits }
+    }
+}
+
+/// %d and %i
+pub fn format_integer(n : i64, spec : &FormatSpec) -> String {
+    let digits = integer_digits(n.unsigned_abs().to_string(), spec);
+    let sign = if n < 0 { "-" } else { spec.positive_sign() };
+    // the 0 flag is ignored when a precision is given
+    pad_number(sign, "", &digits, spec, spec.zero_pad && spec.precision.is_none())
+}
+
+/// %u, %o, %x and %X
+pub fn format_unsigned(n : u64, spec : &FormatSpec) -> String {
+    let digits = match spec.conversion {
+        b'o' => { format!("{:o}", n) }
+        b'x' => { format!("{:x}", n) }
+        b'X' => { format!("{:X}", n) }
+        _ => { n.to_string() }
+    };
+    let mut digits = integer_digits(digits, spec);
+
+    let mut prefix = "";
+    if spec.alternate {
+        match spec.conversion {
+            // the alternate form of octal numbers starts with 0
+            b'o' if !digits.starts_with('0') => { digits.insert(0, '0'); }
+            b'x' if n != 0 => { prefix = "0x"; }
+            b'X' if n != 0 => { prefix = "0X"; }
+            _ => {}
+        }
+    }
+    pad_number("", prefix, &digits, spec, spec.zero_pad && spec.precision.is_none())
+}
+
+/// removes the trailing zeros of the decimal part of a number, and the point if nothing follows it
+fn strip_zeros(s : &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
+
+/// writes a positive number as d.ddde+xx with the given number of decimals
+fn exponent_form(n : f64, precision : usize, alternate : bool) -> (String, i32) {
+    let scientific = format!("{:.*e}", precision, n);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent : i32 = exponent.parse().unwrap_or(0);
+    let point = if alternate && precision == 0 { "." } else { "" };
+    let sign = if exponent < 0 { '-' } else { '+' };
+    (format!("{}{}e{}{:02}", mantissa, point, sign, exponent.abs()), exponent)
+}
+
+/// %e, %E, %f, %F, %g and %G
+pub fn format_float(n : f64, spec : &FormatSpec) -> String {
+    let upper = spec.conversion.is_ascii_uppercase();
+    let sign = if n.is_sign_negative() { "-" } else { spec.positive_sign() };
+
+    // infinities and NaN are never padded with zeros
+    if !n.is_finite() {
+        let text = if n.is_nan() { "nan" } else { "inf" };
+        let text = if upper { text.to_uppercase() } else { String::from(text) };
+        return pad_number(sign, "", &text, spec, false);
+    }
+
+    let n = n.abs();
+    let precision = spec.precision.unwrap_or(6);
+    let digits = match spec.conversion.to_ascii_lowercase() {
+        b'e' => { exponent_form(n, precision, spec.alternate).0 }
+        b'f' => {
+            let point = if spec.alternate && precision == 0 { "." } else { "" };
+            format!("{:.*}{}", precision, n, point)
+        }
+        _ => {
+            // the precision is the number of significant digits
+            let precision = precision.max(1);
+            let (scientific, exponent) = exponent_form(n, precision - 1, spec.alternate);
+            let text = if exponent < -4 || exponent >= precision as i32 {
+                scientific
+            } else {
+                let point = if spec.alternate && precision as i32 - 1 - exponent == 0 { "." } else { "" };
+                format!("{:.*}{}", (precision as i32 - 1 - exponent) as usize, n, point)
+            };
+            if spec.alternate {
+                text
+            } else {
+                // trailing zeros are removed from the decimal part, before the exponent
+                match text.split_once('e') {
+                    Some((mantissa, exponent)) => { format!("{}e{}", strip_zeros(mantissa), exponent) }
+                    None => { String::from(strip_zeros(&text)) }
+                }
+            }
+        }
+    };
+
+    let digits = if upper { digits.to_uppercase() } else { digits };
+    pad_number(sign, "", &digits, spec, spec.zero_pad)
+}
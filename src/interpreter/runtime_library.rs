@@ -1,11 +1,558 @@
-use crate::interpreter::{call_frame::CallFrame, object::Value};
+use core::fmt;
+use std::io::Write;
+use std::rc::Rc;
 
-pub type RuntimeFunction<'frm> = fn (CallFrame<'frm>, &mut Vec<Value<'frm>>);
+use crate::interpreter::{call_frame::CallFrame, global_environment::GlobalEnvironment, object::{str_to_number, LuaStr, TableRef, Value}};
+use crate::interpreter::interpreter::{call_value, raise_error, InterpreterError};
+use crate::interpreter::limits::CountingAllocator;
+use crate::interpreter::metatable::{self, MetaMethod};
+use crate::interpreter::userdata;
 
-pub fn print_lua<'frm>(frame : CallFrame<'frm>, _ : &mut Vec<Value<'frm>> ) {
+// Number of values above which unpack raises an error, LUAI_MAXCSTACK of the reference implementation
+const MAX_RESULTS : usize = 8000;
+
+pub type RuntimeFunction = fn (CallFrame, &mut Vec<Value>, &mut GlobalEnvironment) -> Result<(), InterpreterError>;
+
+/// Body of a runtime closure, it has the same arguments as a runtime function
+pub type RuntimeClosureBody = dyn Fn(CallFrame, &mut Vec<Value>, &mut GlobalEnvironment) -> Result<(), InterpreterError>;
+
+/// Runtime function keeping a state between its calls, such as the iterator returned by string.gmatch
+pub struct RuntimeClosure(pub Box<RuntimeClosureBody>);
+
+impl RuntimeClosure {
+
+    /// wraps the body in a function value
+    pub fn new_value(body : impl Fn(CallFrame, &mut Vec<Value>, &mut GlobalEnvironment) -> Result<(), InterpreterError> + 'static) -> Value {
+        Value::RuntimeClosure(Rc::new(RuntimeClosure(Box::new(body))))
+    }
+
+}
+
+impl fmt::Debug for RuntimeClosure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RuntimeClosure")
+    }
+}
+
+/// error of the argument number i (starting from 0) of the runtime function when it is not of the expected type
+pub fn type_error(frame : &CallFrame, i : usize, func : &'static str, expected : &str) -> InterpreterError {
+    let got = if i < frame.len() { frame.load(i).get_type().to_string() } else { String::from("no value") };
+    InterpreterError::ArgumentError { arg: i + 1, func, msg: format!("{} expected, got {}", expected, got) }
+}
+
+/// returns the table passed as the argument number i (starting from 0) of the runtime function
+pub fn check_table(frame : &CallFrame, i : usize, func : &'static str) -> Result<TableRef, InterpreterError> {
+    match frame.load(i) {
+        Value::Table(t) => { Ok(t) }
+        _ => { Err(type_error(frame, i, func, "table")) }
+    }
+}
+
+/// returns the number passed as the argument number i (starting from 0) of the runtime function
+pub fn check_number(frame : &CallFrame, i : usize, func : &'static str) -> Result<f64, InterpreterError> {
+    frame.load(i).get_number().ok_or_else(|| type_error(frame, i, func, "number"))
+}
+
+/// returns the string passed as the argument number i (starting from 0) of the runtime function, numbers are converted
+pub fn check_string(frame : &CallFrame, i : usize, func : &'static str) -> Result<LuaStr, InterpreterError> {
+    frame.load(i).to_lua_string().ok_or_else(|| type_error(frame, i, func, "string"))
+}
+
+/// returns the integer passed as the argument number i (starting from 0) of the runtime function, or the default value if it is nil
+pub fn opt_integer(frame : &CallFrame, i : usize, func : &'static str, default : i64) -> Result<i64, InterpreterError> {
+    match frame.load(i) {
+        Value::Nil => { Ok(default) }
+        _ => { Ok(check_number(frame, i, func)? as i64) }
+    }
+}
+
+/// checks that the argument number i (starting from 0) of the runtime function was given
+pub fn check_any(frame : &CallFrame, i : usize, func : &'static str) -> Result<Value, InterpreterError> {
+    if i >= frame.len() {
+        return Err(InterpreterError::ArgumentError { arg: i + 1, func, msg: String::from("value expected") });
+    }
+    Ok(frame.load(i))
+}
+
+/// adds the position of the function at the given level to a message, level 0 is the runtime function itself
+pub fn with_location(env : &GlobalEnvironment, level : usize, message : &[u8]) -> Value {
+    let location = env.call_stack.location(level);
+    Value::from_bytes(&[location.as_bytes(), message].concat())
+}
+
+/// converts a value to a string as tostring does, the __tostring handler of the value can return any value
+fn to_string(env : &mut GlobalEnvironment, val : Value) -> Result<Value, InterpreterError> {
+    let handler = metatable::get_metamethod(env, &val, MetaMethod::ToString);
+    if !handler.is_nil() {
+        return metatable::call_metamethod(env, handler, vec![val]);
+    }
+    match val.to_lua_string() {
+        Some(s) => { Ok(Value::LuaString(s)) }
+        None => { Ok(Value::from_string(&val.to_string())) }
+    }
+}
+
+pub fn print_lua(frame : CallFrame, _ : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
     for i in 0..frame.len() {
-        let val = frame.load(i);
-        print!("{}\t", val);
+        let val = to_string(env, frame.load(i))?;
+        // strings are written as raw bytes
+        let s = val.to_lua_string().ok_or(InterpreterError::ToStringError)?;
+        let _ = env.output.write_all(&s);
+        let _ = write!(env.output, "\t");
+    }
+    let _ = writeln!(env.output);
+    Ok(())
+}
+
+pub fn setmetatable_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "setmetatable")?;
+    let metatable = match frame.load(1) {
+        Value::Table(mt) => { Some(mt) }
+        Value::Nil => { None }
+        other => {
+            return Err(InterpreterError::ArgumentError { arg: 2, func: "setmetatable", msg: format!("nil or table expected, got {}", other.get_type()) })
+        }
+    };
+
+    let protected = match &table.borrow().metatable {
+        Some(mt) => { !mt.borrow().get_str(MetaMethod::Metatable.name()).is_nil() }
+        None => { false }
+    };
+    if protected {
+        return Err(InterpreterError::ProtectedMetatableError);
     }
-    println!();
-}
\ No newline at end of file
+
+    table.borrow_mut().metatable = metatable;
+    returned_values.push(Value::Table(table));
+    Ok(())
+}
+
+pub fn getmetatable_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let res = match metatable::get_metatable(env, &frame.load(0)) {
+        // the __metatable field hides the real metatable
+        Some(mt) => {
+            let protected = mt.borrow().get_str(MetaMethod::Metatable.name());
+            if protected.is_nil() { Value::Table(mt) } else { protected }
+        }
+        None => { Value::Nil }
+    };
+    returned_values.push(res);
+    Ok(())
+}
+
+/// select('#', ...) returns the number of extra arguments, select(n, ...) returns the arguments after the nth one
+pub fn select_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let nb_args = frame.len();
+    if let Value::LuaString(s) = frame.load(0) {
+        if s.first() == Some(&b'#') {
+            returned_values.push(Value::Number((nb_args - 1) as f64));
+            return Ok(());
+        }
+    }
+
+    let n = check_number(&frame, 0, "select")? as i64;
+    let first = if n < 0 { nb_args as i64 + n } else { n.min(nb_args as i64) };
+    if first < 1 {
+        return Err(InterpreterError::ArgumentError { arg: 1, func: "select", msg: String::from("index out of range") });
+    }
+
+    for i in first as usize..nb_args {
+        returned_values.push(frame.load(i));
+    }
+    Ok(())
+}
+
+/// next(t, k) returns the key following k in the table and its value, nil at the end of the traversal
+pub fn next_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "next")?;
+    match table.borrow().next(&frame.load(1))? {
+        Some((key, val)) => {
+            returned_values.push(key);
+            returned_values.push(val);
+        }
+        None => { returned_values.push(Value::Nil) }
+    }
+    Ok(())
+}
+
+/// pairs(t) returns the iterator next, the table and nil for the generic for
+pub fn pairs_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "pairs")?;
+    returned_values.push(Value::RuntimeFunction(next_lua));
+    returned_values.push(Value::Table(table));
+    returned_values.push(Value::Nil);
+    Ok(())
+}
+
+/// iterator returned by ipairs, stops at the first nil value
+fn ipairs_iterator(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "ipairs")?;
+    let index = Value::Number(check_number(&frame, 1, "ipairs")? + 1.);
+    let val = table.borrow().get(&index);
+    if !val.is_nil() {
+        returned_values.push(index);
+        returned_values.push(val);
+    }
+    Ok(())
+}
+
+/// ipairs(t) returns an iterator over the pairs (1, t[1]), (2, t[2]), ... up to the first nil value
+pub fn ipairs_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "ipairs")?;
+    returned_values.push(Value::RuntimeFunction(ipairs_iterator));
+    returned_values.push(Value::Table(table));
+    returned_values.push(Value::Number(0.));
+    Ok(())
+}
+
+/* error(value, level) raises the value as an error object
+ * messages are prefixed with the position of the function at the given level,
+ * 1 (the default) is the function calling error, 2 the function that called it, 0 adds no position
+ */
+pub fn error_lua(frame : CallFrame, _ : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let value = frame.load(0);
+    let level = match frame.load(1) {
+        Value::Nil => { 1 }
+        _ => { check_number(&frame, 1, "error")? as i64 }
+    };
+
+    let value = match value {
+        Value::LuaString(s) if level > 0 => { with_location(env, level as usize, &s) }
+        _ => { value }
+    };
+    Err(InterpreterError::RaisedError { value })
+}
+
+/// assert(v, message) returns all its arguments if v is true, raises message otherwise
+pub fn assert_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    if check_any(&frame, 0, "assert")?.to_boolean() {
+        for i in 0..frame.len() {
+            returned_values.push(frame.load(i));
+        }
+        return Ok(());
+    }
+
+    let message = match frame.load(1) {
+        Value::Nil => { Value::from_string("assertion failed!") }
+        other => {
+            other.to_lua_string().map(Value::LuaString)
+                .ok_or_else(|| InterpreterError::ArgumentError { arg: 2, func: "assert", msg: format!("string expected, got {}", other.get_type()) })?
+        }
+    };
+    let value = match message {
+        Value::LuaString(s) => { with_location(env, 1, &s) }
+        _ => { message }
+    };
+    Err(InterpreterError::RaisedError { value })
+}
+
+/// pcall(f, ...) calls f in protected mode, returns true and the results of f or false and the error object
+pub fn pcall_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let func = check_any(&frame, 0, "pcall")?;
+    let args = (1..frame.len()).map(|i| frame.load(i)).collect();
+
+    // errors raised in f are not given to the message handler of an enclosing xpcall
+    env.error_handlers.push(Value::Nil);
+    let res = call_value(env, func, args);
+    env.error_handlers.pop();
+
+    match res {
+        Ok(results) => {
+            returned_values.push(Value::Boolean(true));
+            returned_values.extend(results);
+        }
+        Err(err) if err.is_fatal() => { return Err(err) }
+        Err(err) => {
+            returned_values.push(Value::Boolean(false));
+            returned_values.push(err.to_value());
+        }
+    }
+    Ok(())
+}
+
+/* xpcall(f, handler) calls f without arguments in protected mode
+ * on error the handler is called with the error object before the call stack is unwound,
+ * xpcall then returns false and the result of the handler
+ */
+pub fn xpcall_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let func = frame.load(0);
+    let handler = check_any(&frame, 1, "xpcall")?;
+
+    env.error_handlers.push(handler);
+    // f may not be callable, the handler also sees this error
+    let res = call_value(env, func, Vec::new()).map_err(|err| raise_error(env, err));
+    env.error_handlers.pop();
+
+    match res {
+        Ok(results) => {
+            returned_values.push(Value::Boolean(true));
+            returned_values.extend(results);
+        }
+        Err(err) if err.is_fatal() => { return Err(err) }
+        Err(err) => {
+            returned_values.push(Value::Boolean(false));
+            returned_values.push(err.to_value());
+        }
+    }
+    Ok(())
+}
+
+/* debug.traceback(message, level) returns the message followed by the traceback of the call stack from the given level,
+ * 1 (the default) is the function calling traceback, messages that are not strings are returned unchanged
+ */
+pub fn traceback_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let mut message = match frame.load(0) {
+        _ if frame.is_empty() => { Vec::new() }
+        val => {
+            match val.to_lua_string() {
+                Some(s) => { [&s[..], b"\n"].concat() }
+                None => {
+                    returned_values.push(val);
+                    return Ok(());
+                }
+            }
+        }
+    };
+    let level = match frame.load(1) {
+        Value::Nil => { 1 }
+        _ => { check_number(&frame, 1, "traceback")?.max(0.) as usize }
+    };
+
+    let traceback = env.call_stack.traceback(level, |func| env.function_name(func));
+    message.extend_from_slice(traceback.as_bytes());
+    returned_values.push(Value::from_bytes(&message));
+    Ok(())
+}
+
+/// type(v) returns the name of the type of v
+pub fn type_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let val = check_any(&frame, 0, "type")?;
+    returned_values.push(Value::from_string(&val.get_type().to_string()));
+    Ok(())
+}
+
+/// tostring(v) converts v to a string, numbers are written as "%.14g" and other values through their __tostring handler or their address
+pub fn tostring_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let val = check_any(&frame, 0, "tostring")?;
+    returned_values.push(to_string(env, val)?);
+    Ok(())
+}
+
+/// converts a string to an integer written in the given base, as strtoul with surrounding spaces
+fn str_to_integer(s : &[u8], base : u32) -> Option<f64> {
+    let s = std::str::from_utf8(s).ok()?.trim_matches(|c: char| c.is_ascii_whitespace());
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => { (true, rest) }
+        None => { (false, s) }
+    };
+    // as in C the hexadecimal prefix is accepted in base 16
+    let digits = match base {
+        16 => { digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")).unwrap_or(digits) }
+        _ => { digits }
+    };
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut n = 0.;
+    for c in digits.chars() {
+        n = n * base as f64 + c.to_digit(base)? as f64;
+    }
+    Some(if negative { -n } else { n })
+}
+
+/* tonumber(v, base) converts v to a number, nil if it is not possible
+ * with a base between 2 and 36 the argument must be a string representing an integer in that base
+ */
+pub fn tonumber_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let base = match frame.load(1) {
+        Value::Nil => { 10. }
+        _ => { check_number(&frame, 1, "tonumber")?.trunc() }
+    };
+
+    let res = if base == 10. {
+        match check_any(&frame, 0, "tonumber")? {
+            Value::Number(n) => { Some(n) }
+            Value::LuaString(s) => { str_to_number(&s) }
+            _ => { None }
+        }
+    } else {
+        if !(2. ..=36.).contains(&base) {
+            return Err(InterpreterError::ArgumentError { arg: 2, func: "tonumber", msg: String::from("base out of range") });
+        }
+        let val = frame.load(0);
+        let s = val.to_lua_string().ok_or_else(|| InterpreterError::ArgumentError { arg: 1, func: "tonumber", msg: format!("string expected, got {}", val.get_type()) })?;
+        str_to_integer(&s, base as u32)
+    };
+
+    returned_values.push(res.map(Value::Number).unwrap_or_default());
+    Ok(())
+}
+
+/// rawget(t, k) returns t[k] without calling any metamethod
+pub fn rawget_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "rawget")?;
+    let key = check_any(&frame, 1, "rawget")?;
+    returned_values.push(table.borrow().get(&key));
+    Ok(())
+}
+
+/// rawset(t, k, v) sets t[k] to v without calling any metamethod and returns t
+pub fn rawset_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "rawset")?;
+    let key = check_any(&frame, 1, "rawset")?;
+    let val = check_any(&frame, 2, "rawset")?;
+    table.borrow_mut().set(key, val)?;
+    returned_values.push(Value::Table(table));
+    Ok(())
+}
+
+/// rawequal(a, b) compares a and b without calling any metamethod
+pub fn rawequal_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let a = check_any(&frame, 0, "rawequal")?;
+    let b = check_any(&frame, 1, "rawequal")?;
+    returned_values.push(Value::Boolean(a == b));
+    Ok(())
+}
+
+/* unpack(t, i, j) returns t[i], ..., t[j], from 1 to the length of t by default
+ * raises an error when there are more values than the stack of a function can hold
+ */
+pub fn unpack_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "unpack")?;
+    let table = table.borrow();
+    let first = opt_integer(&frame, 1, "unpack", 1)?;
+    let last = match frame.load(2) {
+        Value::Nil => { table.len() as i64 }
+        _ => { check_number(&frame, 2, "unpack")? as i64 }
+    };
+    if first > last {
+        return Ok(());
+    }
+    // the bounds are saturated, their difference can overflow
+    match last.checked_sub(first) {
+        Some(n) if n < MAX_RESULTS as i64 => { env.budget.charge(n as u64 + 1)?; }
+        _ => { return Err(InterpreterError::UnpackError) }
+    }
+
+    for i in first..=last {
+        returned_values.push(table.get(&Value::Number(i as f64)));
+    }
+    Ok(())
+}
+
+/* returns the function designated by the first argument of getfenv and setfenv,
+ * either a function or a level in the call stack, 1 (the default) is the function calling them
+ * None stands for the level 0, the global environment
+ */
+fn fenv_target(frame : &CallFrame, env : &GlobalEnvironment, func : &'static str) -> Result<Option<Value>, InterpreterError> {
+    let level = match frame.load(0) {
+        f @ (Value::LuaFunction(_) | Value::RuntimeFunction(_) | Value::RuntimeClosure(_)) => { return Ok(Some(f)) }
+        Value::Nil => { 1. }
+        _ => { check_number(frame, 0, func)? }
+    };
+
+    if level < 0. {
+        return Err(InterpreterError::ArgumentError { arg: 1, func, msg: String::from("level must be non-negative") });
+    }
+    if level == 0. {
+        return Ok(None);
+    }
+    match env.call_stack.get(level as usize) {
+        Some(info) => { Ok(Some(info.function())) }
+        None => { Err(InterpreterError::ArgumentError { arg: 1, func, msg: String::from("invalid level") }) }
+    }
+}
+
+/* environment of the sandbox of the nearest lua function calling the runtime function, None when it isn't sandboxed
+ * a coroutine whose stack has no lua function, as coroutine.wrap(getfenv), is in the sandbox it was created in
+ */
+pub(crate) fn caller_sandbox(env : &GlobalEnvironment) -> Option<TableRef> {
+    let mut level = 1;
+    while let Some(info) = env.call_stack.get(level) {
+        if let Value::LuaFunction(closure) = info.function() {
+            let table = closure.env.borrow().clone();
+            return env.is_sandbox(&table).then_some(table);
+        }
+        level += 1;
+    }
+    env.coroutine.as_ref().and_then(|coroutine| coroutine.sandbox())
+}
+
+/* getfenv(f) returns the environment of the function f or of the function at level f of the call stack
+ * a sandboxed function only sees the environment of its sandbox: the other ones, as the global environment, are replaced by it
+ */
+pub fn getfenv_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let res = match fenv_target(&frame, env, "getfenv")? {
+        Some(Value::LuaFunction(closure)) => { closure.env.borrow().clone() }
+        // runtime functions use the global environment
+        _ => { env.globals() }
+    };
+    let res = match caller_sandbox(env) {
+        Some(sandbox) if !Rc::ptr_eq(&res, &sandbox) => { sandbox }
+        _ => { res }
+    };
+    returned_values.push(Value::Table(res));
+    Ok(())
+}
+
+/* setfenv(f, t) sets the environment of the function f or of the function at level f of the call stack and returns it
+ * setfenv(0, t) replaces the global environment
+ * a sandboxed function can only change the environment of the functions of its sandbox, the new environment is a sandbox too
+ */
+pub fn setfenv_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 1, "setfenv")?;
+    let target = fenv_target(&frame, env, "setfenv")?;
+    if let Some(sandbox) = caller_sandbox(env) {
+        match &target {
+            Some(Value::LuaFunction(closure)) if Rc::ptr_eq(&closure.env.borrow(), &sandbox) => { env.register_sandbox(&table) }
+            _ => { return Err(InterpreterError::SetEnvironmentError) }
+        }
+    }
+    match target {
+        Some(Value::LuaFunction(closure)) => {
+            *closure.env.borrow_mut() = table;
+            returned_values.push(Value::LuaFunction(closure));
+        }
+        None => { env.set_globals(table) }
+        _ => { return Err(InterpreterError::SetEnvironmentError) }
+    }
+    Ok(())
+}
+
+/* collectgarbage(opt, arg), values are freed as soon as they are no longer referenced so a collection
+ * only calls the __gc handlers of the released userdata
+ * the options of the reference implementation are accepted, "count" returns the kilobytes allocated by the thread
+ * as measured by CountingAllocator, 0 when it is not the global allocator
+ */
+pub fn collectgarbage_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let option = match frame.load(0) {
+        Value::Nil => { Value::from_string("collect") }
+        val => {
+            val.to_lua_string().map(Value::LuaString)
+                .ok_or_else(|| InterpreterError::ArgumentError { arg: 1, func: "collectgarbage", msg: format!("string expected, got {}", val.get_type()) })?
+        }
+    };
+
+    let res = match option.get_string().map(|s| &s[..]) {
+        Some(b"collect") => {
+            userdata::run_finalizers(env);
+            Value::Number(0.)
+        }
+        Some(b"stop") | Some(b"restart") => { Value::Number(0.) }
+        // the count wraps around when the thread released memory allocated by another one
+        Some(b"count") => { Value::Number((CountingAllocator::allocated() as isize).max(0) as f64 / 1024.) }
+        // a collection cycle is always finished
+        Some(b"step") => {
+            userdata::run_finalizers(env);
+            Value::Boolean(true)
+        }
+        // previous value of the parameter, the defaults of the reference implementation
+        Some(b"setpause") | Some(b"setstepmul") => { Value::Number(200.) }
+        _ => {
+            return Err(InterpreterError::ArgumentError { arg: 1, func: "collectgarbage", msg: format!("invalid option '{}'", option) })
+        }
+    };
+    returned_values.push(res);
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::interpreter::global_environment::GlobalEnvironment;
+use crate::interpreter::interpreter::InterpreterError;
+use crate::interpreter::object::{TableRef, Value};
+use crate::interpreter::runtime_library::RuntimeClosure;
+use crate::interpreter::table::Table;
+
+/** Groups of functions of the io and os libraries that the embedder can give to the scripts
+ * the functions of a denied group are not defined in the global environment
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// io.read, io.write, io.lines without file name and the io.stdin, io.stdout and io.stderr handles
+    pub standard_io  : bool,
+    /// io.open, io.lines with a file name, os.remove, os.rename and os.tmpname
+    pub file_system  : bool,
+    /// os.time, os.clock and os.date
+    pub time         : bool,
+    /// os.getenv
+    pub environment  : bool,
+    /// os.exit, which ends the host process
+    pub exit         : bool
+}
+
+impl Capabilities {
+
+    /// every capability, as the reference interpreter
+    pub fn all() -> Self {
+        Capabilities { standard_io: true, file_system: true, time: true, environment: true, exit: true }
+    }
+
+    /// no capability, for untrusted scripts
+    pub fn none() -> Self {
+        Capabilities { standard_io: false, file_system: false, time: false, environment: false, exit: false }
+    }
+
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities::all()
+    }
+}
+
+// functions that load or inspect code, a profile can't give them to the scripts
+const FORBIDDEN : [&str; 6] = ["load", "loadstring", "loadfile", "dofile", "string.dump", "debug"];
+
+/** Global variables given to the chunks run in a sandbox, each chunk gets its own environment built from the profile
+ * the functions are taken from the global environment of the state by name, as "print" or "string.format",
+ * the tables of the libraries are copied so that the scripts can't change the ones of the host
+ */
+#[derive(Clone, Debug, Default)]
+pub struct SandboxProfile {
+    functions : Vec<String>,
+    read_only : Vec<(String, TableRef)>,
+    globals   : Vec<(String, Value)>
+}
+
+impl SandboxProfile {
+
+    /// profile without any global variable
+    pub fn new() -> Self {
+        SandboxProfile::default()
+    }
+
+    /* functions of the base library that don't reach the global environment of the host and the string, table, math
+     * and coroutine libraries, os.time, os.clock, os.date and os.difftime when the state has them
+     * getmetatable is left out: the metatable of the strings would give access to the string library of the host
+     */
+    pub fn standard() -> Self {
+        let functions = [
+            "assert", "error", "ipairs", "next", "pairs", "pcall", "print", "rawequal", "rawget", "rawset", "select",
+            "setmetatable", "tonumber", "tostring", "type", "unpack", "xpcall", "getfenv", "setfenv", "_VERSION",
+            "string", "table", "math", "coroutine", "os.time", "os.clock", "os.date", "os.difftime"
+        ];
+        functions.iter().fold(SandboxProfile::new(), |profile, name| profile.allow(name))
+    }
+
+    /* gives the scripts a global variable of the state, as "print", or a field of a library, as "string.format"
+     * a whole library is copied, the functions loading or inspecting code are ignored: load, loadstring, string.dump and debug
+     */
+    pub fn allow(mut self, name : &str) -> Self {
+        let forbidden = FORBIDDEN.iter().any(|f| name == *f || name.strip_prefix(*f).is_some_and(|rest| rest.starts_with('.')));
+        if !forbidden {
+            self.functions.push(String::from(name));
+        }
+        self
+    }
+
+    /// gives the scripts a view of the table that they can read but not modify, as are the tables it contains
+    pub fn read_only(mut self, name : &str, table : TableRef) -> Self {
+        self.read_only.push((String::from(name), table));
+        self
+    }
+
+    /// gives the scripts a value of the host, as a host function
+    pub fn global(mut self, name : &str, value : Value) -> Self {
+        self.globals.push((String::from(name), value));
+        self
+    }
+
+}
+
+/* creates the environment of a sandboxed chunk from the profile, _G is the environment itself
+ * the functions run in it only see environments of sandboxes through getfenv and setfenv
+ */
+pub(crate) fn new_environment(env : &mut GlobalEnvironment, profile : &SandboxProfile) -> TableRef {
+    let globals = env.globals();
+    let globals = globals.borrow();
+    let mut sandbox = Table::new();
+
+    for name in &profile.functions {
+        match name.split_once('.') {
+            Some((library, field)) => {
+                let val = match globals.get_str(library) {
+                    Value::Table(t) => { t.borrow().get_str(field) }
+                    _ => { Value::Nil }
+                };
+                if val.is_nil() {
+                    continue;
+                }
+                let library = match sandbox.get_str(library) {
+                    Value::Table(t) => { t }
+                    _ => {
+                        let t = Rc::new(RefCell::new(Table::new()));
+                        set_field(&mut sandbox, library, Value::Table(Rc::clone(&t)));
+                        t
+                    }
+                };
+                set_field(&mut library.borrow_mut(), field, val);
+            }
+            None => {
+                let val = match globals.get_str(name) {
+                    Value::Table(t) => { Value::Table(Rc::new(RefCell::new(copy_table(&t.borrow())))) }
+                    val => { val }
+                };
+                set_field(&mut sandbox, name, val);
+            }
+        }
+    }
+
+    let proxies = Rc::default();
+    for (name, table) in &profile.read_only {
+        set_field(&mut sandbox, name, Value::Table(read_only_proxy(table, &proxies)));
+    }
+    for (name, val) in &profile.globals {
+        set_field(&mut sandbox, name, val.clone());
+    }
+
+    let sandbox = Rc::new(RefCell::new(sandbox));
+    set_field(&mut sandbox.borrow_mut(), "_G", Value::Table(Rc::clone(&sandbox)));
+    env.register_sandbox(&sandbox);
+    sandbox
+}
+
+fn set_field(table : &mut Table, name : &str, val : Value) {
+    table.set(Value::from_string(name), val).expect("field names are strings");
+}
+
+/// copies the fields of a table, not the tables they contain
+fn copy_table(table : &Table) -> Table {
+    let mut res = Table::new();
+    let mut key = Value::Nil;
+    while let Ok(Some((next, val))) = table.next(&key) {
+        res.set(next.clone(), val).expect("keys of a table are valid keys");
+        key = next;
+    }
+    res
+}
+
+// proxies of the tables of a read-only view, a table reached twice gives the same proxy
+type Proxies = Rc<RefCell<HashMap<*const RefCell<Table>, Weak<RefCell<Table>>>>>;
+
+/* returns an empty table whose fields are the ones of the table, read without calling its metamethods
+ * the tables it contains are read through proxies too and the metatable of the proxies is protected
+ * the proxies refuse raw writes as rawset, pairs and the length operator see an empty table
+ */
+fn read_only_proxy(table : &TableRef, proxies : &Proxies) -> TableRef {
+    if let Some(proxy) = proxies.borrow().get(&Rc::as_ptr(table)).and_then(Weak::upgrade) {
+        return proxy;
+    }
+
+    let source = Rc::clone(table);
+    let nested = Rc::clone(proxies);
+    let mut metatable = Table::new();
+    set_field(&mut metatable, "__index", RuntimeClosure::new_value(move |frame, returned_values, _| {
+        let res = match source.borrow().get(&frame.load(1)) {
+            Value::Table(t) => { Value::Table(read_only_proxy(&t, &nested)) }
+            val => { val }
+        };
+        returned_values.push(res);
+        Ok(())
+    }));
+    set_field(&mut metatable, "__newindex", RuntimeClosure::new_value(|_, _, _| Err(InterpreterError::ReadOnlyError)));
+    set_field(&mut metatable, "__metatable", Value::Boolean(false));
+
+    let mut proxy = Table::new();
+    proxy.metatable = Some(Rc::new(RefCell::new(metatable)));
+    proxy.read_only = true;
+    let proxy = Rc::new(RefCell::new(proxy));
+    proxies.borrow_mut().insert(Rc::as_ptr(table), Rc::downgrade(&proxy));
+    proxy
+}
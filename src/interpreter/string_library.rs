@@ -0,0 +1,392 @@
+use std::cell::Cell;
+
+use crate::interpreter::{call_frame::CallFrame, global_environment::GlobalEnvironment, object::Value};
+use crate::interpreter::interpreter::{call_value, InterpreterError};
+use crate::interpreter::metatable;
+use crate::interpreter::pattern::{self, MatchState};
+use crate::interpreter::printf::{self, FormatError, FormatSpec};
+use crate::interpreter::runtime_library::{check_any, check_number, check_string, opt_integer, RuntimeClosure};
+
+// size in bytes of the largest string string.rep builds, a larger allocation would abort the host instead of raising an error
+const MAX_STRING_SIZE : usize = i32::MAX as usize;
+
+/// converts a relative position in a string of length len, negative positions count from the end
+fn relative_position(pos : i64, len : usize) -> i64 {
+    let pos = if pos < 0 { pos + len as i64 + 1 } else { pos };
+    pos.max(0)
+}
+
+/// string.len(s) returns the number of bytes of s
+pub fn len_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let s = check_string(&frame, 0, "len")?;
+    returned_values.push(Value::Number(s.len() as f64));
+    Ok(())
+}
+
+/// string.sub(s, i, j) returns the substring of s from i to j (-1 by default)
+pub fn sub_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let s = check_string(&frame, 0, "sub")?;
+    let start = relative_position(check_number(&frame, 1, "sub")? as i64, s.len()).max(1);
+    let end = relative_position(opt_integer(&frame, 2, "sub", -1)?, s.len()).min(s.len() as i64);
+
+    if start <= end {
+        returned_values.push(Value::from_bytes(&s[start as usize - 1..end as usize]));
+    } else {
+        returned_values.push(Value::from_string(""));
+    }
+    Ok(())
+}
+
+/// string.upper(s) converts the lower case letters of s to upper case
+pub fn upper_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let s = check_string(&frame, 0, "upper")?;
+    returned_values.push(Value::from_bytes(&s.to_ascii_uppercase()));
+    Ok(())
+}
+
+/// string.lower(s) converts the upper case letters of s to lower case
+pub fn lower_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let s = check_string(&frame, 0, "lower")?;
+    returned_values.push(Value::from_bytes(&s.to_ascii_lowercase()));
+    Ok(())
+}
+
+/// string.rep(s, n) returns n copies of s
+pub fn rep_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let s = check_string(&frame, 0, "rep")?;
+    let n = check_number(&frame, 1, "rep")?.max(0.) as usize;
+    let len = match s.len().checked_mul(n) {
+        Some(len) if len <= MAX_STRING_SIZE => { len }
+        _ => { return Err(InterpreterError::StringSizeError) }
+    };
+    env.budget.check_string_length(len)?;
+    env.budget.charge(n as u64)?;
+    returned_values.push(Value::from_bytes(&s.repeat(n)));
+    Ok(())
+}
+
+/// string.reverse(s) returns the bytes of s in the reverse order
+pub fn reverse_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let s = check_string(&frame, 0, "reverse")?;
+    let reversed: Vec<u8> = s.iter().rev().copied().collect();
+    returned_values.push(Value::from_bytes(&reversed));
+    Ok(())
+}
+
+/// string.byte(s, i, j) returns the codes of the bytes of s from i (1 by default) to j (i by default)
+pub fn byte_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let s = check_string(&frame, 0, "byte")?;
+    let start = relative_position(opt_integer(&frame, 1, "byte", 1)?, s.len());
+    let end = relative_position(opt_integer(&frame, 2, "byte", start)?, s.len()).min(s.len() as i64);
+    let start = start.max(1);
+
+    for i in start..=end {
+        returned_values.push(Value::Number(s[i as usize - 1] as f64));
+    }
+    Ok(())
+}
+
+/// string.char(...) returns the string made of the bytes with the given codes
+pub fn char_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let mut res = Vec::with_capacity(frame.len());
+    for i in 0..frame.len() {
+        let code = check_number(&frame, i, "char")? as i64;
+        let byte = u8::try_from(code).map_err(|_| InterpreterError::ArgumentError { arg: i + 1, func: "char", msg: String::from("invalid value") })?;
+        res.push(byte);
+    }
+    returned_values.push(Value::from_bytes(&res));
+    Ok(())
+}
+
+/// returns the position where a search starts from the optional argument i, 1 by default
+fn search_start(frame : &CallFrame, i : usize, func : &'static str, len : usize) -> Result<usize, InterpreterError> {
+    let init = relative_position(opt_integer(frame, i, func, 1)?, len) - 1;
+    Ok(init.clamp(0, len as i64) as usize)
+}
+
+/* common part of string.find and string.match, find returns the position of the match followed by the captures
+ * and match only returns the captures
+ */
+fn find_aux(frame : CallFrame, returned_values : &mut Vec<Value>, env : &GlobalEnvironment, find : bool) -> Result<(), InterpreterError> {
+    let func = if find { "find" } else { "match" };
+    let s = check_string(&frame, 0, func)?;
+    let p = check_string(&frame, 1, func)?;
+    let init = search_start(&frame, 2, func, s.len())?;
+
+    if find && (frame.load(3).to_boolean() || pattern::is_plain(&p)) {
+        match pattern::find_plain(&s[init..], &p) {
+            Some(position) => {
+                returned_values.push(Value::Number((init + position + 1) as f64));
+                returned_values.push(Value::Number((init + position + p.len()) as f64));
+            }
+            None => { returned_values.push(Value::Nil) }
+        }
+        return Ok(());
+    }
+
+    let anchor = p.first() == Some(&b'^');
+    let pat = if anchor { &p[1..] } else { &p[..] };
+    let mut ms = MatchState::new(&s, pat, env.budget.resources());
+    let mut start = init;
+    loop {
+        ms.reset();
+        if let Some(end) = ms.do_match(start, 0)? {
+            if find {
+                returned_values.push(Value::Number((start + 1) as f64));
+                returned_values.push(Value::Number(end as f64));
+                returned_values.extend(ms.get_captures(None)?);
+            } else {
+                returned_values.extend(ms.get_captures(Some((start, end)))?);
+            }
+            return Ok(());
+        }
+        start += 1;
+        if anchor || start > s.len() {
+            break;
+        }
+    }
+
+    returned_values.push(Value::Nil);
+    Ok(())
+}
+
+/// string.find(s, pattern, init, plain) returns the start and the end of the first match of the pattern in s and its captures
+pub fn find_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    find_aux(frame, returned_values, env, true)
+}
+
+/// string.match(s, pattern, init) returns the captures of the first match of the pattern in s
+pub fn match_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    find_aux(frame, returned_values, env, false)
+}
+
+/// string.gmatch(s, pattern) returns an iterator over the captures of the successive matches of the pattern in s
+pub fn gmatch_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let s = check_string(&frame, 0, "gmatch")?;
+    let p = check_string(&frame, 1, "gmatch")?;
+    // position where the next search starts
+    let position = Cell::new(0);
+
+    let iterator = move |_ : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment| {
+        let mut ms = MatchState::new(&s, &p, env.budget.resources());
+        for start in position.get()..=s.len() {
+            ms.reset();
+            if let Some(end) = ms.do_match(start, 0)? {
+                // an empty match moves the search forward
+                position.set(if end == start { end + 1 } else { end });
+                returned_values.extend(ms.get_captures(Some((start, end)))?);
+                return Ok(());
+            }
+        }
+        position.set(s.len() + 1);
+        Ok(())
+    };
+
+    returned_values.push(RuntimeClosure::new_value(iterator));
+    Ok(())
+}
+
+/// appends the replacement string of gsub where %0 to %9 stand for the captures and %% for %
+fn add_string(ms : &MatchState, res : &mut Vec<u8>, repl : &[u8], start : usize, end : usize) -> Result<(), InterpreterError> {
+    let mut i = 0;
+    while i < repl.len() {
+        if repl[i] != b'%' {
+            res.push(repl[i]);
+        } else {
+            i += 1;
+            let c = repl.get(i).copied().unwrap_or(0);
+            if !c.is_ascii_digit() {
+                res.push(c);
+            } else if c == b'0' {
+                res.extend_from_slice(&ms.src()[start..end]);
+            } else {
+                let capture = ms.get_capture((c - b'1') as usize, Some((start, end)))?;
+                res.extend_from_slice(&capture.to_lua_string().unwrap_or_default());
+            }
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// appends the replacement of the match from start to end, the match is kept when the replacement is false or nil
+fn add_value(
+    ms : &MatchState,
+    env : &mut GlobalEnvironment,
+    res : &mut Vec<u8>,
+    repl : &Value,
+    start : usize,
+    end : usize)
+    -> Result<(), InterpreterError> {
+    let value = match repl {
+        Value::LuaString(_) | Value::Number(_) => {
+            let repl = repl.to_lua_string().unwrap_or_default();
+            return add_string(ms, res, &repl, start, end);
+        }
+        Value::Table(_) => {
+            let key = ms.get_capture(0, Some((start, end)))?;
+            metatable::index(env, repl.clone(), key)?
+        }
+        _ => {
+            let captures = ms.get_captures(Some((start, end)))?;
+            call_value(env, repl.clone(), captures)?.into_iter().next().unwrap_or_default()
+        }
+    };
+
+    if !value.to_boolean() {
+        res.extend_from_slice(&ms.src()[start..end]);
+        return Ok(());
+    }
+    match value.to_lua_string() {
+        Some(s) => { res.extend_from_slice(&s); Ok(()) }
+        None => { Err(InterpreterError::ReplacementError { typ: value.get_type() }) }
+    }
+}
+
+/* string.gsub(s, pattern, repl, n) replaces the first n (all by default) matches of the pattern in s by repl
+ * repl is a string, a table indexed by the first capture or a function called with the captures
+ * returns the new string and the number of matches
+ */
+pub fn gsub_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let s = check_string(&frame, 0, "gsub")?;
+    let p = check_string(&frame, 1, "gsub")?;
+    let repl = check_any(&frame, 2, "gsub")?;
+    let max_n = opt_integer(&frame, 3, "gsub", s.len() as i64 + 1)?;
+    match repl {
+        Value::Number(_) | Value::LuaString(_) | Value::Table(_) | Value::LuaFunction(_)
+        | Value::RuntimeFunction(_) | Value::RuntimeClosure(_) => {}
+        _ => {
+            return Err(InterpreterError::ArgumentError { arg: 3, func: "gsub", msg: String::from("string/function/table expected") });
+        }
+    }
+
+    let anchor = p.first() == Some(&b'^');
+    let pat = if anchor { &p[1..] } else { &p[..] };
+    let mut ms = MatchState::new(&s, pat, env.budget.resources());
+    let mut res = Vec::with_capacity(s.len());
+    let mut src = 0;
+    let mut n = 0;
+
+    while n < max_n {
+        env.budget.charge(1)?;
+        ms.reset();
+        let end = ms.do_match(src, 0)?;
+        if let Some(end) = end {
+            n += 1;
+            add_value(&ms, env, &mut res, &repl, src, end)?;
+            env.budget.check_string_length(res.len())?;
+        }
+        match end {
+            Some(end) if end > src => { src = end; }
+            _ if src < s.len() => { res.push(s[src]); src += 1; }
+            _ => { break }
+        }
+        if anchor {
+            break;
+        }
+    }
+    res.extend_from_slice(&s[src..]);
+
+    returned_values.push(Value::from_bytes(&res));
+    returned_values.push(Value::Number(n as f64));
+    Ok(())
+}
+
+/// writes s between double quotes so that it can be read back by Lua
+fn add_quoted(res : &mut Vec<u8>, s : &[u8]) {
+    res.push(b'"');
+    for &c in s {
+        match c {
+            b'"' | b'\\' | b'\n' => { res.push(b'\\'); res.push(c); }
+            b'\r' => { res.extend_from_slice(b"\\r"); }
+            b'\0' => { res.extend_from_slice(b"\\000"); }
+            _ => { res.push(c); }
+        }
+    }
+    res.push(b'"');
+}
+
+/// returns the argument number i (starting from 0) of string.format converted to an integer
+fn check_format_integer(frame : &CallFrame, i : usize, signed : bool) -> Result<i64, InterpreterError> {
+    const LIMIT : f64 = 9223372036854775808.; // 2^63
+    let n = check_number(frame, i, "format")?.trunc();
+    if signed && (-LIMIT..LIMIT).contains(&n) {
+        Ok(n as i64)
+    } else if !signed && (-LIMIT..2. * LIMIT).contains(&n) {
+        // negative numbers wrap around as in a C cast
+        Ok(if n < LIMIT { n as i64 } else { (n as u64) as i64 })
+    } else {
+        let msg = if signed { "not a number in proper range" } else { "not a non-negative number in proper range" };
+        Err(InterpreterError::ArgumentError { arg: i + 1, func: "format", msg: String::from(msg) })
+    }
+}
+
+/* string.format(fmt, ...) returns fmt where each conversion specification is replaced by the next argument
+ * formatted as the C function sprintf does, %q writes a string that can be read back by Lua
+ */
+pub fn format_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let fmt = check_string(&frame, 0, "format")?;
+    let mut res = Vec::with_capacity(fmt.len());
+    let mut arg = 0;
+    let mut i = 0;
+
+    while i < fmt.len() {
+        if fmt[i] != b'%' {
+            res.push(fmt[i]);
+            i += 1;
+            continue;
+        }
+        if fmt.get(i + 1) == Some(&b'%') {
+            res.push(b'%');
+            i += 2;
+            continue;
+        }
+
+        arg += 1;
+        if arg >= frame.len() {
+            return Err(InterpreterError::ArgumentError { arg: arg + 1, func: "format", msg: String::from("no value") });
+        }
+        let (spec, len) = FormatSpec::parse(&fmt[i + 1..])?;
+        i += len + 1;
+
+        match spec.conversion {
+            b'c' => {
+                let c = check_format_integer(&frame, arg, true)? as u8;
+                res.extend(printf::pad(&[c], &spec));
+            }
+            b'd' | b'i' => {
+                let n = check_format_integer(&frame, arg, true)?;
+                res.extend_from_slice(printf::format_integer(n, &spec).as_bytes());
+            }
+            b'o' | b'u' | b'x' | b'X' => {
+                let n = check_format_integer(&frame, arg, false)?;
+                res.extend_from_slice(printf::format_unsigned(n as u64, &spec).as_bytes());
+            }
+            b'e' | b'E' | b'f' | b'g' | b'G' => {
+                let n = check_number(&frame, arg, "format")?;
+                res.extend_from_slice(printf::format_float(n, &spec).as_bytes());
+            }
+            b'q' => {
+                add_quoted(&mut res, &check_string(&frame, arg, "format")?);
+            }
+            b's' => {
+                let s = check_string(&frame, arg, "format")?;
+                match spec.precision {
+                    // long strings without precision are kept whole, the width is ignored
+                    None if s.len() >= 100 => { res.extend_from_slice(&s); }
+                    _ => {
+                        // sprintf stops at the first '\0'
+                        let s = s.split(|&c| c == b'\0').next().unwrap_or(&[]);
+                        let s = &s[..spec.precision.map_or(s.len(), |precision| precision.min(s.len()))];
+                        res.extend(printf::pad(s, &spec));
+                    }
+                }
+            }
+            option => { return Err(FormatError::InvalidOptionError { option }.into()); }
+        }
+    }
+
+    env.budget.check_string_length(res.len())?;
+    returned_values.push(Value::from_bytes(&res));
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use crate::interpreter::object::{TableRef, Value};
+
+#[derive(thiserror::Error, Debug)]
+pub enum TableError {
+    #[error("table index is nil")]
+    NilIndexError,
+    #[error("table index is NaN")]
+    NaNIndexError,
+    #[error("invalid key to 'next'")]
+    NextKeyError,
+    #[error("attempt to modify a read-only table")]
+    ReadOnlyError
+}
+
+/** Lua table, integer keys from 1 to n are stored in a vector and the other keys in a hash part
+ * the hash part remembers the insertion order of the keys so that traversals are deterministic
+ */
+#[derive(Default)]
+pub struct Table {
+    array     : Vec<Value>,
+    // position of each key in the entries vector
+    hash      : HashMap<Value, usize>,
+    // removed entries keep their place with a nil value until the next compaction
+    entries   : Vec<(Value, Value)>,
+    removed   : usize,
+    pub metatable : Option<TableRef>,
+    // refuses every write, even the raw ones of rawset and of the table library
+    pub read_only : bool
+}
+
+/// returns the position in the array part corresponding to the key if it is a positive integer
+fn array_index(key : &Value) -> Option<usize> {
+    match key {
+        Value::Number(n) if n.fract() == 0. && *n >= 1. && *n <= usize::MAX as f64 => { Some(*n as usize - 1) }
+        _ => { None }
+    }
+}
+
+impl Table {
+
+    pub fn new() -> Self {
+        Table::default()
+    }
+
+    pub fn with_capacity(array_size : usize, hash_size : usize) -> Self {
+        Table {
+            array   : Vec::with_capacity(array_size),
+            hash    : HashMap::with_capacity(hash_size),
+            entries : Vec::with_capacity(hash_size),
+            ..Table::default()
+        }
+    }
+
+    /// returns the value associated with the key without calling any metamethod
+    pub fn get(&self, key : &Value) -> Value {
+        if let Some(i) = array_index(key) {
+            if i < self.array.len() {
+                return self.array[i].clone();
+            }
+        }
+
+        match self.hash.get(key) {
+            Some(&i) => { self.entries[i].1.clone() }
+            None => { Value::Nil }
+        }
+    }
+
+    /// shortcut for string keys, used to find metamethods
+    pub fn get_str(&self, key : &str) -> Value {
+        self.get(&Value::from_string(key))
+    }
+
+    /// associates the value to the key without calling any metamethod
+    pub fn set(&mut self, key : Value, val : Value) -> Result<(), TableError> {
+        match key {
+            Value::Nil => { return Err(TableError::NilIndexError) }
+            Value::Number(n) if n.is_nan() => { return Err(TableError::NaNIndexError) }
+            _ if self.read_only => { return Err(TableError::ReadOnlyError) }
+            _ => {}
+        }
+
+        if let Some(i) = array_index(&key) {
+            if i < self.array.len() {
+                self.array[i] = val;
+                // the array part never ends with nil so that its size is the length of the table
+                while self.array.last().is_some_and(Value::is_nil) {
+                    self.array.pop();
+                }
+                return Ok(());
+            }
+            if i == self.array.len() && !val.is_nil() {
+                self.remove_entry(&key);
+                self.array.push(val);
+                self.migrate_to_array();
+                return Ok(());
+            }
+        }
+
+        match self.hash.get(&key) {
+            Some(&i) => {
+                if val.is_nil() && !self.entries[i].1.is_nil() {
+                    self.removed += 1;
+                } else if !val.is_nil() && self.entries[i].1.is_nil() {
+                    self.removed -= 1;
+                }
+                self.entries[i].1 = val;
+            }
+            None if !val.is_nil() => {
+                if self.removed > self.entries.len() / 2 {
+                    self.compact();
+                }
+                self.hash.insert(key.clone(), self.entries.len());
+                self.entries.push((key, val));
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// moves the integer keys following the array part from the hash part to the array part
+    fn migrate_to_array(&mut self) {
+        loop {
+            let key = Value::Number((self.array.len() + 1) as f64);
+            match self.remove_entry(&key) {
+                Some(val) => { self.array.push(val) }
+                None => { break }
+            }
+        }
+    }
+
+    /// removes a key from the hash part and returns its value if it was present
+    fn remove_entry(&mut self, key : &Value) -> Option<Value> {
+        let i = self.hash.remove(key)?;
+        let val = std::mem::take(&mut self.entries[i].1);
+        if val.is_nil() {
+            return None;
+        }
+        self.removed += 1;
+        Some(val)
+    }
+
+    /// drops the removed entries from the hash part
+    fn compact(&mut self) {
+        self.entries.retain(|(_, val)| !val.is_nil());
+        self.hash.clear();
+        for (i, (key, _)) in self.entries.iter().enumerate() {
+            self.hash.insert(key.clone(), i);
+        }
+        self.removed = 0;
+    }
+
+    /* returns the key and value following the given key in the traversal of the table, None at the end of the traversal
+     * the traversal starts with the array part, then the hash part in the insertion order of the keys
+     * fields can be cleared during the traversal
+     */
+    pub fn next(&self, key : &Value) -> Result<Option<(Value, Value)>, TableError> {
+        let (array_start, entries_start) = match key {
+            Value::Nil => { (0, 0) }
+            _ => {
+                let position = array_index(key);
+                match (position, self.hash.get(key)) {
+                    (Some(i), _) if i < self.array.len() => { (i + 1, 0) }
+                    (_, Some(&j)) => { (self.array.len(), j + 1) }
+                    // the key was at the end of the array part before being cleared
+                    (Some(_), None) => { (self.array.len(), 0) }
+                    (None, None) => { return Err(TableError::NextKeyError) }
+                }
+            }
+        };
+
+        for i in array_start..self.array.len() {
+            if !self.array[i].is_nil() {
+                return Ok(Some((Value::Number((i + 1) as f64), self.array[i].clone())));
+            }
+        }
+
+        for (key, val) in &self.entries[entries_start.min(self.entries.len())..] {
+            if !val.is_nil() {
+                return Ok(Some((key.clone(), val.clone())));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// returns the length of the table as the # operator does
+    pub fn len(&self) -> usize {
+        self.array.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.array.is_empty() && self.entries.len() == self.removed
+    }
+
+}
+
+// tables can reference themselves so we only display their address
+impl fmt::Debug for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "table: {:p}", self)
+    }
+}
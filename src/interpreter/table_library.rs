@@ -0,0 +1,232 @@
+use crate::interpreter::{call_frame::CallFrame, global_environment::GlobalEnvironment, object::{TableRef, TypeLua, Value}};
+use crate::interpreter::interpreter::{call_value, InterpreterError};
+use crate::interpreter::metatable;
+use crate::interpreter::runtime_library::{check_number, check_string, check_table, opt_integer};
+
+#[derive(thiserror::Error, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum TableLibraryError {
+    #[error("wrong number of arguments to 'insert'")]
+    InsertArgumentsError,
+    #[error("invalid value (at index {index}) in table for 'concat'")]
+    ConcatValueError {
+        index : i64
+    },
+    #[error("invalid order function for sorting")]
+    OrderFunctionError,
+    #[error("'setn' is obsolete")]
+    ObsoleteError
+}
+
+// the functions of the table library only use raw accesses, as in the reference implementation
+fn get_index(table : &TableRef, i : i64) -> Value {
+    table.borrow().get(&Value::Number(i as f64))
+}
+
+fn set_index(table : &TableRef, i : i64, val : Value) -> Result<(), InterpreterError> {
+    table.borrow_mut().set(Value::Number(i as f64), val)?;
+    Ok(())
+}
+
+/// table.getn(t) returns the length of t, kept from Lua 5.0
+pub fn getn_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "getn")?;
+    returned_values.push(Value::Number(table.borrow().len() as f64));
+    Ok(())
+}
+
+/// table.setn(t, n) cannot change the length of a table since Lua 5.1
+pub fn setn_lua(frame : CallFrame, _ : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    check_table(&frame, 0, "setn")?;
+    Err(TableLibraryError::ObsoleteError.into())
+}
+
+/// table.maxn(t) returns the largest positive numerical key of t, or 0
+pub fn maxn_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "maxn")?;
+    let table = table.borrow();
+    let mut max = 0.;
+    let mut key = Value::Nil;
+    while let Some((k, _)) = table.next(&key)? {
+        if let Value::Number(n) = k {
+            if n > max {
+                max = n;
+            }
+        }
+        key = k;
+    }
+    returned_values.push(Value::Number(max));
+    Ok(())
+}
+
+/* table.insert(t, [pos,] v) inserts v at the position pos (the end of t by default), shifting up the following elements
+ * positions below 1 would shift the elements down to them and are refused as in Lua 5.2
+ */
+pub fn insert_lua(frame : CallFrame, _ : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "insert")?;
+    // first empty element
+    let mut end = table.borrow().len() as i64 + 1;
+    let position = match frame.len() {
+        2 => { end }
+        3 => {
+            // the position is saturated, the element following it must exist
+            let position = check_number(&frame, 1, "insert")? as i64;
+            if position < 1 || position.checked_add(1).is_none() {
+                return Err(InterpreterError::ArgumentError { arg: 2, func: "insert", msg: String::from("position out of bounds") });
+            }
+            end = end.max(position);
+            for i in (position + 1..=end).rev() {
+                set_index(&table, i, get_index(&table, i - 1))?;
+            }
+            position
+        }
+        _ => { return Err(TableLibraryError::InsertArgumentsError.into()) }
+    };
+    set_index(&table, position, frame.load(frame.len() - 1))
+}
+
+/// table.remove(t, pos) removes and returns the element at the position pos (the last one by default), shifting down the following elements
+pub fn remove_lua(frame : CallFrame, returned_values : &mut Vec<Value>, _ : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "remove")?;
+    let end = table.borrow().len() as i64;
+    let position = opt_integer(&frame, 1, "remove", end)?;
+    if !(1..=end).contains(&position) {
+        return Ok(());
+    }
+
+    returned_values.push(get_index(&table, position));
+    for i in position..end {
+        set_index(&table, i, get_index(&table, i + 1))?;
+    }
+    set_index(&table, end, Value::Nil)
+}
+
+/// table.concat(t, sep, i, j) returns the concatenation of the strings or numbers t[i] to t[j] separated by sep
+pub fn concat_lua(frame : CallFrame, returned_values : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "concat")?;
+    let separator = match frame.load(1) {
+        Value::Nil => { None }
+        _ => { Some(check_string(&frame, 1, "concat")?) }
+    };
+    let first = opt_integer(&frame, 2, "concat", 1)?;
+    let last = opt_integer(&frame, 3, "concat", table.borrow().len() as i64)?;
+
+    let mut res = Vec::new();
+    for i in first..=last {
+        env.budget.charge(1)?;
+        let s = get_index(&table, i).to_lua_string().ok_or(TableLibraryError::ConcatValueError { index: i })?;
+        res.extend_from_slice(&s);
+        if let (Some(separator), true) = (&separator, i < last) {
+            res.extend_from_slice(separator);
+        }
+        env.budget.check_string_length(res.len())?;
+    }
+
+    returned_values.push(Value::from_bytes(&res));
+    Ok(())
+}
+
+/// evaluates a < b with the order function of table.sort, or the < operator if there is none
+fn sort_less(env : &mut GlobalEnvironment, comparator : &Value, a : Value, b : Value) -> Result<bool, InterpreterError> {
+    env.budget.charge(1)?;
+    match comparator {
+        Value::Nil => { metatable::less_than(env, a, b) }
+        _ => {
+            let res = call_value(env, comparator.clone(), vec![a, b])?;
+            Ok(res.first().is_some_and(Value::to_boolean))
+        }
+    }
+}
+
+/* quicksort of the elements from l to u of the table, ported from the reference implementation so that
+ * the order functions are called on the same elements and inconsistent ones are detected the same way
+ */
+fn sort_range(env : &mut GlobalEnvironment, table : &TableRef, comparator : &Value, mut l : i64, mut u : i64) -> Result<(), InterpreterError> {
+    while l < u {
+        // sorts a[l], a[(l + u) / 2] and a[u]
+        let (a_l, a_u) = (get_index(table, l), get_index(table, u));
+        if sort_less(env, comparator, a_u.clone(), a_l.clone())? {
+            set_index(table, l, a_u)?;
+            set_index(table, u, a_l)?;
+        }
+        if u - l == 1 {
+            break;
+        }
+
+        let mut i = (l + u) / 2;
+        let (a_i, a_l) = (get_index(table, i), get_index(table, l));
+        if sort_less(env, comparator, a_i.clone(), a_l.clone())? {
+            set_index(table, i, a_l)?;
+            set_index(table, l, a_i)?;
+        } else {
+            let a_u = get_index(table, u);
+            if sort_less(env, comparator, a_u.clone(), a_i.clone())? {
+                set_index(table, i, a_u)?;
+                set_index(table, u, a_i)?;
+            }
+        }
+        if u - l == 2 {
+            break;
+        }
+
+        // the pivot is moved to u - 1, a[l] <= pivot <= a[u] so only l + 1 to u - 2 remain to be sorted
+        let pivot = get_index(table, i);
+        set_index(table, i, get_index(table, u - 1))?;
+        set_index(table, u - 1, pivot.clone())?;
+
+        i = l;
+        let mut j = u - 1;
+        loop {
+            // invariant: a[l..i] <= pivot <= a[j..u]
+            i += 1;
+            while sort_less(env, comparator, get_index(table, i), pivot.clone())? {
+                if i > u {
+                    return Err(TableLibraryError::OrderFunctionError.into());
+                }
+                i += 1;
+            }
+            j -= 1;
+            while sort_less(env, comparator, pivot.clone(), get_index(table, j))? {
+                if j < l {
+                    return Err(TableLibraryError::OrderFunctionError.into());
+                }
+                j -= 1;
+            }
+            if j < i {
+                break;
+            }
+            let (a_i, a_j) = (get_index(table, i), get_index(table, j));
+            set_index(table, i, a_j)?;
+            set_index(table, j, a_i)?;
+        }
+
+        // swaps the pivot with a[i], a[l..i - 1] <= a[i] <= a[i + 1..u]
+        let (a_u, a_i) = (get_index(table, u - 1), get_index(table, i));
+        set_index(table, u - 1, a_i)?;
+        set_index(table, i, a_u)?;
+
+        // the smaller half is sorted recursively and the larger one by the loop
+        if i - l < u - i {
+            sort_range(env, table, comparator, l, i - 1)?;
+            l = i + 1;
+        } else {
+            sort_range(env, table, comparator, i + 1, u)?;
+            u = i - 1;
+        }
+    }
+    Ok(())
+}
+
+/// table.sort(t, comp) sorts the elements of t from 1 to #t in place, comp(a, b) returns true when a must come before b
+pub fn sort_lua(frame : CallFrame, _ : &mut Vec<Value>, env : &mut GlobalEnvironment) -> Result<(), InterpreterError> {
+    let table = check_table(&frame, 0, "sort")?;
+    let comparator = frame.load(1);
+    match comparator.get_type() {
+        TypeLua::Nil | TypeLua::Function => {}
+        other => {
+            return Err(InterpreterError::ArgumentError { arg: 2, func: "sort", msg: format!("function expected, got {}", other) });
+        }
+    }
+    let len = table.borrow().len() as i64;
+    sort_range(env, &table, &comparator, 1, len)
+}
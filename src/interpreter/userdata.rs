@@ -0,0 +1,122 @@
+use core::fmt;
+use std::any::{Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::mem;
+use std::rc::{Rc, Weak};
+
+use crate::interpreter::global_environment::GlobalEnvironment;
+use crate::interpreter::interpreter::call_value;
+use crate::interpreter::metatable::MetaMethod;
+use crate::interpreter::object::{TableRef, Value};
+
+/// Userdata released by the scripts whose __gc handler has not been called yet
+pub type FinalizerQueue = Rc<RefCell<Vec<Rc<UserData>>>>;
+
+/** Rust value handed to the scripts, its methods and operators are the handlers of its metatable
+ * the value is dropped with the last reference to the userdata,
+ * the __gc handler of its metatable is then called with the userdata by the state that created it
+ * references are counted and cycles are not collected: a userdata reachable from itself, as one stored in a table its
+ * value holds or captured by a closure of its metatable, is never released and its __gc handler is never called
+ */
+pub struct UserData {
+    value      : RefCell<Box<dyn Any>>,
+    type_id    : TypeId,
+    metatable  : RefCell<Option<TableRef>>,
+    // queue of the state receiving the userdata when it is released, empty once it was finalized
+    finalizers : Weak<RefCell<Vec<Rc<UserData>>>>
+}
+
+impl UserData {
+
+    pub fn new<T : 'static>(value : T, metatable : Option<TableRef>) -> Self {
+        UserData {
+            value      : RefCell::new(Box::new(value)),
+            type_id    : TypeId::of::<T>(),
+            metatable  : RefCell::new(metatable),
+            finalizers : Weak::new()
+        }
+    }
+
+    /// userdata whose __gc handler is called by the state owning the queue once it is released
+    pub fn with_finalizers<T : 'static>(value : T, metatable : Option<TableRef>, finalizers : &FinalizerQueue) -> Self {
+        UserData {
+            value      : RefCell::new(Box::new(value)),
+            type_id    : TypeId::of::<T>(),
+            metatable  : RefCell::new(metatable),
+            finalizers : Rc::downgrade(finalizers)
+        }
+    }
+
+    /// tells if the value is a T
+    pub fn is<T : 'static>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    /// returns the value if it is a T, None if it has another type or is being modified
+    pub fn borrow<T : 'static>(&self) -> Option<Ref<'_, T>> {
+        let value = self.value.try_borrow().ok()?;
+        Ref::filter_map(value, |v| v.downcast_ref::<T>()).ok()
+    }
+
+    /// returns the value to modify it if it is a T, None if it has another type or is already borrowed
+    pub fn borrow_mut<T : 'static>(&self) -> Option<RefMut<'_, T>> {
+        let value = self.value.try_borrow_mut().ok()?;
+        RefMut::filter_map(value, |v| v.downcast_mut::<T>()).ok()
+    }
+
+    pub fn metatable(&self) -> Option<TableRef> {
+        self.metatable.borrow().clone()
+    }
+
+    pub fn set_metatable(&self, metatable : Option<TableRef>) {
+        *self.metatable.borrow_mut() = metatable;
+    }
+
+}
+
+// a released userdata with a __gc handler is given back to the script one last time
+impl Drop for UserData {
+    fn drop(&mut self) {
+        let (Some(queue), Some(metatable)) = (self.finalizers.upgrade(), self.metatable.get_mut().take()) else {
+            return;
+        };
+        // a metatable being modified can't be inspected, its handler is not called
+        let has_handler = metatable.try_borrow().map(|mt| !mt.get_str(MetaMethod::Gc.name()).is_nil()).unwrap_or(false);
+        if !has_handler {
+            return;
+        }
+
+        let finalized = UserData {
+            value      : RefCell::new(mem::replace(self.value.get_mut(), Box::new(()))),
+            type_id    : self.type_id,
+            metatable  : RefCell::new(Some(metatable)),
+            finalizers : Weak::new()
+        };
+        if let Ok(mut released) = queue.try_borrow_mut() {
+            released.push(Rc::new(finalized));
+        };
+    }
+}
+
+impl fmt::Debug for UserData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UserData")
+    }
+}
+
+/// calls the __gc handlers of the userdata released since the last call, errors raised by the handlers are ignored
+pub fn run_finalizers(env : &mut GlobalEnvironment) {
+    // the handlers don't see the message handler of an enclosing xpcall
+    env.error_handlers.push(Value::Nil);
+    loop {
+        let released = mem::take(&mut *env.finalizers.borrow_mut());
+        if released.is_empty() {
+            break;
+        }
+        for userdata in released {
+            let handler = userdata.metatable().map(|mt| mt.borrow().get_str(MetaMethod::Gc.name())).unwrap_or_default();
+            let _ = call_value(env, handler, vec![Value::UserData(userdata)]);
+        }
+    }
+    env.error_handlers.pop();
+}
@@ -1,5 +1,14 @@
+pub mod binding;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
 pub mod config;
 
+pub mod conversion;
+
+pub mod lua;
+
 pub mod structure {
 
     pub mod function;
@@ -10,15 +19,33 @@ pub mod structure {
 }
 
 pub mod decompile {
+    #[allow(clippy::module_inception)]
     pub mod decompile;
     mod metadata;
 }
 
 pub mod interpreter {
     mod call_frame;
-    mod global_environment;
+    mod call_stack;
+    mod coroutine_library;
+    mod debug_info;
+    pub(crate) mod global_environment;
+    #[allow(clippy::module_inception)]
     pub mod interpreter;
+    mod io_library;
+    pub mod limits;
+    pub(crate) mod math_library;
+    pub(crate) mod metatable;
+    mod pattern;
+    mod printf;
     pub mod object;
-    mod runtime_library;
+    pub mod output;
+    mod os_library;
+    pub(crate) mod runtime_library;
+    pub mod sandbox;
+    mod string_library;
+    pub mod table;
+    mod table_library;
+    pub mod userdata;
 }
 
@@ -0,0 +1,347 @@
+use crate::binding::{LuaMethods, UserDataRegistry};
+use crate::conversion::{FromLuaMulti, IntoLuaMulti, Values};
+use crate::decompile::decompile::{decompile_bytes, DecompileError};
+use crate::interpreter::interpreter::{call_value, InterpreterError};
+use crate::interpreter::global_environment::GlobalEnvironment;
+use crate::interpreter::limits::{Budget, Limit, Limits, LimitsError};
+use crate::interpreter::math_library::Random;
+use crate::interpreter::object::{Closure, LuaStr, TableRef, Value};
+use crate::interpreter::runtime_library::RuntimeClosure;
+use crate::interpreter::sandbox::{self, Capabilities, SandboxProfile};
+use crate::interpreter::userdata::run_finalizers;
+use std::any::TypeId;
+use std::io::Write;
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+
+/// Errors returned to the host by a Lua state
+#[derive(thiserror::Error, Debug)]
+pub enum LuaError {
+    #[error("{decompile_error}")]
+    LoadError {
+        #[from]
+        decompile_error : DecompileError
+    },
+    #[error("{interpreter_error}")]
+    RuntimeError {
+        #[from]
+        interpreter_error : InterpreterError
+    }
+}
+
+impl LuaError {
+
+    /// error raised by a host function, the message is prefixed with the position of the lua function calling it
+    pub fn runtime(message : impl Into<String>) -> Self {
+        InterpreterError::HostError { message: message.into() }.into()
+    }
+
+    /// returns the error object raised by the script, or the message of the error
+    pub fn to_value(&self) -> Value {
+        match self {
+            LuaError::LoadError { decompile_error } => { Value::from_string(&decompile_error.to_string()) }
+            LuaError::RuntimeError { interpreter_error } => { interpreter_error.to_value() }
+        }
+    }
+
+    /// returns the resource whose limit the script exceeded
+    pub fn limit(&self) -> Option<Limit> {
+        match self {
+            LuaError::RuntimeError { interpreter_error: InterpreterError::LimitError { limit } } => { Some(*limit) }
+            _ => { None }
+        }
+    }
+
+}
+
+// errors of host functions are raised in the script
+impl From<LuaError> for InterpreterError {
+    fn from(err : LuaError) -> Self {
+        match err {
+            LuaError::LoadError { decompile_error } => { InterpreterError::HostError { message: decompile_error.to_string() } }
+            LuaError::RuntimeError { interpreter_error } => { interpreter_error }
+        }
+    }
+}
+
+/** Access to the state of the interpreter, given to the host functions while they run
+ * a host function can read and write the global variables and call lua functions through it
+ */
+pub struct Context<'a> {
+    env : &'a mut GlobalEnvironment
+}
+
+impl Context<'_> {
+
+    /* calls a function value with the given arguments and returns its results
+     * errors are propagated to the enclosing protected call of the script,
+     * a host function returning them raises them again
+     */
+    pub fn call(&mut self, func : Value, args : Vec<Value>) -> Result<Vec<Value>, LuaError> {
+        Ok(call_value(self.env, func, args)?)
+    }
+
+    /// returns the value of a global variable, undefined variables are nil
+    pub fn get_global(&self, name : &str) -> Value {
+        self.env.get_global(&LuaStr::from(name.as_bytes()))
+    }
+
+    pub fn set_global(&mut self, name : &str, value : Value) {
+        self.env.insert_global(LuaStr::from(name.as_bytes()), value);
+    }
+
+    /// returns the table of the global variables
+    pub fn globals(&self) -> TableRef {
+        self.env.globals()
+    }
+
+    /// wraps a host value in a userdata, it gets the metatable registered for its type
+    pub fn create_userdata<T : 'static>(&self, value : T) -> Value {
+        Value::UserData(self.env.new_userdata(value))
+    }
+
+    /// wraps a host closure in a function value, the closure receives the arguments of the call and returns its results
+    pub fn create_function<F>(&self, func : F) -> Value
+    where F : Fn(&mut Context, Vec<Value>) -> Result<Vec<Value>, LuaError> + 'static {
+        host_function(func)
+    }
+
+    /// wraps a host closure that changes its state, a call made while the closure is running raises an error
+    pub fn create_function_mut<F>(&self, func : F) -> Value
+    where F : FnMut(&mut Context, Vec<Value>) -> Result<Vec<Value>, LuaError> + 'static {
+        let func = RefCell::new(func);
+        self.create_function(move |context, args| {
+            let mut func = func.try_borrow_mut().map_err(|_| InterpreterError::RecursiveHostCallError)?;
+            (*func)(context, args)
+        })
+    }
+
+    /* wraps a host closure taking and returning host values, the arguments are converted before the call and the results after it
+     * an argument that can't be converted raises an error naming its position and the function
+     */
+    pub fn create_typed_function<A, R, F>(&self, func : F) -> Value
+    where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, A) -> Result<R, LuaError> + 'static {
+        self.create_function(move |context, args| {
+            let mut values = Values::new(args);
+            let args = A::from_lua_multi(&mut values, context).map_err(|err| values.argument_error(err))?;
+            func(context, args)?.into_lua_multi(context)
+        })
+    }
+
+    /// wraps a host closure that changes its state and takes and returns host values, see create_typed_function
+    pub fn create_typed_function_mut<A, R, F>(&self, func : F) -> Value
+    where A : FromLuaMulti, R : IntoLuaMulti, F : FnMut(&mut Context, A) -> Result<R, LuaError> + 'static {
+        let func = RefCell::new(func);
+        self.create_typed_function(move |context, args| {
+            let mut func = func.try_borrow_mut().map_err(|_| InterpreterError::RecursiveHostCallError)?;
+            (*func)(context, args)
+        })
+    }
+
+}
+
+/// wraps a host closure in a function value, the closure is given a context on the state calling it
+pub(crate) fn host_function<F>(func : F) -> Value
+where F : Fn(&mut Context, Vec<Value>) -> Result<Vec<Value>, LuaError> + 'static {
+    RuntimeClosure::new_value(move |frame, returned_values, env| {
+        let args = (0..frame.len()).map(|i| frame.load(i)).collect();
+        returned_values.extend(func(&mut Context { env }, args)?);
+        Ok(())
+    })
+}
+
+/** Interpreter state embedded in a host application
+ * the global variables persist between the chunks it runs and the calls the host makes,
+ * errors of the scripts are returned to the host as by pcall
+ */
+pub struct Lua {
+    pub(crate) env : GlobalEnvironment
+}
+
+impl Lua {
+
+    /// creates a state with the whole standard library
+    pub fn new() -> Self {
+        Lua::with_capabilities(Capabilities::all())
+    }
+
+    /// creates a state whose io and os libraries only contain the functions allowed by the capabilities
+    pub fn with_capabilities(capabilities : Capabilities) -> Self {
+        Lua { env: GlobalEnvironment::new(&capabilities) }
+    }
+
+    /// returns the context through which host functions access the state
+    pub fn context(&mut self) -> Context<'_> {
+        Context { env: &mut self.env }
+    }
+
+    /// restarts the sequence of math.random from the given seed
+    pub fn set_random_seed(&mut self, seed : u64) {
+        self.env.random = Random::new(seed);
+    }
+
+    /* limits the resources used by each call of the host, as exec and call
+     * fails if there is a memory limit and CountingAllocator is not the global allocator, the previous limits are kept
+     */
+    pub fn set_limits(&mut self, limits : Limits) -> Result<(), LimitsError> {
+        self.env.budget = Budget::new(limits)?;
+        Ok(())
+    }
+
+    /// sends what print, io.write and io.stdout write to the writer, the standard output by default
+    pub fn set_output(&mut self, writer : impl Write + 'static) {
+        self.env.output.redirect(writer);
+    }
+
+    /// sends what io.stderr writes to the writer, the standard error by default
+    pub fn set_error_output(&mut self, writer : impl Write + 'static) {
+        self.env.error_output.redirect(writer);
+    }
+
+    /// decodes a chunk compiled by luac 5.1 and returns its main function, its global variables are the ones of the state
+    pub fn load(&self, bytecode : &[u8]) -> Result<Value, LuaError> {
+        let main = decompile_bytes(bytecode.to_vec())?;
+        Ok(Value::LuaFunction(Rc::new(Closure::new(Rc::new(main), self.env.globals()))))
+    }
+
+    /// loads a compiled chunk and runs it, returns the values returned by the chunk
+    pub fn exec(&mut self, bytecode : &[u8]) -> Result<Vec<Value>, LuaError> {
+        let main = self.load(bytecode)?;
+        self.call(main, Vec::new())
+    }
+
+    /* creates the environment of a sandboxed chunk from the profile, each call gives a new one
+     * getfenv called by a function of the sandbox returns it instead of the global environment
+     */
+    pub fn create_sandbox(&mut self, profile : &SandboxProfile) -> TableRef {
+        sandbox::new_environment(&mut self.env, profile)
+    }
+
+    /// decodes a compiled chunk and returns its main function, its global variables are the ones of the table
+    pub fn load_in(&self, bytecode : &[u8], env : TableRef) -> Result<Value, LuaError> {
+        let main = decompile_bytes(bytecode.to_vec())?;
+        Ok(Value::LuaFunction(Rc::new(Closure::new(Rc::new(main), env))))
+    }
+
+    /// runs a compiled chunk in a new sandbox created from the profile, returns the values returned by the chunk
+    pub fn exec_sandboxed(&mut self, bytecode : &[u8], profile : &SandboxProfile) -> Result<Vec<Value>, LuaError> {
+        let env = self.create_sandbox(profile);
+        let main = self.load_in(bytecode, env)?;
+        self.call(main, Vec::new())
+    }
+
+    /// calls a function value with the given arguments and returns its results
+    pub fn call(&mut self, func : Value, args : Vec<Value>) -> Result<Vec<Value>, LuaError> {
+        self.env.budget.start();
+        // the call is protected, no message handler sees the errors
+        self.env.error_handlers.push(Value::Nil);
+        let res = self.context().call(func, args);
+        self.env.error_handlers.pop();
+        run_finalizers(&mut self.env);
+        res
+    }
+
+    /// returns the value of a global variable, undefined variables are nil
+    pub fn get_global(&self, name : &str) -> Value {
+        self.env.get_global(&LuaStr::from(name.as_bytes()))
+    }
+
+    pub fn set_global(&mut self, name : &str, value : Value) {
+        self.env.insert_global(LuaStr::from(name.as_bytes()), value);
+    }
+
+    /// returns the table of the global variables
+    pub fn globals(&self) -> TableRef {
+        self.env.globals()
+    }
+
+    /// wraps a host value in a userdata, see Context::create_userdata
+    pub fn create_userdata<T : 'static>(&mut self, value : T) -> Value {
+        self.context().create_userdata(value)
+    }
+
+    /* sets the metatable of the userdata of type T created afterwards,
+     * its __index field gives their methods, __gc is called with a userdata once the scripts and the host released it,
+     * which never happens to a userdata in a reference cycle, see UserData
+     */
+    pub fn set_userdata_metatable<T : 'static>(&mut self, metatable : TableRef) {
+        self.env.userdata_metatables.insert(TypeId::of::<T>(), metatable);
+    }
+
+    /* gives the scripts the fields and methods of a host type bound with #[derive(LuaUserData)] and #[lua_methods]
+     * they are found in the metatable of its userdata, its associated functions are in a global table named after the type
+     */
+    pub fn register_userdata<T : LuaMethods>(&mut self) {
+        let mut registry = UserDataRegistry::new();
+        T::add_fields(&mut registry);
+        T::add_methods(&mut registry);
+        let (metatable, functions) = registry.build();
+        self.set_userdata_metatable::<T>(metatable);
+        if !functions.borrow().is_empty() {
+            self.set_global(T::TYPE_NAME, Value::Table(functions));
+        }
+    }
+
+    /// wraps a host closure in a function value, see Context::create_function
+    pub fn create_function<F>(&mut self, func : F) -> Value
+    where F : Fn(&mut Context, Vec<Value>) -> Result<Vec<Value>, LuaError> + 'static {
+        self.context().create_function(func)
+    }
+
+    /// wraps a host closure that changes its state, see Context::create_function_mut
+    pub fn create_function_mut<F>(&mut self, func : F) -> Value
+    where F : FnMut(&mut Context, Vec<Value>) -> Result<Vec<Value>, LuaError> + 'static {
+        self.context().create_function_mut(func)
+    }
+
+    /// wraps a host closure taking and returning host values, see Context::create_typed_function
+    pub fn create_typed_function<A, R, F>(&mut self, func : F) -> Value
+    where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, A) -> Result<R, LuaError> + 'static {
+        self.context().create_typed_function(func)
+    }
+
+    /// defines a global function implemented by the host
+    pub fn register<F>(&mut self, name : &str, func : F)
+    where F : Fn(&mut Context, Vec<Value>) -> Result<Vec<Value>, LuaError> + 'static {
+        let func = self.create_function(func);
+        self.set_global(name, func);
+    }
+
+    /// defines a global function implemented by a host closure that changes its state
+    pub fn register_mut<F>(&mut self, name : &str, func : F)
+    where F : FnMut(&mut Context, Vec<Value>) -> Result<Vec<Value>, LuaError> + 'static {
+        let func = self.create_function_mut(func);
+        self.set_global(name, func);
+    }
+
+    /// defines a global function implemented by a host closure taking and returning host values
+    pub fn register_typed<A, R, F>(&mut self, name : &str, func : F)
+    where A : FromLuaMulti, R : IntoLuaMulti, F : Fn(&mut Context, A) -> Result<R, LuaError> + 'static {
+        let func = self.create_typed_function(func);
+        self.set_global(name, func);
+    }
+
+    /// defines a global function implemented by a host closure that changes its state and takes and returns host values
+    pub fn register_typed_mut<A, R, F>(&mut self, name : &str, func : F)
+    where A : FromLuaMulti, R : IntoLuaMulti, F : FnMut(&mut Context, A) -> Result<R, LuaError> + 'static {
+        let func = self.context().create_typed_function_mut(func);
+        self.set_global(name, func);
+    }
+
+}
+
+// the userdata still referenced by the global variables are finalized with the state
+impl Drop for Lua {
+    fn drop(&mut self) {
+        let globals = mem::take(&mut *self.env.globals().borrow_mut());
+        drop(globals);
+        run_finalizers(&mut self.env);
+    }
+}
+
+impl Default for Lua {
+    fn default() -> Self {
+        Lua::new()
+    }
+}
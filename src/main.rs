@@ -1,31 +1,47 @@
 use luaca::config::Vmconfig;
 use luaca::decompile::decompile;
 use luaca::interpreter::interpreter::eval_program;
+use luaca::interpreter::limits::CountingAllocator;
+use luaca::interpreter::output::Output;
+use luaca::interpreter::sandbox::Capabilities;
 
 use std::env;
+use std::io::Write;
 use std::process;
 
+// measures the memory used by the script for its limit
+#[global_allocator]
+static ALLOCATOR : CountingAllocator = CountingAllocator;
+
 // One function that parse args and give back flags with the right values
 
 fn main() {
     
     let args: Vec<String> = env::args().collect();
 
+    // the script and the errors reported here share the error output
+    let output = Output::stdout();
+    let mut error_output = Output::stderr();
+
     let vmconfig = Vmconfig::build(args).unwrap_or_else(
         |err|{
-            println!("Problem parsing command line: {err}");
+            let _ = writeln!(error_output, "Problem parsing command line: {err}");
             process::exit(1);
         });
 
+    let seed = vmconfig.get_seed();
+    let limits = vmconfig.get_limits();
+    let capabilities = if vmconfig.get_sandbox() { Capabilities::none() } else { Capabilities::all() };
+
     let main = decompile::decompile(vmconfig).unwrap_or_else(
         |err|{
-            println!("Problem parsing file: {err}");
+            let _ = writeln!(error_output, "Problem parsing file: {err}");
             process::exit(1);
         });
 
-    eval_program(main).unwrap_or_else(
+    eval_program(main, seed, capabilities, limits, output, error_output.clone()).unwrap_or_else(
         |err|{
-            println!("Problem interpreting bytecode: {err}");
+            let _ = writeln!(error_output, "Problem interpreting bytecode: {err}");
             process::exit(1);
         }
     );
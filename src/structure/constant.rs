@@ -1,5 +1,5 @@
 use std::fmt;
-use crate::interpreter::object::Value;
+use crate::interpreter::object::{LuaStr, Value};
 
 // TODO Retirer cette enum directement utilise value
 #[derive(Debug, Clone)]
@@ -7,7 +7,7 @@ pub enum Constant {
     Null,
     Boolean(bool),
     Number(f64),
-    String(String)
+    String(LuaStr)
 }
 
 impl fmt::Display for Constant {
@@ -17,7 +17,7 @@ impl fmt::Display for Constant {
             Constant::Null               => { Ok(()) }
             Constant::Boolean(b)  => { write!(f, "Boolean: {}", b) }
             Constant::Number(n)    => { write!(f, "Number: {}", n) }
-            Constant::String(s) => { write!(f, "String: {}", s) }
+            Constant::String(s) => { write!(f, "String: {}", String::from_utf8_lossy(s)) }
         }
     }
 
@@ -26,20 +26,20 @@ impl fmt::Display for Constant {
 
 impl Constant {
 
-    pub fn as_value <'guard>(&'guard self) -> Value<'guard> {
+    pub fn as_value(&self) -> Value {
         match self {
             Constant::Null => { Value::Nil },
             Constant::Boolean(b) => { Value::Boolean(*b) },
             Constant::Number(n) => { Value::Number(*n) },
             Constant::String(s) => { 
-                Value::LuaString(s)}
+                Value::LuaString(s.clone())}
         }
     }  
 
-    pub fn get_string(&self) -> String {
+    pub fn get_string(&self) -> Option<LuaStr> {
         match self {
-            Constant::String(s) => { String::from(s) }
-            _ => panic!("should not happen")
+            Constant::String(s) => { Some(s.clone()) }
+            _ => { None }
         }
     }
 
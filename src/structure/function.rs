@@ -3,6 +3,8 @@ use crate::structure::constant::Constant;
 use crate::structure::instruction::Instruction;
 
 use std::fmt;
+use std::rc::Rc;
+
 #[derive(Debug)]
 pub struct Function{
     pub name          : String,
@@ -14,14 +16,10 @@ pub struct Function{
     pub stack         : u8,
     pub instr_list    : Vec<Instruction>,
     pub const_list    : Vec<Constant>,
-    pub func_list     : Vec<Function>,
+    pub func_list     : Vec<Rc<Function>>,
     pub lines_list    : Vec<u64>,
     pub local_list    : Vec<LocalVariable>,
-    pub upvalues_list : Vec<String>,
-    // To manage the upvalues of each function We need a way to identify
-    // each function uniquely, the name is not enough as compilers
-    // don't always 
-    pub identifier    : usize
+    pub upvalues_list : Vec<String>
 }
 
 impl fmt::Display for Function {
@@ -32,28 +30,51 @@ impl fmt::Display for Function {
 
 }
 
+// flags of the vargs field
+const VARARG_ISVARARG : u8 = 2;
+const VARARG_NEEDSARG : u8 = 4;
+
 impl Function {
 
+    /// the function accepts a variable number of arguments
+    pub fn is_vararg(&self) -> bool {
+        self.vargs & VARARG_ISVARARG != 0
+    }
+
+    /// the extra arguments are stored in the arg table (compatibility with lua 5.0)
+    pub fn needs_arg(&self) -> bool {
+        self.vargs & VARARG_NEEDSARG != 0
+    }
+
+    /// returns the name of the local variable stored in the given register while the instruction at index pc is evaluated
+    pub fn local_name(&self, register : usize, pc : usize) -> Option<&str> {
+        // the active variables are stored in the registers in the order of their declaration
+        self.local_list.iter()
+            .filter(|var| var.is_active(pc))
+            .nth(register)
+            .map(|var| var.identifier())
+    }
+
     fn display_function(&self, f: &mut fmt::Formatter<'_>, tabulation : usize) -> fmt::Result {
 
         let tabs = "\t".repeat(tabulation);
 
-        write!(f, "{tabs}Function {}: {{\n", self.name)?;
-        write!(f, "{tabs}\tFirst Line: {}\n", self.first_line)?;
-        write!(f, "{tabs}\tLast Line : {}\n", self.last_line)?;
-        write!(f, "{tabs}\tUpValues  : {}\n", self.up_values)?;
-        write!(f, "{tabs}\tArgs      : {}\n", self.args)?;
-        write!(f, "{tabs}\tVargs     : {}\n", self.vargs)?;
-        write!(f, "{tabs}\tStack     : {}\n", self.stack)?;
+        writeln!(f, "{tabs}Function {}: {{", self.name)?;
+        writeln!(f, "{tabs}\tFirst Line: {}", self.first_line)?;
+        writeln!(f, "{tabs}\tLast Line : {}", self.last_line)?;
+        writeln!(f, "{tabs}\tUpValues  : {}", self.up_values)?;
+        writeln!(f, "{tabs}\tArgs      : {}", self.args)?;
+        writeln!(f, "{tabs}\tVargs     : {}", self.vargs)?;
+        writeln!(f, "{tabs}\tStack     : {}", self.stack)?;
 
-        write!(f, "{tabs}\tInstructions:\n")?;
+        writeln!(f, "{tabs}\tInstructions:")?;
         for instr in &self.instr_list {
-            write!(f, "{tabs}\t\t{}\n", instr)?;
+            writeln!(f, "{tabs}\t\t{}", instr)?;
         }
 
-        write!(f, "{tabs}\tConstants:\n")?;
+        writeln!(f, "{tabs}\tConstants:")?;
         for cst in &self.const_list {
-            write!(f, "{tabs}\t\t{}\n", cst)?;
+            writeln!(f, "{tabs}\t\t{}", cst)?;
         }    
 
         for func in &self.func_list {
@@ -64,32 +85,18 @@ impl Function {
         for line in &self.lines_list {
             write!(f, "{}, ", line)?;
         }
-        write!(f, "]\n")?;
+        writeln!(f, "]")?;
 
-        write!(f, "{tabs}\tLocal Variables:\n")?;
+        writeln!(f, "{tabs}\tLocal Variables:")?;
         for vars in &self.local_list {
-            write!(f, "{tabs}\t\t{}\n", vars)?;
+            writeln!(f, "{tabs}\t\t{}", vars)?;
         }
 
-        write!(f, "{tabs}\tUpvalues:\n")?;
+        writeln!(f, "{tabs}\tUpvalues:")?;
         for val in &self.upvalues_list {
-            write!(f, "{tabs}\t\t{}\n", val)?;
+            writeln!(f, "{tabs}\t\t{}", val)?;
         }
 
-        write!(f, "{tabs}}}\n")
+        writeln!(f, "{tabs}}}")
     }
-
-    fn assign_id(&mut self, mut id : usize) -> usize {
-
-        self.identifier = id;
-        for func in &mut self.func_list {
-            id = func.assign_id(id + 1)
-        }
-
-        id
-    }
-
-    pub fn assign_upval_id(&mut self) -> usize {
-        self.assign_id(0)
-    }
-}
\ No newline at end of file
+}
@@ -56,7 +56,9 @@ pub enum Instruction {
   SetList(usize, usize, usize),
   Close(usize, usize, usize),
   Closure(usize, usize),
-  VarArg(usize, usize, usize)
+  VarArg(usize, usize, usize),
+  // raw integer following a SetList whose C is 0, the block number of the SetList
+  ExtraArg(usize)
 }
 
 impl Instruction {
@@ -97,7 +99,7 @@ impl Instruction {
             34 => { Ok(Instruction::SetList(a, b, c)) }
             35 => { Ok(Instruction::Close(a, b, c)) }
             37 => { Ok(Instruction::VarArg(a, b, c)) }
-            _ => { return Err(InstructionError::NotABCError{ instr_code: opcode }) }
+            _ => { Err(InstructionError::NotABCError{ instr_code: opcode }) }
         
         }
     }
@@ -112,7 +114,7 @@ impl Instruction {
             5  => { Ok(Instruction::GetGlobal(a, b)) }
             7  => { Ok(Instruction::SetGlobal(a, b)) }
             36 => { Ok(Instruction::Closure(a, b)) }
-            _  => { return Err(InstructionError::NotABxError{ instr_code: opcode }) }
+            _  => { Err(InstructionError::NotABxError{ instr_code: opcode }) }
         }
 
     }
@@ -125,7 +127,7 @@ impl Instruction {
             22 => { Ok(Instruction::Jmp(a, b)) }
             31 => { Ok(Instruction::ForLoop(a, b)) }
             32 => { Ok(Instruction::ForPrep(a, b)) }
-            _  => { return Err(InstructionError::NotAsBError{ instr_code: opcode }) }
+            _  => { Err(InstructionError::NotAsBError{ instr_code: opcode }) }
         } 
 
     }
@@ -176,6 +178,7 @@ impl fmt::Display for Instruction {
             Instruction::Close(a, b, c)    => { write!(f, "Close     : [A: {}, B: {}, C: {}]", a, b, c) }
             Instruction::Closure(a, b)           => { write!(f, "Closure   : [A: {}, B: {}]", a, b) }
             Instruction::VarArg(a, b, c)   => { write!(f, "VarArg    : [A: {}, B: {}, C: {}]", a, b, c) }
+            Instruction::ExtraArg(a)             => { write!(f, "ExtraArg  : [{}]", a) }
         }
 
     }
@@ -28,4 +28,13 @@ impl LocalVariable {
 
     }
 
-}
\ No newline at end of file
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// the variable is in scope while the instruction at index pc is evaluated
+    pub fn is_active(&self, pc : usize) -> bool {
+        self.start_scope as usize <= pc && pc < self.end_scope as usize
+    }
+
+}
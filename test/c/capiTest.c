@@ -0,0 +1,347 @@
+/* checks the C API against chunks compiled by luac 5.1, their source is given above them
+ * built and run by test/capi.rs
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "lua.h"
+#include "lauxlib.h"
+#include "lualib.h"
+
+/* return add(1, 2), greet("world") */
+static const unsigned char calls_chunk[] = {
+    0x1b, 0x4c, 0x75, 0x61, 0x51, 0x00, 0x01, 0x04, 0x08, 0x04, 0x08, 0x00, 0x0a, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x3d, 0x63, 0x61, 0x70, 0x69, 0x54, 0x65, 0x73, 0x74, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x14, 0x08, 0x00, 0x00, 0x00, 0x05, 0x00,
+    0x00, 0x00, 0x41, 0x40, 0x00, 0x00, 0x81, 0x80, 0x00, 0x00, 0x1c, 0x80, 0x80, 0x01, 0x45, 0xc0,
+    0x00, 0x00, 0x81, 0x00, 0x01, 0x00, 0x5c, 0x00, 0x00, 0x01, 0x1e, 0x00, 0x00, 0x00, 0x05, 0x00,
+    0x00, 0x00, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x61, 0x64, 0x64, 0x00, 0x03,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x40, 0x04, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x67, 0x72, 0x65, 0x65, 0x74, 0x00,
+    0x04, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x77, 0x6f, 0x72, 0x6c, 0x64, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+};
+
+/* counter() counter() return counter() */
+static const unsigned char counter_chunk[] = {
+    0x1b, 0x4c, 0x75, 0x61, 0x51, 0x00, 0x01, 0x04, 0x08, 0x04, 0x08, 0x00, 0x0a, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x3d, 0x63, 0x61, 0x70, 0x69, 0x54, 0x65, 0x73, 0x74, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x14, 0x07, 0x00, 0x00, 0x00, 0x05, 0x00,
+    0x00, 0x00, 0x1c, 0x40, 0x80, 0x00, 0x05, 0x00, 0x00, 0x00, 0x1c, 0x40, 0x80, 0x00, 0x05, 0x00,
+    0x00, 0x00, 0x1d, 0x00, 0x80, 0x00, 0x1e, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x04, 0x08,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x63, 0x6f, 0x75, 0x6e, 0x74, 0x65, 0x72, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+};
+
+/* fail("oops") */
+static const unsigned char fail_chunk[] = {
+    0x1b, 0x4c, 0x75, 0x61, 0x51, 0x00, 0x01, 0x04, 0x08, 0x04, 0x08, 0x00, 0x0a, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x3d, 0x63, 0x61, 0x70, 0x69, 0x54, 0x65, 0x73, 0x74, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x14, 0x04, 0x00, 0x00, 0x00, 0x05, 0x00,
+    0x00, 0x00, 0x41, 0x40, 0x00, 0x00, 0x1c, 0x40, 0x00, 0x01, 0x1e, 0x00, 0x80, 0x00, 0x02, 0x00,
+    0x00, 0x00, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x61, 0x69, 0x6c, 0x00,
+    0x04, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x6f, 0x6f, 0x70, 0x73, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00,
+    0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+};
+
+/* return apply(function (v) return v * 2 end, 21) */
+static const unsigned char apply_chunk[] = {
+    0x1b, 0x4c, 0x75, 0x61, 0x51, 0x00, 0x01, 0x04, 0x08, 0x04, 0x08, 0x00, 0x0a, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x3d, 0x63, 0x61, 0x70, 0x69, 0x54, 0x65, 0x73, 0x74, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x14, 0x05, 0x00, 0x00, 0x00, 0x05, 0x00,
+    0x00, 0x00, 0x64, 0x00, 0x00, 0x00, 0x81, 0x40, 0x00, 0x00, 0x1d, 0x00, 0x80, 0x01, 0x1e, 0x00,
+    0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x04, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x61,
+    0x70, 0x70, 0x6c, 0x79, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x35, 0x40, 0x01, 0x00,
+    0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3d, 0x63, 0x61, 0x70, 0x69, 0x54,
+    0x65, 0x73, 0x74, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x14,
+    0x03, 0x00, 0x00, 0x00, 0x4e, 0x00, 0x40, 0x00, 0x5e, 0x00, 0x00, 0x01, 0x1e, 0x00, 0x80, 0x00,
+    0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00,
+    0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+    0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+};
+
+/* return {a = 1, b = 2}, #list */
+static const unsigned char tables_chunk[] = {
+    0x1b, 0x4c, 0x75, 0x61, 0x51, 0x00, 0x01, 0x04, 0x08, 0x04, 0x08, 0x00, 0x0a, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x3d, 0x63, 0x61, 0x70, 0x69, 0x54, 0x65, 0x73, 0x74, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x14, 0x06, 0x00, 0x00, 0x00, 0x0a, 0x80,
+    0x00, 0x00, 0x09, 0x40, 0x40, 0x80, 0x09, 0xc0, 0x40, 0x81, 0x45, 0x00, 0x01, 0x00, 0x54, 0x00,
+    0x80, 0x00, 0x1e, 0x00, 0x80, 0x01, 0x05, 0x00, 0x00, 0x00, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x61, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, 0x04, 0x02,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x62, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x40, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x6c, 0x69, 0x73, 0x74, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+};
+
+/* local p = point(5) return type(p), getx(p) */
+static const unsigned char userdata_chunk[] = {
+    0x1b, 0x4c, 0x75, 0x61, 0x51, 0x00, 0x01, 0x04, 0x08, 0x04, 0x08, 0x00, 0x0a, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x3d, 0x63, 0x61, 0x70, 0x69, 0x54, 0x65, 0x73, 0x74, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x14, 0x0a, 0x00, 0x00, 0x00, 0x05, 0x00,
+    0x00, 0x00, 0x41, 0x40, 0x00, 0x00, 0x1c, 0x80, 0x00, 0x01, 0x45, 0x80, 0x00, 0x00, 0x80, 0x00,
+    0x00, 0x00, 0x5c, 0x80, 0x00, 0x01, 0x85, 0xc0, 0x00, 0x00, 0xc0, 0x00, 0x00, 0x00, 0x9c, 0x80,
+    0x00, 0x01, 0x5e, 0x00, 0x80, 0x01, 0x04, 0x00, 0x00, 0x00, 0x04, 0x06, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x70, 0x6f, 0x69, 0x6e, 0x74, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x14, 0x40, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x74, 0x79, 0x70, 0x65, 0x00,
+    0x04, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x67, 0x65, 0x74, 0x78, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00,
+    0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00,
+    0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+};
+
+static int failures = 0;
+
+#define CHECK(cond) do { \
+        if (!(cond)) { \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            failures++; \
+        } \
+    } while (0)
+
+#define LOAD(L, chunk) luaL_loadbuffer(L, (const char *) chunk, sizeof(chunk), #chunk)
+
+static int add(lua_State *L) {
+    lua_pushnumber(L, lua_tonumber(L, 1) + lua_tonumber(L, 2));
+    return 1;
+}
+
+static int greet(lua_State *L) {
+    lua_pushliteral(L, "hello ");
+    lua_insert(L, 1);
+    return 2;
+}
+
+static int counter(lua_State *L) {
+    lua_Integer n = lua_tointeger(L, lua_upvalueindex(1)) + 1;
+    lua_pushinteger(L, n);
+    lua_pushvalue(L, -1);
+    lua_replace(L, lua_upvalueindex(1));
+    return 1;
+}
+
+static int fail(lua_State *L) {
+    lua_pushvalue(L, 1);
+    return lua_error(L);
+}
+
+static int continued = 0;
+
+/* the error of lua_call is raised once the function returns, the function goes on with nil results */
+static int call_fail(lua_State *L) {
+    lua_getglobal(L, "fail");
+    lua_pushstring(L, "again");
+    lua_call(L, 1, 1);
+    continued = lua_isnil(L, -1);
+    return 0;
+}
+
+static int handler(lua_State *L) {
+    char message[64];
+    snprintf(message, sizeof(message), "handled: %s", lua_tostring(L, 1));
+    lua_pushstring(L, message);
+    return 1;
+}
+
+static int apply(lua_State *L) {
+    lua_pushvalue(L, 1);
+    lua_pushvalue(L, 2);
+    lua_call(L, 1, 1);
+    return 1;
+}
+
+static int finalized = 0;
+
+static int point_gc(lua_State *L) {
+    double *x = (double *) lua_touserdata(L, 1);
+    finalized = x != NULL && *x == 5;
+    return 0;
+}
+
+static int point(lua_State *L) {
+    double *x = (double *) lua_newuserdata(L, sizeof(double));
+    *x = lua_tonumber(L, 1);
+    lua_getglobal(L, "Point");
+    lua_setmetatable(L, -2);
+    return 1;
+}
+
+static int getx(lua_State *L) {
+    double *x = (double *) lua_touserdata(L, 1);
+    lua_pushnumber(L, x == NULL ? -1 : *x);
+    return 1;
+}
+
+static void test_stack(lua_State *L) {
+    size_t len;
+    lua_pushinteger(L, 42);
+    lua_pushstring(L, "text");
+    lua_pushboolean(L, 1);
+    lua_pushnil(L);
+    CHECK(lua_gettop(L) == 4);
+    CHECK(lua_type(L, 1) == LUA_TNUMBER && lua_type(L, -3) == LUA_TSTRING);
+    CHECK(lua_isboolean(L, 3) && lua_isnil(L, -1) && lua_isnone(L, 5));
+    CHECK(strcmp(lua_typename(L, lua_type(L, 2)), "string") == 0);
+    CHECK(lua_isnumber(L, 1) && lua_isstring(L, 1) && !lua_isnumber(L, 2));
+    CHECK(strcmp(lua_tolstring(L, 1, &len), "42") == 0 && len == 2);
+    CHECK(lua_type(L, 1) == LUA_TSTRING && lua_tonumber(L, 1) == 42);
+    CHECK(lua_tostring(L, 3) == NULL && lua_toboolean(L, 3) && !lua_toboolean(L, 4));
+    lua_remove(L, 1);
+    CHECK(strcmp(lua_tostring(L, 1), "text") == 0);
+    lua_settop(L, 1);
+    lua_pushlstring(L, "a\0b", 3);
+    CHECK(lua_objlen(L, -1) == 3);
+    lua_pushliteral(L, "text");
+    CHECK(lua_equal(L, 1, -1) && lua_rawequal(L, 1, -1) && !lua_equal(L, 1, 2));
+    CHECK(lua_lessthan(L, 2, 1));
+    lua_settop(L, 0);
+    CHECK(lua_gettop(L) == 0);
+}
+
+static void test_calls(lua_State *L) {
+    lua_register(L, "add", add);
+    lua_register(L, "greet", greet);
+    CHECK(LOAD(L, calls_chunk) == 0);
+    CHECK(lua_pcall(L, 0, LUA_MULTRET, 0) == 0);
+    CHECK(lua_gettop(L) == 3);
+    CHECK(lua_tonumber(L, 1) == 3);
+    CHECK(strcmp(lua_tostring(L, 2), "hello ") == 0);
+    CHECK(strcmp(lua_tostring(L, 3), "world") == 0);
+    lua_settop(L, 0);
+
+    lua_pushinteger(L, 0);
+    lua_pushcclosure(L, counter, 1);
+    lua_setglobal(L, "counter");
+    CHECK(LOAD(L, counter_chunk) == 0);
+    CHECK(lua_pcall(L, 0, 1, 0) == 0);
+    CHECK(lua_tointeger(L, -1) == 3);
+    lua_pop(L, 1);
+
+    lua_register(L, "apply", apply);
+    CHECK(LOAD(L, apply_chunk) == 0);
+    CHECK(lua_pcall(L, 0, 1, 0) == 0);
+    CHECK(lua_tonumber(L, -1) == 42);
+    lua_pop(L, 1);
+    CHECK(lua_gettop(L) == 0);
+}
+
+static void test_errors(lua_State *L) {
+    const char invalid[] = "not a chunk";
+    lua_register(L, "fail", fail);
+    CHECK(LOAD(L, fail_chunk) == 0);
+    CHECK(lua_pcall(L, 0, 0, 0) == LUA_ERRRUN);
+    CHECK(strcmp(lua_tostring(L, -1), "oops") == 0);
+    lua_pop(L, 1);
+
+    lua_pushcfunction(L, handler);
+    CHECK(LOAD(L, fail_chunk) == 0);
+    CHECK(lua_pcall(L, 0, 0, 1) == LUA_ERRRUN);
+    CHECK(strcmp(lua_tostring(L, -1), "handled: oops") == 0);
+    lua_settop(L, 0);
+
+    lua_getglobal(L, "fail");
+    lua_pushnumber(L, 7);
+    CHECK(lua_pcall(L, 1, 0, 0) == LUA_ERRRUN);
+    CHECK(lua_tonumber(L, -1) == 7);
+    lua_pop(L, 1);
+
+    lua_pushcfunction(L, call_fail);
+    CHECK(lua_pcall(L, 0, 0, 0) == LUA_ERRRUN);
+    CHECK(continued);
+    CHECK(strcmp(lua_tostring(L, -1), "again") == 0);
+    lua_pop(L, 1);
+
+    CHECK(luaL_loadbuffer(L, invalid, sizeof(invalid), "invalid") == LUA_ERRSYNTAX);
+    CHECK(lua_isstring(L, -1));
+    lua_pop(L, 1);
+    CHECK(lua_gettop(L) == 0);
+}
+
+static void test_tables(lua_State *L) {
+    int i;
+    double sum = 0;
+    lua_createtable(L, 3, 0);
+    for (i = 1; i <= 3; i++) {
+        lua_pushinteger(L, i * 10);
+        lua_rawseti(L, -2, i);
+    }
+    lua_setglobal(L, "list");
+    CHECK(LOAD(L, tables_chunk) == 0);
+    CHECK(lua_pcall(L, 0, 2, 0) == 0);
+    CHECK(lua_tointeger(L, -1) == 3);
+    lua_pop(L, 1);
+
+    lua_pushnil(L);
+    while (lua_next(L, 1)) {
+        sum += lua_tonumber(L, -1);
+        lua_pop(L, 1);
+    }
+    CHECK(sum == 3);
+    lua_getfield(L, 1, "b");
+    CHECK(lua_tonumber(L, -1) == 2);
+    lua_pop(L, 1);
+    lua_pushliteral(L, "c");
+    lua_pushliteral(L, "value");
+    lua_settable(L, 1);
+    lua_pushliteral(L, "c");
+    lua_rawget(L, 1);
+    CHECK(strcmp(lua_tostring(L, -1), "value") == 0);
+    lua_settop(L, 0);
+
+    lua_getglobal(L, "list");
+    lua_rawgeti(L, -1, 2);
+    CHECK(lua_tointeger(L, -1) == 20);
+    CHECK(lua_objlen(L, 1) == 3);
+    lua_settop(L, 0);
+}
+
+static void test_userdata(lua_State *L) {
+    int x = 0;
+    lua_newtable(L);
+    lua_pushcfunction(L, point_gc);
+    lua_setfield(L, -2, "__gc");
+    lua_setglobal(L, "Point");
+    lua_register(L, "point", point);
+    lua_register(L, "getx", getx);
+    CHECK(LOAD(L, userdata_chunk) == 0);
+    CHECK(lua_pcall(L, 0, 2, 0) == 0);
+    CHECK(strcmp(lua_tostring(L, 1), "userdata") == 0);
+    CHECK(lua_tonumber(L, 2) == 5);
+    lua_settop(L, 0);
+
+    lua_newuserdata(L, 3);
+    CHECK(lua_objlen(L, 1) == 3);
+    lua_settop(L, 0);
+
+    lua_pushlightuserdata(L, &x);
+    CHECK(lua_islightuserdata(L, 1) && lua_touserdata(L, 1) == &x);
+    CHECK(lua_getmetatable(L, 1) == 0);
+    lua_settop(L, 0);
+}
+
+int main(void) {
+    lua_State *L = luaL_newstate();
+    luaL_openlibs(L);
+    test_stack(L);
+    test_calls(L);
+    test_errors(L);
+    test_tables(L);
+    test_userdata(L);
+    lua_close(L);
+    CHECK(finalized);
+    if (failures != 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("all checks passed\n");
+    return 0;
+}
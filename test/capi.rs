@@ -0,0 +1,35 @@
+/* builds the static library of luaca-capi, links test/c/capiTest.c with it and runs the checks of the C program
+ * skipped when there is no C compiler, the compiler is the one of the variable CC, cc by default
+ */
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn c_program_passes_its_checks() {
+    let compiler = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+    if !Command::new(&compiler).arg("--version").output().is_ok_and(|out| out.status.success()) {
+        eprintln!("no C compiler {}, the test of the C API is skipped", compiler);
+        return;
+    }
+
+    let status = Command::new(env!("CARGO")).args(["build", "-p", "luaca-capi"]).status().expect("cargo");
+    assert!(status.success(), "build of luaca-capi");
+
+    // the temporary directory of the tests is in the target directory
+    let tmp = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let library = tmp.parent().expect("target directory").join("debug").join("libluaca_capi.a");
+    let program = tmp.join("capiTest");
+    let status = Command::new(&compiler)
+        .args(["-Wall", "-Wextra", "-Werror", "-std=c99", "-I", "include", "test/c/capiTest.c"])
+        .arg(&library)
+        .args(["-lm", "-lpthread", "-ldl", "-o"])
+        .arg(&program)
+        .status().expect("C compiler");
+    assert!(status.success(), "compilation of test/c/capiTest.c");
+
+    let output = Command::new(&program).output().expect("C program");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert_eq!(stdout, "all checks passed\n");
+}
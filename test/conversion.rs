@@ -0,0 +1,130 @@
+/* checks the conversions between lua values and host values, and the errors of the values that can't be converted
+ * the functions of test/lua/conversionTest.lua call typed host functions with values of the scripts
+ */
+use luaca::conversion::{FromLua, FromLuaMulti, HostFunction, IntoLua, IntoLuaMulti, Values, Variadic};
+use luaca::interpreter::object::{TableRef, Value};
+use luaca::lua::{Context, Lua};
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+
+fn load() -> Lua {
+    let mut lua = Lua::new();
+    lua.register_typed("byte", |_, n : u8| Ok(n));
+    lua.register_typed("concat", |_, (a, b) : (String, String)| Ok(a + &b));
+    lua.register_typed("adder", |_, n : f64| Ok(HostFunction::new(move |_, x : f64| Ok(x + n))));
+    lua.register_typed("describe", |ctx, ()| {
+        let counts = HashMap::from([("apples", 3), ("pears", 5)]);
+        Ok(HashMap::from([
+            ("name", Value::from_string("fruits")),
+            ("list", vec![1., 2., 3.].into_lua(ctx)?),
+            ("counts", counts.into_lua(ctx)?)
+        ]))
+    });
+    let bytecode = fs::read("test/out/conversionTest.out").expect("compiled script");
+    lua.exec(&bytecode).expect("functions of the script");
+    lua
+}
+
+/// converts the value to a T, or returns the message of the error
+fn convert<T : FromLua>(ctx : &Context, value : Value) -> Result<T, String> {
+    T::from_lua(value, ctx).map_err(|err| err.to_string())
+}
+
+fn assert_converts<T : FromLua + PartialEq + Debug>(ctx : &Context, value : Value, expected : T) {
+    assert_eq!(convert::<T>(ctx, value), Ok(expected));
+}
+
+fn assert_fails<T : FromLua + Debug>(ctx : &Context, value : Value, message : &str) {
+    assert_eq!(convert::<T>(ctx, value).expect_err("conversion error"), message);
+}
+
+#[test]
+fn integers_need_an_exact_value_in_range() {
+    let mut lua = Lua::new();
+    let ctx = lua.context();
+    assert_converts::<u8>(&ctx, Value::Number(255.), 255);
+    assert_converts::<u8>(&ctx, Value::from_string(" 12 "), 12);
+    assert_converts::<i64>(&ctx, Value::Number(-9223372036854775808.), i64::MIN);
+    assert_converts::<u64>(&ctx, Value::Number(18446744073709549568.), 18446744073709549568);
+    assert_converts::<f32>(&ctx, Value::Number(1.5), 1.5);
+
+    assert_fails::<u8>(&ctx, Value::Number(300.), "u8 expected, got 300");
+    assert_fails::<u8>(&ctx, Value::Number(-1.), "u8 expected, got -1");
+    assert_fails::<u8>(&ctx, Value::Number(1.5), "u8 expected, got 1.5");
+    assert_fails::<u8>(&ctx, Value::Number(f64::NAN), &format!("u8 expected, got {}", Value::Number(f64::NAN)));
+    assert_fails::<i32>(&ctx, Value::Number(f64::INFINITY), "i32 expected, got inf");
+    assert_fails::<i64>(&ctx, Value::Number(9223372036854775808.), "i64 expected, got 9.2233720368548e+18");
+    assert_fails::<u64>(&ctx, Value::Number(18446744073709551616.), "u64 expected, got 1.844674407371e+19");
+    assert_fails::<usize>(&ctx, Value::from_string("x"), "number expected, got string");
+    assert_fails::<f64>(&ctx, Value::Nil, "number expected, got nil");
+}
+
+#[test]
+fn host_values_round_trip() {
+    let mut lua = Lua::new();
+    let ctx = lua.context();
+    let value = vec![String::from("a"), String::from("b")].into_lua(&ctx).expect("vector");
+    assert!(matches!(value, Value::Table(_)));
+    assert_converts(&ctx, value, vec![String::from("a"), String::from("b")]);
+
+    let map = HashMap::from([(String::from("x"), 1), (String::from("y"), 2)]);
+    let value = map.clone().into_lua(&ctx).expect("map");
+    assert_converts(&ctx, value, map);
+
+    assert_converts(&ctx, Some(true).into_lua(&ctx).expect("option"), Some(true));
+    assert_converts::<Option<bool>>(&ctx, None::<bool>.into_lua(&ctx).expect("option"), None);
+    // any value is a condition
+    assert_converts(&ctx, Value::Number(0.), true);
+    assert_converts(&ctx, Value::Number(10.), String::from("10"));
+    assert_fails::<String>(&ctx, Value::Boolean(true), "string expected, got boolean");
+    assert_fails::<TableRef>(&ctx, Value::Number(1.), "table expected, got number");
+    assert_fails::<Vec<i32>>(&ctx, vec![1.5].into_lua(&ctx).expect("vector"), "i32 expected, got 1.5");
+}
+
+#[test]
+fn lists_of_values() {
+    let mut lua = Lua::new();
+    let ctx = lua.context();
+    let values = (1, "two", Variadic(vec![3., 4.])).into_lua_multi(&ctx).expect("values");
+    assert_eq!(values, vec![Value::Number(1.), Value::from_string("two"), Value::Number(3.), Value::Number(4.)]);
+
+    let mut list = Values::new(values);
+    let (one, two, rest) = <(u32, String, Variadic<f64>)>::from_lua_multi(&mut list, &ctx).expect("values");
+    assert_eq!((one, two.as_str(), rest), (1, "two", Variadic(vec![3., 4.])));
+
+    // the missing values are nil, they are reported as no value
+    let mut list = Values::new(vec![Value::Number(1.)]);
+    let err = <(u32, Option<u32>, String)>::from_lua_multi(&mut list, &ctx).expect_err("missing value");
+    assert_eq!(list.position(), 3);
+    assert_eq!(list.argument_error(err).to_string(), "bad argument #3 (string expected, got no value)");
+}
+
+#[test]
+fn typed_functions_report_bad_arguments() {
+    let mut lua = load();
+    let res = lua.call(lua.get_global("bad_byte"), vec![Value::Number(300.)]).expect("protected call");
+    assert_eq!(res[0], Value::Boolean(false));
+    assert!(res[1].to_string().ends_with("bad argument #1 to 'byte' (u8 expected, got 300)"), "{}", res[1]);
+    let res = lua.call(lua.get_global("bad_byte"), vec![Value::Number(0.5)]).expect("protected call");
+    assert!(res[1].to_string().ends_with("bad argument #1 to 'byte' (u8 expected, got 0.5)"), "{}", res[1]);
+    let res = lua.call(lua.get_global("bad_byte"), vec![Value::Number(7.)]).expect("protected call");
+    assert_eq!(res, vec![Value::Boolean(true), Value::Number(7.)]);
+
+    let res = lua.call(lua.get_global("missing_argument"), Vec::new()).expect("protected call");
+    assert!(res[1].to_string().ends_with("bad argument #2 to 'concat' (string expected, got no value)"), "{}", res[1]);
+}
+
+#[test]
+fn closures_and_tables_are_returned_to_the_scripts() {
+    let mut lua = load();
+    let res = lua.call(lua.get_global("add"), vec![Value::Number(2.), Value::Number(3.)]).expect("closure");
+    assert_eq!(res, vec![Value::from_string("function"), Value::Number(5.)]);
+
+    let res = lua.call(lua.get_global("fields"), Vec::new()).expect("table");
+    assert_eq!(res, vec![
+        Value::from_string("fruits"), Value::Number(3.), Value::Number(1.), Value::Number(3.),
+        Value::from_string("apples"), Value::from_string("pears"), Value::Number(5.)
+    ]);
+}
@@ -0,0 +1,155 @@
+/* binds a struct with #[derive(LuaUserData)] and #[lua_methods] and runs the functions of test/lua/deriveTest.lua on it
+ * checks the options of the fields and methods, the arguments taken by reference and the errors of the methods
+ */
+use luaca::binding::{lua_methods, LuaUserData};
+use luaca::conversion::Function;
+use luaca::interpreter::object::Value;
+use luaca::lua::{Context, Lua, LuaError};
+
+use std::fs;
+
+type LuaResult<T> = Result<T, LuaError>;
+
+#[derive(LuaUserData)]
+#[lua(name = "Vec2")]
+struct Vector {
+    pub x : f64,
+    pub y : f64,
+    #[lua(readonly)]
+    pub id : u32,
+    #[lua(skip)]
+    pub secret : String,
+    #[lua(name = "label")]
+    tag : String,
+    hidden : u8
+}
+
+#[lua_methods]
+impl Vector {
+
+    pub fn new(x : f64, y : f64) -> Self {
+        Vector { x, y, id: 7, secret: String::from("secret"), tag: String::from("vector"), hidden: 0 }
+    }
+
+    pub fn length(&self) -> f64 {
+        self.squared().sqrt()
+    }
+
+    pub fn scale(&mut self, k : f64) {
+        self.x *= k;
+        self.y *= k;
+    }
+
+    pub fn dot(&self, other : &Vector) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn translate(&mut self, other : &Vector) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+
+    pub fn rename(&mut self, label : &str) {
+        self.tag = String::from(label);
+    }
+
+    #[lua(result)]
+    pub fn divide(&self, k : f64) -> LuaResult<Vector> {
+        if k == 0. {
+            return Err(LuaError::runtime("division by zero"));
+        }
+        Ok(Vector::new(self.x / k, self.y / k))
+    }
+
+    pub fn normalize(&self) -> Result<Vector, LuaError> {
+        let length = self.length();
+        if length == 0. {
+            return Err(LuaError::runtime("null vector"));
+        }
+        self.divide(length)
+    }
+
+    #[lua(result)]
+    pub fn apply(&self, ctx : &mut Context, func : Function) -> LuaResult<Value> {
+        func.call(ctx, (self.x, self.y))
+    }
+
+    #[lua(meta = "__add")]
+    pub fn add(a : &Vector, b : &Vector) -> Vector {
+        Vector::new(a.x + b.x, a.y + b.y)
+    }
+
+    #[lua(meta = "__tostring")]
+    pub fn show(&self) -> String {
+        format!("({}, {})", self.x, self.y)
+    }
+
+    #[lua(skip)]
+    pub fn internal(&self) -> u8 {
+        self.hidden
+    }
+
+    fn squared(&self) -> f64 {
+        self.dot(self)
+    }
+
+}
+
+fn load() -> Lua {
+    let mut lua = Lua::new();
+    lua.register_userdata::<Vector>();
+    let bytecode = fs::read("test/out/deriveTest.out").expect("compiled script");
+    lua.exec(&bytecode).expect("functions of the script");
+    lua
+}
+
+fn run(lua : &mut Lua, name : &str) -> Vec<Value> {
+    let func = lua.get_global(name);
+    lua.call(func, Vec::new()).unwrap_or_else(|err| panic!("{}: {}", name, err))
+}
+
+fn string(s : &str) -> Value {
+    Value::from_string(s)
+}
+
+fn assert_ends_with(value : &Value, end : &str) {
+    assert!(value.to_string().ends_with(end), "{} doesn't end with {}", value, end);
+}
+
+#[test]
+fn fields_follow_their_options() {
+    let mut lua = load();
+    assert_eq!(run(&mut lua, "fields"), vec![
+        Value::Number(3.), Value::Number(4.), Value::Number(7.), string("vector"), Value::Nil, Value::Nil, Value::Nil, Value::Nil
+    ]);
+
+    let res = run(&mut lua, "set_fields");
+    assert_eq!(res[..3], [Value::Number(10.), string("moved"), Value::Number(7.)]);
+    assert_ends_with(&res[3], "cannot set field 'id' of Vec2");
+    assert_ends_with(&res[4], "invalid value for field 'x' of Vec2 (number expected, got string)");
+    // the hidden fields and methods are still there for the host
+    let vector = Vector::new(0., 0.);
+    assert_eq!((vector.internal(), vector.secret.as_str()), (0, "secret"));
+}
+
+#[test]
+fn methods_take_references_and_strings() {
+    let mut lua = load();
+    assert_eq!(run(&mut lua, "methods"), vec![Value::Number(5.), Value::Number(7.), Value::Number(8.), Value::Number(7.), string("renamed")]);
+    assert_eq!(run(&mut lua, "metamethods"), vec![string("(4, 6)"), Value::Number(4.)]);
+    assert_eq!(run(&mut lua, "apply"), vec![Value::Number(12.)]);
+}
+
+#[test]
+fn methods_raise_their_errors() {
+    let mut lua = load();
+    let res = run(&mut lua, "errors");
+    assert_eq!(res[0], Value::Number(1.5));
+    assert_ends_with(&res[1], "division by zero");
+    assert_ends_with(&res[2], "null vector");
+    assert_ends_with(&res[3], "bad argument #2 to '?' (Vec2 expected, got number)");
+
+    let res = run(&mut lua, "reenter");
+    assert_eq!(res[0], Value::Boolean(false));
+    assert_ends_with(&res[1], "Vec2 is already borrowed");
+}
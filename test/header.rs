@@ -0,0 +1,174 @@
+/* checks that the C header include/lua.h declares the functions and constants exported by src/capi.rs
+ * the header is rendered from the items of the parsed source: the functions marked #[no_mangle] with the C ABI,
+ * their doc comments, and the public constants; running the test with LUACA_BLESS=1 rewrites the header
+ */
+use std::env;
+use std::fs;
+
+use syn::spanned::Spanned;
+use syn::{Expr, FnArg, Item, Lit, Pat, ReturnType, Type, UnOp, Visibility};
+
+const SOURCE : &str = "src/capi.rs";
+const HEADER : &str = "include/lua.h";
+
+const PROLOGUE : &str = "\
+/* C API of luaca, modelled on the stack API of Lua 5.1
+ * checked against src/capi.rs by test/header.rs, rewrite it with `LUACA_BLESS=1 cargo test --test header`
+ */
+#ifndef lua_h
+#define lua_h
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern \"C\" {
+#endif
+
+typedef struct lua_State lua_State;
+
+typedef double lua_Number;
+typedef ptrdiff_t lua_Integer;
+
+typedef int (*lua_CFunction) (lua_State *L);
+typedef void * (*lua_Alloc) (void *ud, void *ptr, size_t osize, size_t nsize);
+
+";
+
+const EPILOGUE : &str = "\
+#define lua_upvalueindex(i)     (LUA_GLOBALSINDEX - (i))
+
+#define lua_pop(L, n)           lua_settop(L, -(n) - 1)
+#define lua_newtable(L)         lua_createtable(L, 0, 0)
+#define lua_pushcfunction(L, f) lua_pushcclosure(L, (f), 0)
+#define lua_register(L, n, f)   (lua_pushcfunction(L, (f)), lua_setglobal(L, (n)))
+#define lua_pushliteral(L, s)   lua_pushlstring(L, \"\" s, (sizeof(s) / sizeof(char)) - 1)
+
+#define lua_isfunction(L, n)      (lua_type(L, (n)) == LUA_TFUNCTION)
+#define lua_istable(L, n)         (lua_type(L, (n)) == LUA_TTABLE)
+#define lua_islightuserdata(L, n) (lua_type(L, (n)) == LUA_TLIGHTUSERDATA)
+#define lua_isnil(L, n)           (lua_type(L, (n)) == LUA_TNIL)
+#define lua_isboolean(L, n)       (lua_type(L, (n)) == LUA_TBOOLEAN)
+#define lua_isthread(L, n)        (lua_type(L, (n)) == LUA_TTHREAD)
+#define lua_isnone(L, n)          (lua_type(L, (n)) == LUA_TNONE)
+#define lua_isnoneornil(L, n)     (lua_type(L, (n)) <= 0)
+
+#define lua_setglobal(L, s)     lua_setfield(L, LUA_GLOBALSINDEX, (s))
+#define lua_getglobal(L, s)     lua_getfield(L, LUA_GLOBALSINDEX, (s))
+#define lua_tostring(L, i)      lua_tolstring(L, (i), NULL)
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
+";
+
+/// C type of a type of the signatures, the C API only uses a few of them
+fn c_type(ty : &Type) -> String {
+    match ty {
+        Type::Ptr(ptr) => {
+            let pointee = match (c_type(&ptr.elem).as_str(), ptr.mutability.is_some()) {
+                ("char", false) => { String::from("const char") }
+                ("void", true) => { String::from("void") }
+                (pointee @ ("lua_State" | "size_t"), true) => { String::from(pointee) }
+                (pointee, _) => { panic!("{}: no C type for a pointer to {}", SOURCE, pointee) }
+            };
+            format!("{} *", pointee)
+        }
+        Type::Path(path) => {
+            let name = path.path.segments.last().expect("name of a type").ident.to_string();
+            match name.as_str() {
+                "c_int" => { String::from("int") }
+                "c_char" => { String::from("char") }
+                "c_void" => { String::from("void") }
+                "usize" => { String::from("size_t") }
+                "lua_State" | "lua_Number" | "lua_Integer" | "lua_CFunction" | "lua_Alloc" => { name }
+                _ => { panic!("{}: no C type for {}", SOURCE, name) }
+            }
+        }
+        _ => { panic!("{}: no C type for the type of an argument", SOURCE) }
+    }
+}
+
+/// declaration of a type and a name, the pointer stars stick to the name
+fn declarator(ty : String, name : &str) -> String {
+    if ty.ends_with('*') { format!("{}{}", ty, name) } else { format!("{} {}", ty, name) }
+}
+
+/// doc comments as C comments, a block doc comment keeps its lines
+fn comments(attrs : &[syn::Attribute]) -> String {
+    let mut res = String::new();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("doc")) {
+        let text = match &attr.meta.require_name_value().expect("doc comment").value {
+            Expr::Lit(lit) => { match &lit.lit { Lit::Str(text) => { text.value() } _ => { panic!("doc comments are strings") } } }
+            _ => { panic!("doc comments are strings") }
+        };
+        if text.contains('\n') {
+            res.push_str(&format!("/*{}*/\n", text));
+        } else {
+            res.push_str(&format!("/* {} */\n", text.trim()));
+        }
+    }
+    res
+}
+
+/// value of an integer constant, possibly negative
+fn constant(expr : &Expr) -> String {
+    match expr {
+        Expr::Lit(lit) => { match &lit.lit { Lit::Int(value) => { value.base10_digits().to_string() } _ => { panic!("constants are integers") } } }
+        Expr::Unary(unary) if matches!(unary.op, UnOp::Neg(_)) => { format!("-{}", constant(&unary.expr)) }
+        _ => { panic!("constants are integers") }
+    }
+}
+
+fn render(source : &str) -> String {
+    let file = syn::parse_file(source).expect("source of the C API");
+    let mut header = String::from(PROLOGUE);
+    // line of the previous constant, the constants separated by blank lines are in separate groups
+    let mut last_constant = None;
+
+    for item in &file.items {
+        match item {
+            Item::Const(item) if matches!(item.vis, Visibility::Public(_)) => {
+                let line = item.span().start().line;
+                if last_constant.is_some_and(|last| line > last + 1) {
+                    header.push('\n');
+                }
+                last_constant = Some(line);
+                header.push_str(&format!("#define {:<20}({})\n", item.ident, constant(&item.expr)));
+            }
+            Item::Fn(item) if item.attrs.iter().any(|attr| attr.path().is_ident("no_mangle")) => {
+                assert!(item.sig.abi.is_some(), "{}: {} is exported without the C ABI", SOURCE, item.sig.ident);
+                if last_constant.take().is_some() {
+                    header.push('\n');
+                }
+                let params : Vec<String> = item.sig.inputs.iter().map(|param| {
+                    let FnArg::Typed(param) = param else { panic!("{}: exported functions have no receiver", SOURCE) };
+                    let Pat::Ident(name) = &*param.pat else { panic!("{}: arguments are named", SOURCE) };
+                    declarator(c_type(&param.ty), name.ident.to_string().trim_start_matches('_'))
+                }).collect();
+                let params = if params.is_empty() { String::from("void") } else { params.join(", ") };
+                let ret = match &item.sig.output {
+                    ReturnType::Default => { String::from("void") }
+                    ReturnType::Type(_, ty) => { c_type(ty) }
+                };
+                header.push_str(&comments(&item.attrs));
+                header.push_str(&format!("{}({});\n\n", declarator(ret, &item.sig.ident.to_string()), params));
+            }
+            _ => {}
+        }
+    }
+    header.push_str(EPILOGUE);
+    header
+}
+
+#[test]
+fn header_declares_the_api() {
+    let header = render(&fs::read_to_string(SOURCE).expect("source of the C API"));
+    if env::var_os("LUACA_BLESS").is_some() {
+        fs::write(HEADER, &header).expect("header of the C API");
+        return;
+    }
+    let expected = fs::read_to_string(HEADER).expect("header of the C API");
+    assert!(header == expected, "{} differs from the API of {}, rewrite it with LUACA_BLESS=1\n--- expected\n{}--- got\n{}", HEADER, SOURCE, expected, header);
+}
@@ -0,0 +1,95 @@
+/* runs the functions of test/lua/hostFunctionTest.lua, which call host closures registered on the state
+ * checks the arguments and results they exchange, the errors going both ways and the closures called recursively
+ */
+use luaca::interpreter::object::Value;
+use luaca::lua::{Lua, LuaError};
+
+use std::fs;
+
+fn load(lua : &mut Lua) {
+    let bytecode = fs::read("test/out/hostFunctionTest.out").expect("compiled script");
+    lua.exec(&bytecode).expect("functions of the script");
+}
+
+fn number(value : &Value) -> f64 {
+    match value {
+        Value::Number(n) => { *n }
+        _ => { panic!("{:?} is not a number", value) }
+    }
+}
+
+#[test]
+fn arguments_and_results() {
+    let mut lua = Lua::new();
+    lua.register("add", |_, args| {
+        let sum = args.iter().filter(|arg| !matches!(arg, Value::Nil)).map(number).sum();
+        Ok(vec![Value::Number(sum), Value::Number(args.len() as f64)])
+    });
+    load(&mut lua);
+    let sum_and_count = lua.get_global("sum_and_count");
+    let res = lua.call(sum_and_count.clone(), vec![Value::Number(1.), Value::Nil, Value::Number(2.)]).expect("sum");
+    assert_eq!(res, vec![Value::Number(3.), Value::Number(3.)]);
+    let res = lua.call(sum_and_count, Vec::new()).expect("empty sum");
+    assert_eq!(res, vec![Value::Number(0.), Value::Number(0.)]);
+}
+
+#[test]
+fn host_errors_are_raised_in_the_script() {
+    let mut lua = Lua::new();
+    lua.register("fail", |_, _| Err(LuaError::runtime("host failure")));
+    load(&mut lua);
+    let res = lua.call(lua.get_global("catch_host_error"), Vec::new()).expect("protected call");
+    assert_eq!(res.len(), 2);
+    assert_eq!(res[0], Value::Boolean(false));
+    assert!(matches!(&res[1], Value::LuaString(_) if res[1].to_string().ends_with("host failure")), "{:?}", res[1]);
+
+    // without a protected call of the script, the error goes back to the host
+    let err = lua.call(lua.get_global("fail"), Vec::new()).expect_err("unprotected call");
+    assert!(err.to_string().ends_with("host failure"), "{}", err);
+}
+
+#[test]
+fn context_calls_back_into_lua() {
+    let mut lua = Lua::new();
+    lua.register("apply", |context, mut args| {
+        let func = args.remove(0);
+        context.call(func, args)
+    });
+    load(&mut lua);
+    let res = lua.call(lua.get_global("call_back"), vec![Value::Number(4.)]).expect("callback");
+    assert_eq!(res, vec![Value::Number(12.), Value::Number(7.)]);
+    let res = lua.call(lua.get_global("error_through_host"), Vec::new()).expect("protected call");
+    assert_eq!(res, vec![Value::Boolean(false), Value::Number(42.)]);
+}
+
+#[test]
+fn mutable_closures_keep_their_state() {
+    let mut lua = Lua::new();
+    let mut calls = 0;
+    lua.register_mut("counter", move |_, _| {
+        calls += 1;
+        Ok(vec![Value::Number(calls as f64)])
+    });
+    load(&mut lua);
+    assert_eq!(lua.call(lua.get_global("count"), vec![Value::Number(4.)]).expect("count"), vec![Value::Number(5.)]);
+    assert_eq!(lua.call(lua.get_global("count"), vec![Value::Number(0.)]).expect("count"), vec![Value::Number(6.)]);
+}
+
+#[test]
+fn mutable_closures_cannot_be_reentered() {
+    let mut lua = Lua::new();
+    lua.register_mut("recurse", |context, args| {
+        let func = args[0].clone();
+        context.call(func, args)
+    });
+    load(&mut lua);
+    let res = lua.call(lua.get_global("reenter"), Vec::new()).expect("protected call");
+    assert_eq!(res.len(), 2);
+    assert_eq!(res[0], Value::Boolean(false));
+    assert!(matches!(&res[1], Value::LuaString(_) if res[1].to_string().ends_with("host function called recursively")), "{:?}", res[1]);
+
+    // the closure is released after the error, it can be called again
+    lua.register_mut("recurse", |_, args| Ok(args));
+    let res = lua.call(lua.get_global("reenter"), Vec::new()).expect("protected call");
+    assert_eq!(res[0], Value::Boolean(true));
+}
@@ -0,0 +1,55 @@
+/* runs the functions of test/lua/limitsTest.lua under the limits of a state
+ * the test program keeps the allocator of the system, so memory limits can't be measured
+ */
+use luaca::interpreter::limits::{Limit, Limits, LimitsError};
+use luaca::interpreter::object::Value;
+use luaca::lua::Lua;
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+fn load(limits : Limits) -> Lua {
+    let mut lua = Lua::new();
+    let bytecode = fs::read("test/out/limitsTest.out").expect("compiled script");
+    lua.exec(&bytecode).expect("functions of the script");
+    lua.set_limits(limits).expect("limits without memory");
+    lua
+}
+
+#[test]
+fn memory_limit_needs_the_counting_allocator() {
+    let mut lua = Lua::new();
+    let limits = Limits { memory: Some(1 << 20), ..Limits::default() };
+    assert!(matches!(lua.set_limits(limits), Err(LimitsError::AllocatorError)));
+}
+
+#[test]
+fn time_limit_stops_a_backtracking_pattern() {
+    let mut lua = load(Limits { time: Some(Duration::from_millis(200)), ..Limits::default() });
+    let start = Instant::now();
+    let backtrack = lua.get_global("backtrack");
+    let err = lua.call(backtrack, Vec::new()).expect_err("the pattern can't finish in time");
+    assert_eq!(err.limit(), Some(Limit::Time));
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn call_depth_counts_the_resuming_coroutines() {
+    let mut lua = load(Limits { call_depth: Some(50), ..Limits::default() });
+    let nested = lua.get_global("nested_coroutines");
+    assert_eq!(lua.call(nested.clone(), vec![Value::Number(10.)]).expect("shallow recursion"), vec![Value::Number(10.)]);
+    let err = lua.call(nested, vec![Value::Number(100.)]).expect_err("deep recursion");
+    assert!(err.to_string().contains("call depth limit exceeded"), "{}", err);
+}
+
+#[test]
+fn huge_repetitions_raise_an_error() {
+    let mut lua = load(Limits::default());
+    let repeat_string = lua.get_global("repeat_string");
+    for (s, n) in [("x", 1e18), ("multi-byte \u{e9}", 1e17), ("xy", 2e9)] {
+        let err = lua.call(repeat_string.clone(), vec![Value::from_string(s), Value::Number(n)]).expect_err("string too large");
+        assert!(err.to_string().contains("resulting string too large"), "{}", err);
+        assert_eq!(err.limit(), None);
+    }
+    assert_eq!(lua.call(repeat_string, vec![Value::from_string("ab"), Value::Number(3.)]).expect("small repetition"), vec![Value::from_string("ababab")]);
+}
@@ -0,0 +1,40 @@
+print(type(print), type(nil), type({}), type("s"), type(2), type(true))
+print(tostring(1e15), tostring(0.1), tostring(nil), tostring(10 / 2))
+print(tonumber("ff", 16), tonumber("z", 36), tonumber("  12  "), tonumber("x"), tonumber("0x10"))
+
+local t = setmetatable({}, { __index = function() return "default" end })
+print(t.x, rawget(t, "x"), rawequal(t, t), rawequal(t, {}))
+rawset(t, "x", 1)
+print(t.x)
+
+print(unpack({ 1, "a", 3 }))
+print(unpack({ 1, 2, 3 }, 2))
+print(_VERSION, _G._G == _G, getfenv() == _G)
+print(collectgarbage("count") >= 0, type(collectgarbage("count")))
+
+x = 10
+local function sandboxed()
+    return x
+end
+setfenv(sandboxed, { x = 20 })
+print(sandboxed(), x)
+
+-- unpack returns nothing for an empty range and refuses ranges larger than a stack
+print(select("#", unpack({}, 3, 1)), select("#", unpack({ 1, 2 }, 3, 1)))
+print(unpack({ 1, 2 }, -1, 3))
+print(select("#", unpack({}, 1, 5000)))
+print(pcall(unpack, {}, 1, 1e9))
+print(pcall(unpack, {}, -2^63, 2^63))
+print(pcall(unpack, {}, 1e300, -1e300))
+
+-- tonumber checks its base, other conversions give nil
+print(tonumber("10", 2), tonumber("7", 8), tonumber("8", 8), tonumber(""), tonumber("1e2"), tonumber(" 0x "))
+print(pcall(tonumber, "10", 1))
+print(pcall(tonumber, "10", 37))
+
+-- missing arguments are reported as no value
+print(pcall(unpack))
+print(pcall(setmetatable))
+print(pcall(rawget, {}))
+print(pcall(rawset, {}, nil, 1))
+print(pcall(type))
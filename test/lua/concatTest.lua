@@ -0,0 +1,27 @@
+local name = "luaca"
+print("Hello " .. name .. "!")
+print(1 .. "")
+print("pi is about " .. 3.14159265358979)
+print(2^53 .. " " .. 1e100 .. " " .. 0.1)
+
+local t = setmetatable({}, {__concat = function(a, b) return "table" end})
+print("a" .. t)
+print(t .. 1, 1 .. t)
+
+-- numbers use the %.14g format, both operands can be numbers
+print(1 .. 2, -0.5 .. "", 1/0 .. "|" .. -1/0, 123456789012345 .. "", 1e15 .. "", 2^63 .. "")
+
+-- a chain of concatenations is built in one piece
+local parts = {}
+for i = 1, 10 do parts[i] = i end
+local s = parts[1] .. parts[2] .. parts[3] .. parts[4] .. parts[5] .. parts[6] .. parts[7] .. parts[8] .. parts[9] .. parts[10]
+print(s, #s)
+local long = ""
+for i = 1, 1000 do long = long .. "x" end
+print(#long)
+
+-- the error names the type of the operand that is not a string or a number
+print(pcall(function() return "a" .. nil end))
+print(pcall(function() return {} .. "b" end))
+print(pcall(function() return "c" .. true end))
+print(pcall(function() return 1 .. "d" .. print end))
@@ -0,0 +1,34 @@
+-- functions run by test/conversion.rs, they call the typed host functions registered by the host
+
+-- the error of a call to the host function byte, which takes an u8
+function bad_byte(n)
+    local ok, err = pcall(function ()
+        return byte(n)
+    end)
+    return ok, err
+end
+
+-- the error of a call to the host function concat with a missing argument
+function missing_argument()
+    local ok, err = pcall(function ()
+        return concat("a")
+    end)
+    return ok, err
+end
+
+-- the host function adder returns a host closure adding n to its argument
+function add(n, x)
+    local f = adder(n)
+    return type(f), f(x)
+end
+
+-- the fields of the table made by the host function describe, sorted by key
+function fields()
+    local t = describe()
+    local keys = {}
+    for key in pairs(t.counts) do
+        keys[#keys + 1] = key
+    end
+    table.sort(keys)
+    return t.name, #t.list, t.list[1], t.list[3], keys[1], keys[2], t.counts[keys[2]]
+end
@@ -0,0 +1,80 @@
+local function inner(x)
+    local r = coroutine.yield(x)
+    return r + 1
+end
+
+local co = coroutine.create(function(a, b)
+    local c = inner(a + b)
+    print("resumed with", c)
+    local d, e = coroutine.yield(c * 2)
+    return d + e, "end"
+end)
+
+print(type(co), coroutine.status(co))
+print(coroutine.resume(co, 1, 2))
+print(coroutine.status(co))
+print(coroutine.resume(co, 5))
+print(coroutine.resume(co, 3, 4))
+print(coroutine.status(co))
+print(coroutine.resume(co))
+
+local failing = coroutine.create(function() error("boom") end)
+print(coroutine.resume(failing))
+print(coroutine.status(failing))
+
+local function range(n)
+    return coroutine.wrap(function()
+        for i = 1, n do
+            coroutine.yield(i)
+        end
+    end)
+end
+for i in range(3) do
+    print("range", i)
+end
+
+local tail = coroutine.wrap(function(x) return coroutine.yield(x) end)
+print(tail(1), tail(2))
+print(pcall(tail, 3))
+
+print(pcall(coroutine.yield, 1))
+print(coroutine.resume(coroutine.create(function() print(pcall(inner, 1)) end)))
+
+local outer
+outer = coroutine.create(function()
+    local nested = coroutine.create(function()
+        print(coroutine.status(coroutine.running()), coroutine.status(outer))
+    end)
+    print(coroutine.resume(nested))
+end)
+print(coroutine.running())
+print(coroutine.resume(outer))
+
+local function deep(n)
+    if n == 0 then
+        coroutine.yield("bottom")
+        return 0
+    end
+    return 1 + deep(n - 1)
+end
+local recursive = coroutine.create(deep)
+print(coroutine.resume(recursive, 10000))
+print(coroutine.resume(recursive))
+print(coroutine.resume(coroutine.create(deep), 30000))
+
+print(type(coroutine.create(print)), pcall(coroutine.create))
+print(pcall(coroutine.resume, 1))
+
+-- runtime functions can be the body of a coroutine
+local printer = coroutine.create(print)
+print(coroutine.resume(printer, "from", "print"))
+print(coroutine.status(printer), coroutine.resume(printer))
+local yielder = coroutine.create(coroutine.yield)
+print(coroutine.resume(yielder, 1, 2))
+print(coroutine.status(yielder), coroutine.resume(yielder, "back"))
+print(coroutine.status(yielder))
+print(coroutine.wrap(string.rep)("ab", 3))
+print(pcall(coroutine.wrap(error), "wrapped", 0))
+print(coroutine.resume(coroutine.create(error), { "table error" }) == false)
+print(pcall(coroutine.create, {}))
+print(pcall(coroutine.wrap))
@@ -0,0 +1,54 @@
+-- functions run by test/derive.rs on the userdata Vec2 bound with #[derive(LuaUserData)] and #[lua_methods]
+
+-- reads the fields given to the scripts, the hidden fields and methods are nil
+function fields()
+    local v = Vec2.new(3, 4)
+    return v.x, v.y, v.id, v.label, v.secret, v.hidden, v.internal, v.squared
+end
+
+-- writes the fields, returns the errors of the field that is read-only and of a value of another type
+function set_fields()
+    local v = Vec2.new(1, 2)
+    v.x = 10
+    v.label = "moved"
+    local _, readonly = pcall(function () v.id = 5 end)
+    local _, invalid = pcall(function () v.x = "far" end)
+    return v.x, v.label, v.id, readonly, invalid
+end
+
+-- calls the methods with numbers, strings and other userdata
+function methods()
+    local v, w = Vec2.new(3, 4), Vec2.new(1, 0)
+    local length = v:length()
+    v:scale(2)
+    v:translate(w)
+    v:rename("renamed")
+    return length, v.x, v.y, v:dot(w), v.label
+end
+
+-- handlers of the metatable
+function metamethods()
+    local v = Vec2.new(1, 2) + Vec2.new(3, 4)
+    return tostring(v), v.x
+end
+
+-- errors of the methods returning a Result, and of an argument that is not a Vec2
+function errors()
+    local v, zero = Vec2.new(3, 4), Vec2.new(0, 0)
+    local half = v:divide(2)
+    local _, by_zero = pcall(v.divide, v, 0)
+    local _, null = pcall(zero.normalize, zero)
+    local _, not_vector = pcall(v.dot, v, 1)
+    return half.x, by_zero, null, not_vector
+end
+
+-- the method gets the context of the call to call the function given to it
+function apply()
+    return Vec2.new(3, 4):apply(function (x, y) return x * y end)
+end
+
+-- a method changing the userdata can't run while another method reads it
+function reenter()
+    local v = Vec2.new(1, 1)
+    return pcall(v.apply, v, function () v:scale(2) end)
+end
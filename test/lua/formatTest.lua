@@ -0,0 +1,33 @@
+print(string.format("%d %5d|%-5d|%05d|%+d|% d|%.3d", 1, 42, 42, -42, 7, 7, 5))
+print(string.format("%u %o %#o %x %#X %08.3x", 3.9, 8, 8, 255, 255, 10))
+print(string.format("%c%c%c|%3c|", 76, 117, 97, 65))
+print(string.format("%e %E %.0e %12.4e", 12345.678, 0.00012, 5, -3.5))
+print(string.format("%f %.2f %10.3f|%-10.1f|%010.2f|%#.0f", 3.14159, 2.675, -1.5, 2.25, -3.14159, 2))
+print(string.format("%g %g %g %G %.3g %#g", 100000, 1000000, 0.00001, 1e-20, 3.14159, 1))
+print(string.format("%q", 'he said "hi"\\\n\r\0end'))
+print(string.format("%s|%10s|%-10s|%.2s|%s %%", "abc", "right", "left", "truncate", 12.5))
+
+print(pcall(string.format, "%d", "x"))
+print(pcall(string.format, "%d %d", 1))
+print(pcall(string.format, "%y", 1))
+print(pcall(string.format, "%------d", 1))
+print(pcall(string.format, "%123d", 1))
+print(pcall(string.format, "%d", 1e300))
+
+-- numbers are converted to strings as "%.14g" does
+print(1e15, 123456789012345, 0.1, 1/3, 2^53, -0, 1e100, 1e300 * 1e10)
+print(tostring(12.5) .. "|" .. 100 .. "|" .. 1e-5)
+
+-- special values and the limits of the conversions
+print(string.format("%f %e %g %.3f", 1/0, -1/0, 1/0, 0/0 ~= 0/0 and 1 or 0))
+print(string.format("%5.1s|%-3s|%s", "abc", "", "x\0y"))
+print(string.format("%.99f", 1):len(), string.format("%99d", 1):len())
+print(string.format("%x %X %o", 2^32, 2^31 - 1, 2^40))
+print(string.format("%q", "\1\127\255"), string.format("%q", 12))
+print(string.format("%s %s", 1e15, -0.5), pcall(string.format, "%s", true))
+print(string.format("no conversions"), string.format(""))
+print(pcall(string.format, "%100d", 1))
+print(pcall(string.format, "%.100f", 1))
+print(pcall(string.format, "%", 1))
+print(pcall(string.format, "%s"))
+print(pcall(string.format))
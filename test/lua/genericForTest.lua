@@ -0,0 +1,63 @@
+local t = {10, 20, 30, x = 1, y = 2}
+
+for k, v in pairs(t) do
+	print(k, v)
+end
+
+for i, v in ipairs(t) do
+	print(i, v)
+end
+
+-- clearing fields during a traversal is allowed
+for k in pairs(t) do
+	t[k] = nil
+end
+print(next(t))
+
+local function range(n)
+	local function step(limit, i)
+		if i < limit then
+			return i + 1
+		end
+	end
+	return step, n, 0
+end
+
+for i in range(3) do
+	print(i)
+end
+
+-- ipairs stops at the first nil, pairs visits the array part in order
+for i, v in ipairs({1, 2, nil, 4}) do
+	print(i, v)
+end
+local order = {}
+for k in pairs({"a", "b", "c", "d"}) do
+	order[#order + 1] = k
+end
+print(table.concat(order, " "))
+
+-- the loop variables are copies, the state and the control variable belong to the loop
+local visited = 0
+for i, v in ipairs({1, 2, 3}) do
+	i = i * 10
+	visited = visited + 1
+end
+print(visited)
+
+-- every result of the iterator up to the variables is assigned, break leaves the loop
+local function triples()
+	return function(_, i)
+		if i < 3 then return i + 1, i * 2, "extra" end
+	end, nil, 0
+end
+for a, b, c, d in triples() do
+	print(a, b, c, d)
+	if a == 2 then break end
+end
+
+print(next({}), next({5}), next({5}, 1))
+print(pcall(next, {}, "missing"))
+print(pcall(pairs, nil))
+print(pcall(ipairs))
+print(pcall(function() for x in 5 do end end))
@@ -0,0 +1,35 @@
+-- functions run by test/host_functions.rs, they call the functions registered by the host
+
+-- the results of the host function add, the nil arguments are counted
+function sum_and_count(...)
+    return add(...)
+end
+
+-- an error returned by a host function is caught by pcall
+function catch_host_error()
+    return pcall(fail)
+end
+
+-- the host function apply calls the callback back through its context
+function call_back(x)
+    return apply(function (a, b) return a * b, a + b end, x, 3)
+end
+
+-- an error of the callback goes through the host function up to pcall with its error object
+function error_through_host()
+    local ok, err = pcall(apply, function () error({ code = 42 }) end)
+    return ok, err.code
+end
+
+-- counter is a host closure changing its state at each call
+function count(n)
+    for i = 1, n do
+        counter()
+    end
+    return counter()
+end
+
+-- the host closure recurse calls the function given to it, here itself
+function reenter()
+    return pcall(recurse, recurse)
+end
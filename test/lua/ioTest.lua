@@ -0,0 +1,52 @@
+local name = os.tmpname()
+-- the name of the temporary file changes between runs
+local function hide(...)
+    local res = { ... }
+    for i = 1, select("#", ...) do
+        if type(res[i]) == "string" then
+            res[i] = res[i]:gsub(name:gsub("%p", "%%%0"), "TMPNAME")
+        end
+    end
+    return unpack(res, 1, select("#", ...))
+end
+
+local f = assert(io.open(name, "w"))
+print(type(f), getmetatable(f) == getmetatable(io.stdout))
+print(f:write("line one\n", 42, " ", 3.5, "\nline three\n") == f)
+print(f:close(), tostring(f))
+print(pcall(f.read, f))
+
+f = assert(io.open(name))
+print(f:read("*l"))
+print(f:read("*n", "*n"))
+print(f:read(2), f:read("*l", "*l"))
+print(f:read(0), f:read("*l"))
+print(f:seek("set", 5), f:read("*a"))
+print(f:seek("end"), f:seek())
+f:close()
+
+for line in io.lines(name) do
+    io.write("[", line, "]\n")
+end
+
+print(io.open("/nonexistent/file"))
+print(hide(io.open(name, "rw")))
+print(io.stdout:close())
+print(pcall(io.stdout.write, {}))
+print(hide(os.remove(name), os.remove(name)))
+
+-- reading and writing at the edges of a file
+f = assert(io.open(name, "w+"))
+print(f:read("*a"), f:read("*l"), f:read(0), f:read(1))
+f:write("12 0x10 -3.5e1 abc")
+print(f:seek("set"), f:read("*n", "*n", "*n", "*n"))
+print(f:read("*a"), f:read("*a"), f:seek("cur", -3), f:read(10))
+print(pcall(f.read, f, "*x"))
+print(pcall(f.seek, f, "middle"))
+print(pcall(f.write, f, {}))
+f:close()
+print(pcall(f.write, f, "x"))
+print(pcall(f.seek, f))
+print(tostring(f), pcall(io.lines, "/nonexistent/file"))
+print(pcall(io.open))
+print(os.remove(name))
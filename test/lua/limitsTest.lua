@@ -0,0 +1,19 @@
+-- functions run by test/limits.rs under the limits of a state
+
+-- the matcher tries every split of the subject between the items before failing
+function backtrack()
+    return string.find(string.rep("a", 40), string.rep("a*", 40) .. "b")
+end
+
+-- each level of the recursion runs in a new coroutine
+function nested_coroutines(n)
+    if n == 0 then
+        return 0
+    end
+    return coroutine.wrap(nested_coroutines)(n - 1) + 1
+end
+
+-- n copies of s, the size of the result is checked before it is built
+function repeat_string(s, n)
+    return string.rep(s, n)
+end
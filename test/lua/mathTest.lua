@@ -0,0 +1,40 @@
+print(math.floor(-3.5), math.ceil(3.2), math.abs(-2), math.sqrt(16))
+print(math.sin(0), math.cos(0), math.tan(1), math.asin(1), math.acos(1), math.atan(1), math.atan2(1, -1))
+print(math.exp(1), math.log(10), math.log10(1000), math.pow(2, 10), math.fmod(-7, 3))
+print(math.modf(-3.75))
+print(math.frexp(8), math.frexp(-0.1), math.ldexp(0.5, 4))
+print(math.min(3, 1, 2), math.max(3, 1, 2), math.huge, -math.huge, math.pi)
+
+-- the same seed gives the same sequence
+math.randomseed(42)
+local first = { math.random(), math.random(10), math.random(5, 7) }
+math.randomseed(42)
+local second = { math.random(), math.random(10), math.random(5, 7) }
+print(first[1] == second[1], first[2] == second[2], first[3] == second[3])
+
+for _ = 1, 100 do
+    local r = math.random(3, 5)
+    assert(r >= 3 and r <= 5 and r == math.floor(r))
+end
+
+print(pcall(math.random, 0))
+print(pcall(math.random, 3, 1))
+print(pcall(math.random, 1, 2, 3))
+
+-- wide intervals
+print(pcall(math.random, -2^63, 2^63))
+print(pcall(math.random, -1e300, 1e300))
+for _ = 1, 100 do
+    local r = math.random(-2^61, 2^61)
+    assert(r >= -2^61 and r <= 2^61)
+    r = math.random(2^53)
+    assert(r >= 1 and r <= 2^53)
+end
+print(math.random(7, 7), math.random(1), math.random(-3, -3))
+print(pcall(math.random, 1e300, -1e300))
+print(pcall(math.random, "x"))
+
+-- special values
+print(math.floor(1/0), math.ceil(-1/0), math.max(1, 0/0) ~= math.max(1, 0/0), math.fmod(1, 0) ~= math.fmod(1, 0))
+print(math.modf(1/0), math.modf(-1/0), math.frexp(0), math.abs(-0))
+print(pcall(math.floor), pcall(math.max), pcall(math.min, 1, "x"))
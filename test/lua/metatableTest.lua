@@ -0,0 +1,79 @@
+local Vector = {}
+Vector.__index = Vector
+
+function Vector.new(x, y)
+	return setmetatable({x = x, y = y}, Vector)
+end
+
+function Vector.norm2(v)
+	return v.x * v.x + v.y * v.y
+end
+
+Vector.__add = function(a, b) return Vector.new(a.x + b.x, a.y + b.y) end
+Vector.__eq = function(a, b) return a.x == b.x and a.y == b.y end
+Vector.__lt = function(a, b) return Vector.norm2(a) < Vector.norm2(b) end
+Vector.__tostring = function(v) return "vector" end
+
+local u = Vector.new(1, 2)
+local v = Vector.new(3, 4)
+local w = u + v
+print(w.x, w.y)
+print(Vector.norm2(w))
+print(u == Vector.new(1, 2), u < v, u <= v)
+print(w)
+
+local protected = setmetatable({}, {__metatable = "locked"})
+print(getmetatable(protected))
+
+local calls = setmetatable({}, {__call = function(self, a) return a * 2 end})
+print(calls(21))
+
+-- __index and __newindex as functions and as tables
+local log = {}
+local proxy = setmetatable({}, {
+	__index = function(t, k) return k .. "!" end,
+	__newindex = function(t, k, v) log[#log + 1] = k; rawset(t, k, v * 10) end
+})
+proxy.a = 1
+proxy.a = 2
+print(proxy.a, proxy.b, rawget(proxy, "b"), #log)
+local store = {}
+local redirect = setmetatable({}, {__newindex = store, __index = store})
+redirect.x = 5
+print(rawget(redirect, "x"), store.x, redirect.x)
+local chain = setmetatable({}, {__index = setmetatable({}, {__index = {deep = "found"}})})
+print(chain.deep)
+
+-- arithmetic, concatenation and comparison metamethods
+local N = {}
+N.__index = N
+local function n(v) return setmetatable({v = v}, N) end
+local function val(x) return type(x) == "table" and x.v or x end
+N.__sub = function(a, b) return n(val(a) - val(b)) end
+N.__mul = function(a, b) return n(val(a) * val(b)) end
+N.__div = function(a, b) return n(val(a) / val(b)) end
+N.__mod = function(a, b) return n(val(a) % val(b)) end
+N.__pow = function(a, b) return n(val(a) ^ val(b)) end
+N.__unm = function(a) return n(-a.v) end
+N.__concat = function(a, b) return "<" .. tostring(val(a)) .. "|" .. tostring(val(b)) .. ">" end
+N.__le = function(a, b) return a.v <= b.v end
+N.__lt = function(a, b) return a.v < b.v end
+print((n(7) - 2).v, (3 * n(4)).v, (n(9) / n(3)).v, (n(7) % 3).v, (n(2) ^ 10).v, (-n(5)).v)
+print(n(1) .. "x", "y" .. n(2), 3 .. n(4), n(1) .. n(2) .. "z")
+print(n(1) <= n(1), n(2) <= n(1), n(1) > n(0), n(2) >= n(3))
+
+-- __eq is only used between two tables sharing the metamethod
+local eq = function() return true end
+local a, b = setmetatable({}, {__eq = eq}), setmetatable({}, {__eq = eq})
+print(a == b, a ~= b, a == {}, rawequal(a, b))
+
+-- # ignores __len on tables, __call receives every argument
+print(#setmetatable({1, 2, 3}, {__len = function() return 0 end}))
+local callable = setmetatable({}, {__call = function(self, ...) return select("#", ...), ... end})
+print(callable(1, nil, 3))
+
+-- errors raised by metamethods and by missing ones
+print(pcall(setmetatable, protected, {}))
+print(pcall(function() return n(1) < 2 end))
+print(pcall(function() return {} + 1 end))
+print(pcall(function() return setmetatable({}, {__index = function() error("no field") end}).x end))
@@ -0,0 +1,36 @@
+local Account = {}
+Account.__index = Account
+
+function Account.new(balance)
+	return setmetatable({balance = balance}, Account)
+end
+
+function Account:deposit(v)
+	self.balance = self.balance + v
+	return self
+end
+
+function Account:get()
+	return self.balance
+end
+
+local acc = Account.new(100)
+acc:deposit(50):deposit(25)
+print(acc:get())
+
+-- the receiver is evaluated once and passed as the first argument
+local calls = 0
+local function get() calls = calls + 1; return acc end
+print(get():get(), calls)
+
+-- methods found through __index functions, on strings and with a key held in a register
+local dynamic = setmetatable({}, {__index = function(t, k) return function(self, x) return k, x, self == t end end})
+print(dynamic:anything(7))
+print(("abc"):upper(), ("%d-%d"):format(1, 2))
+local name = "get"
+print(acc[name](acc))
+
+-- the error names the method or the receiver that can't be indexed
+print(pcall(function() return acc:missing() end))
+print(pcall(function() local n = 5; return n:foo() end))
+print(pcall(function() return (nil):foo() end))
@@ -0,0 +1,53 @@
+-- each call nesting an evaluation on the stack of the host counts toward the C stack limit instead of crashing the interpreter
+local function report(ok, err)
+	print(ok, (string.gsub(tostring(err), "[^%s:]+:%d+: ", "")))
+end
+
+local function through_pcall() pcall(through_pcall) end
+through_pcall()
+print("pcall done")
+
+local depth = 0
+local function through_pcall_error() depth = depth + 1; local ok, err = pcall(through_pcall_error); if not ok then error(err, 0) end end
+report(pcall(through_pcall_error))
+print(depth > 150 and depth < 250)
+
+local function through_xpcall()
+	local ok, err = xpcall(through_xpcall, function(msg) return msg end)
+	if not ok then error(err, 0) end
+end
+report(pcall(through_xpcall))
+
+local index = setmetatable({}, {})
+getmetatable(index).__index = function(t, k) return t[k] end
+report(pcall(function() return index.x end))
+
+local newindex = setmetatable({}, {})
+getmetatable(newindex).__newindex = function(t, k, v) t[k] = v end
+report(pcall(function() newindex.x = 1 end))
+
+local callable = setmetatable({}, {})
+getmetatable(callable).__call = function(self) local res = self(); return res end
+report(pcall(callable))
+
+local looped = setmetatable({}, {})
+getmetatable(looped).__call = looped
+report(pcall(looped))
+
+local function compare(a, b) table.sort({3, 2, 1}, compare); return a < b end
+report(pcall(table.sort, {3, 2, 1}, compare))
+
+local function replace(s) return (string.gsub("x", "x", replace)) end
+report(pcall(replace, "x"))
+
+local function iterate() for _ in iterate do end end
+report(pcall(iterate))
+
+local function through_wrap() coroutine.wrap(through_wrap)() end
+report(pcall(through_wrap))
+
+local function through_resume() local ok, err = coroutine.resume(coroutine.create(through_resume)); if not ok then error(err, 0) end end
+report(pcall(through_resume))
+
+-- the interpreter is usable after the overflow
+print(pcall(string.rep, "ok", 2))
@@ -0,0 +1,20 @@
+print(os.date("%Y-%m-%d %H:%M:%S %a %b %j|%c|%x|%X|%p", 86400 * 365))
+print(os.date("!%A %B %d %I %y %%", 1700000000))
+
+local date = os.date("*t", 1700000000)
+print(date.year, date.month, date.day, date.hour, date.min, date.sec, date.wday, date.yday, date.isdst)
+print(os.time(date), os.time({ year = 2000, month = 13, day = 1, hour = 0 }))
+print(pcall(os.time, { year = 2000 }))
+
+print(type(os.time()), type(os.clock()), os.getenv("NO_SUCH_VARIABLE"))
+print(os.rename("/nonexistent/a", "/nonexistent/b"))
+
+-- dates far from the epoch and invalid fields
+print(os.date("!%Y-%m-%d %H:%M:%S", 0), os.date("!%Y-%m-%d", -86400), os.date("!%Y-%m-%d", 951782400))
+print(os.time({ year = 1970, month = 1, day = 1, hour = 0 }), os.time({ year = 2000, month = 3, day = 0, hour = 12 }))
+print(os.time({ year = 2024, month = 2, day = 29, hour = 12, min = 60, sec = -1 }))
+print(pcall(os.time, { year = "x", month = 1, day = 1 }))
+print(pcall(os.date, {}))
+print(os.date("!", 0), os.date("!%", 0) ~= nil)
+print(type(os.clock()), os.clock() >= 0, os.difftime(10, 4), os.difftime(5))
+print(pcall(os.difftime))
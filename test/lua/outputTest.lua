@@ -0,0 +1,6 @@
+-- run by test/output.rs, writes to the outputs redirected by the host
+print("hello", 1, nil)
+io.write("no newline ", 2.5)
+io.write("\n")
+io.stdout:write("through the file\n")
+io.stderr:write("to the error output\n")
@@ -0,0 +1,52 @@
+local function fail()
+    error("boom")
+end
+
+function blame_caller()
+    error("bad call", 2)
+end
+
+local function caller()
+    blame_caller()
+end
+
+print(pcall(fail))
+print(pcall(caller))
+print(xpcall(fail, function(msg) return "handled: " .. msg end))
+print(pcall(error, "no position", 0))
+print(pcall(assert, false))
+print(pcall(assert, 1, 2))
+print(pcall(nil))
+
+local ok, err = pcall(error, { code = 42 })
+print(ok, err.code)
+
+-- a faulty plugin does not stop the others
+local plugins = { function() return "first" end, function() local t = nil; return t.x end, function() return "third" end }
+for i, plugin in ipairs(plugins) do
+    print(i, pcall(plugin))
+end
+
+-- error values of any type, levels and messages of assert
+print(pcall(error))
+print(pcall(error, nil))
+print(pcall(error, 42))
+print(pcall(error, "level 1", 1))
+print(pcall(function() error("inside", 1) end))
+print(pcall(function() error("in the pcall", 3) end))
+print(pcall(assert, nil, "custom message"))
+print(pcall(assert, false, { "table message" }))
+print(select("#", pcall(assert, 1, 2, 3)))
+print(pcall(assert))
+
+-- the handler of xpcall runs before the stack is unwound and its errors are not handled again
+print(xpcall(function() error({}) end, function(e) return type(e) end))
+print(xpcall(function() error("x") end, function(e) error("again") end))
+print(xpcall(function() return "no error", 2 end, print))
+print(pcall(xpcall, function() end))
+
+-- nested protected calls only catch the errors raised inside them
+print(pcall(function()
+    local ok, err = pcall(error, "inner")
+    error("outer after " .. err, 0)
+end))
@@ -0,0 +1,18 @@
+-- none of these lines may crash the interpreter, each one raises a lua error
+print(not nil)
+print(1 / 0, -1 % 3)
+for i = 1, "10", 4 do
+	print(i)
+end
+print(undefined_global)
+print(pcall(function() return nil + 1 end))
+print(pcall(function() return 1 < "2" end))
+print(pcall(function() return {} <= {} end))
+print(pcall(function() return -{} end))
+print(pcall(function() for i = "x", 2 do end end))
+print(pcall(function() for i = 1, 2, {} do end end))
+print(pcall(function() local t = nil; t.x = 1 end))
+print(pcall(function() local t = {}; t[nil] = 1 end))
+print(pcall(function() local t = {}; t[0 / 0] = 1 end))
+print(pcall(function() undefined_global() end))
+print(#5)
@@ -0,0 +1,6 @@
+-- run in a sandbox by test/sandbox.rs, the runtime functions run as the body of a coroutine stay in the sandbox
+local env = coroutine.wrap(getfenv)(0)
+local nested = coroutine.wrap(coroutine.wrap)(getfenv)(0)
+local _, resumed = coroutine.resume(coroutine.create(getfenv), 0)
+local replaced = pcall(coroutine.wrap(setfenv), 0, {})
+return env == getfenv(), env.secret, nested == getfenv(), resumed == getfenv(), replaced
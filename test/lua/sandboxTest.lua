@@ -0,0 +1,10 @@
+-- run by test/sandbox.rs with the standard profile, config is a read-only view of a table of the host
+
+-- every write to the view is refused, the raw ones too
+local assigned = pcall(function() config.name = "changed" end)
+local raw = pcall(rawset, config, "name", "changed")
+local nested = pcall(rawset, config.list, 1, "changed")
+local inserted = pcall(table.insert, config.list, "changed")
+local protected = pcall(setmetatable, config, nil)
+
+return assigned, raw, nested, inserted, protected, config.name, config.list[1], rawget(config, "name"), load, string.dump
@@ -0,0 +1,38 @@
+local s = "Hello World"
+print(s:len(), s:upper(), s:lower(), s:sub(2, -2), s:sub(-5))
+print(string.rep("ab", 3), string.reverse("abc"), string.byte("ABC", 1, -1), string.char(72, 105))
+
+print(string.find("hello world", "o w"), string.find("a.b", ".", 1, true))
+print(string.match("key = value", "(%w+)%s*=%s*(%w+)"))
+print(string.match("  trim  ", "^%s*(.-)%s*$"), string.match("hello", "()ll()"))
+print(string.match("f(a(b)c)d", "%b()"), string.match("THE quick", "%f[%a]%a+", 5))
+
+for k, v in string.gmatch("a=1, b=2", "(%w+)=(%w+)") do
+    print(k, v)
+end
+
+print(string.gsub("hello world", "(%w+)", "<%1>"))
+print(string.gsub("hello world", "%w+", string.upper))
+print(string.gsub("$name is $age", "%$(%w+)", { name = "lua", age = 18 }))
+print(pcall(string.find, "abc", "[a"))
+
+-- single characters are matched in a loop, only the items that can backtrack nest
+local long = string.rep("a", 10000)
+print(#string.match(long, long), string.find(long .. "b", string.rep("a", 500) .. "b"))
+print(#string.match(long, string.rep("a?", 150)), #string.match(long, string.rep("(a)", 30) .. ".*"))
+print(pcall(string.match, long, string.rep("a?", 300)))
+
+-- malformed patterns
+print(pcall(string.find, "abc", "%"))
+print(pcall(string.find, "abc", "%f"))
+print(pcall(string.find, "abc", "%b"))
+print(pcall(string.find, "abc", "(()"))
+print(pcall(string.find, "abc", "a)"))
+print(pcall(string.find, "abc", "%1"))
+print(pcall(string.rep))
+
+-- positions are clamped to the subject
+print(string.sub("abc", 0), string.sub("abc", -10, 2), string.sub("abc", 3, 2), string.sub("abc", 2, 100))
+print(string.byte("abc", -10, 10))
+print(string.find("abc", "", 10), string.find("abc", "", 4), string.find("abc", "b", -1))
+print(string.rep("x", 0), string.rep("x", -1), pcall(string.char, 256))
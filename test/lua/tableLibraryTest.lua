@@ -0,0 +1,48 @@
+local t = { 5, 2, 8, 1, 9, 3 }
+table.insert(t, 4)
+table.insert(t, 1, 0)
+print(table.concat(t, ","), table.getn(t))
+
+table.insert(t, 100, 7)
+print(table.maxn(t), #t)
+
+print(table.remove(t), table.remove(t, 1), table.remove(t, 50))
+print(table.concat(t, ", ", 2, 4), table.concat({}), table.concat({ 1, "x", 2.5 }))
+
+table.sort(t)
+print(table.concat(t, " "))
+table.sort(t, function(a, b) return a > b end)
+print(table.concat(t, " "))
+
+local fruits = { "pear", "apple", "fig", "banana" }
+table.sort(fruits)
+print(table.concat(fruits, "-"))
+
+local records = { { name = "b", age = 30 }, { name = "a", age = 25 }, { name = "c", age = 35 } }
+table.sort(records, function(r1, r2) return r1.age < r2.age end)
+for _, r in ipairs(records) do
+    print(r.name, r.age)
+end
+
+local many = {}
+for i = 1, 15 do
+    many[i] = i
+end
+print(pcall(table.sort, many, function() return true end))
+print(pcall(table.sort, { 3, {}, 1 }))
+print(pcall(table.sort, t, 1))
+print(pcall(table.insert, t, 1, 2, 3))
+print(pcall(table.concat, { 1, {}, 3 }))
+print(pcall(table.setn, t, 3))
+
+-- positions far from the elements
+local sparse = { 1, 2 }
+table.insert(sparse, 2^53, "far")
+print(#sparse, sparse[2^53], table.maxn(sparse))
+print(pcall(table.insert, sparse, 1e300, "x"))
+print(pcall(table.insert, sparse, -1e300, "x"))
+print(pcall(table.insert, sparse, 0, "x"))
+print(pcall(table.insert, sparse, "x", "y"))
+print(table.remove(sparse, -1e300), table.remove(sparse, 1e300), table.remove({}), #sparse)
+print(table.concat({ 1, 2, 3 }, ",", 3, 1), pcall(table.concat, {}, ",", 1, 1))
+print(pcall(table.insert), pcall(table.remove), pcall(table.concat, nil))
@@ -0,0 +1,36 @@
+function fail()
+    local t = nil
+    return t.x + 1
+end
+
+local function outer()
+    fail()
+end
+
+local function show()
+    print(debug.traceback("here"))
+end
+
+show()
+print(pcall(outer))
+print(xpcall(outer, debug.traceback))
+
+-- runtime functions appear as [C], fields and methods are named after the key
+local M = {}
+function M.field() print(debug.traceback("field", 1)) end
+function M:method() M.field() end
+table.sort({1, 2}, function(a, b) M:method(); return a < b end)
+
+-- tail calls leave no frame, the level skips the innermost functions
+local function tail() return show() end
+tail()
+local function level() print(debug.traceback("from main", 2)) end
+level()
+
+-- messages that are not strings are returned unchanged
+print(debug.traceback({}) ~= nil, type(debug.traceback({})), debug.traceback(nil))
+
+-- long tracebacks keep the innermost and outermost functions
+local function deep(n) if n == 0 then print(debug.traceback()) else deep(n - 1) end end
+deep(30)
+outer()
@@ -0,0 +1,34 @@
+-- functions run by test/userdata.rs on the userdata made by the host function make
+
+-- the userdata are released in the reverse order of their creation
+function release_in_reverse(first, second, third)
+    local a, b, c = make(first), make(second), make(third)
+    c = nil
+    b = nil
+    a = nil
+    collectgarbage()
+    return "collected"
+end
+
+-- the userdata are released together with the table holding them
+function release_all(...)
+    local list = {}
+    for i, name in ipairs({...}) do
+        list[i] = make(name)
+    end
+    list = nil
+    collectgarbage()
+    return "collected"
+end
+
+-- the userdata stays in a global variable until the state is dropped
+function keep(name)
+    kept = make(name)
+end
+
+-- the kilobytes in use before and after building a string of n bytes
+function count(n)
+    local before = collectgarbage("count")
+    local s = string.rep("x", n)
+    return before, collectgarbage("count")
+end
@@ -0,0 +1,47 @@
+local function log(level, ...)
+	print(level, ...)
+	print(select('#', ...), select(2, ...))
+	local first, second = ...
+	print(first, second)
+end
+
+log("info", "starting", 1, 2)
+log("debug")
+
+local function count(...)
+	return select('#', ...)
+end
+print(count(nil, nil, nil))
+print(select(-1, "a", "b", "c"))
+
+-- lua 5.0 style arg table
+function old_style(...)
+	print(arg.n, arg[1], arg[2])
+end
+old_style("x", "y")
+
+-- ... expands to every value at the end of a list and to its first value elsewhere
+local function pass(...) return ... end
+local function pack(...) return {...}, select('#', ...) end
+print(pass(1, nil, 3))
+print(pass(1, 2), pass(3, 4))
+print((pass(5, 6)))
+local t, n = pack(nil, 2, nil)
+print(n, t[2], t[3])
+print(#{pass(1, 2, 3)}, #{pass(1, 2, 3), 4})
+print(pass())
+
+-- missing parameters are nil and extra arguments of a fixed function are dropped
+local function fixed(a, b) return a, b end
+print(fixed(1), fixed(1, 2, 3))
+
+-- many values go through a call
+local many = {}
+for i = 1, 300 do many[i] = i end
+print(count(unpack(many)), select(300, unpack(many)))
+
+print(select(2, "a", "b", "c"))
+print(select(4, "a", "b", "c"))
+print(pcall(select, 0, "a"))
+print(pcall(select, -4, "a", "b", "c"))
+print(pcall(select, "x"))
@@ -0,0 +1,23 @@
+local cfg = nil
+
+print(pcall(function() return cfg.x end))
+print(pcall(function() foo() end))
+print(pcall(function() local t = {}; return t.a.b end))
+print(pcall(function() local s = "x"; return s + 1 end))
+print(pcall(function() local t = {}; t:m() end))
+print(pcall(function() return #nothing end))
+print(pcall(function() return "a" .. missing .. "b" end))
+
+-- values that are not held by a variable are not named
+print(pcall(function() local function f() end; f()() end))
+print(pcall(function() return ({}).x.y end))
+print(pcall(function() return 1 + {} end))
+print(pcall(function() for _ in 5 do end end))
+
+-- the name follows the variable through moves and the last assignment
+print(pcall(function() local a = nil; local b = a; b() end))
+print(pcall(function() local t = { inner = {} }; return t.inner.deep.x end))
+print(pcall((function() local up = cfg; return function() return up.field end end)()))
+print(pcall(function() local n = 1; n = nil; return -n end))
+print(pcall(function() local k = "key"; local t = {}; t[k]() end))
+print(pcall(function() local s = setmetatable({}, {__index = function() return nil end}); s.method() end))
@@ -0,0 +1,28 @@
+/* runs test/lua/outputTest.lua on a state whose outputs are redirected and checks the captured text
+ * print, io.write and io.stdout go to the output, io.stderr to the error output
+ */
+use luaca::interpreter::output::OutputBuffer;
+use luaca::lua::Lua;
+
+use std::fs;
+
+#[test]
+fn outputs_are_captured() {
+    let mut lua = Lua::new();
+    let output = OutputBuffer::new();
+    let error_output = OutputBuffer::new();
+    lua.set_output(output.clone());
+    lua.set_error_output(error_output.clone());
+    let bytecode = fs::read("test/out/outputTest.out").expect("compiled script");
+    lua.exec(&bytecode).expect("script run");
+    // print follows each value with a tab
+    assert_eq!(String::from_utf8_lossy(&output.take()), "hello\t1\tnil\t\nno newline 2.5\nthrough the file\n");
+    assert_eq!(String::from_utf8_lossy(&error_output.take()), "to the error output\n");
+
+    // a new writer receives the text written afterwards
+    let redirected = OutputBuffer::new();
+    lua.set_output(redirected.clone());
+    lua.exec(&bytecode).expect("script run");
+    assert!(output.contents().is_empty());
+    assert_eq!(String::from_utf8_lossy(&redirected.contents()), "hello\t1\tnil\t\nno newline 2.5\nthrough the file\n");
+}
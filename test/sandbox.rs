@@ -0,0 +1,44 @@
+/* runs test/lua/sandboxTest.lua in a sandbox and checks that it can't modify the read-only views of the host tables
+ * nor get the functions loading code, and runs test/lua/sandboxCoroutineTest.lua to check that coroutines don't leave the sandbox
+ */
+use luaca::interpreter::object::Value;
+use luaca::interpreter::sandbox::SandboxProfile;
+use luaca::interpreter::table::Table;
+use luaca::lua::Lua;
+
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+#[test]
+fn sandbox_cannot_modify_read_only_views() {
+    let mut list = Table::new();
+    list.set(Value::Number(1.), Value::from_string("first")).expect("integer key");
+    let mut config = Table::new();
+    config.set(Value::from_string("name"), Value::from_string("host")).expect("string key");
+    config.set(Value::from_string("list"), Value::Table(Rc::new(RefCell::new(list)))).expect("string key");
+    let config = Rc::new(RefCell::new(config));
+
+    let mut lua = Lua::new();
+    let profile = SandboxProfile::standard().allow("load").allow("string.dump").read_only("config", Rc::clone(&config));
+    let bytecode = fs::read("test/out/sandboxTest.out").expect("compiled script");
+    let res = lua.exec_sandboxed(&bytecode, &profile).expect("script run");
+
+    assert_eq!(res, vec![
+        Value::Boolean(false), Value::Boolean(false), Value::Boolean(false), Value::Boolean(false), Value::Boolean(false),
+        Value::from_string("host"), Value::from_string("first"), Value::Nil, Value::Nil, Value::Nil
+    ]);
+    assert_eq!(config.borrow().get_str("name"), Value::from_string("host"));
+}
+
+#[test]
+fn coroutines_stay_in_the_sandbox() {
+    let mut lua = Lua::new();
+    lua.set_global("secret", Value::from_string("host"));
+    let bytecode = fs::read("test/out/sandboxCoroutineTest.out").expect("compiled script");
+    let res = lua.exec_sandboxed(&bytecode, &SandboxProfile::standard()).expect("script run");
+
+    assert_eq!(res, vec![Value::Boolean(true), Value::Nil, Value::Boolean(true), Value::Boolean(true), Value::Boolean(false)]);
+    assert_eq!(lua.get_global("secret"), Value::from_string("host"));
+    assert!(!lua.get_global("print").is_nil());
+}
@@ -0,0 +1,77 @@
+/* runs the test scripts and compares what they write with the expected output
+ * the scripts of test/lua are compiled by luac 5.1 from the root of the repository, `luac -o test/out/name.out test/lua/name.lua`,
+ * the expected output of a script is in test/expected/name.txt, running the tests with LUACA_BLESS=1 rewrites it
+ */
+use luaca::decompile::decompile::decompile_bytes;
+use luaca::interpreter::interpreter::eval_program;
+use luaca::interpreter::limits::Limits;
+use luaca::interpreter::output::{Output, OutputBuffer};
+use luaca::interpreter::sandbox::Capabilities;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::thread;
+
+// unoptimized builds need more than the stack of the test threads to reach the limit of nested calls
+const STACK_SIZE : usize = 32 * 1024 * 1024;
+
+// output of the script followed by its error, reported as the command line does
+fn run(name : &str) -> String {
+    let bytecode = fs::read(format!("test/out/{}.out", name)).expect("compiled script");
+    let main = decompile_bytes(bytecode).expect("valid chunk");
+
+    let buffer = OutputBuffer::new();
+    let mut output = Output::new(buffer.clone());
+    if let Err(err) = eval_program(main, 0, Capabilities::all(), Limits::default(), output.clone(), output.clone()) {
+        let _ = writeln!(output, "Problem interpreting bytecode: {err}");
+    }
+    String::from_utf8_lossy(&buffer.contents()).into_owned()
+}
+
+fn check(name : &'static str) {
+    let res = thread::Builder::new().stack_size(STACK_SIZE).spawn(move || run(name)).expect("thread of the script").join().expect("script run");
+    let path = format!("test/expected/{}.txt", name);
+    if env::var_os("LUACA_BLESS").is_some() {
+        fs::write(&path, &res).expect("expected output");
+        return;
+    }
+    let expected = fs::read_to_string(&path).expect("expected output");
+    assert!(res == expected, "output of {} differs from {}\n--- expected\n{}--- got\n{}", name, path, expected, res);
+}
+
+macro_rules! scripts {
+    ($($test:ident => $name:literal),* $(,)?) => {
+        $(
+            #[test]
+            fn $test() {
+                check($name);
+            }
+        )*
+    };
+}
+
+scripts! {
+    constructor => "constructorTest",
+    table => "tableTest",
+    tail_recursion => "tailRecursionOpti",
+    metatable => "metatableTest",
+    concat => "concatTest",
+    vararg => "varargTest",
+    method => "methodTest",
+    generic_for => "genericForTest",
+    large_constructor => "largeConstructorTest",
+    runtime_error => "runtimeErrorTest",
+    nested_call => "nestedCallTest",
+    pcall => "pcallTest",
+    traceback => "tracebackTest",
+    variable_name => "variableNameTest",
+    base_library => "baseLibraryTest",
+    string => "stringTest",
+    format => "formatTest",
+    table_library => "tableLibraryTest",
+    math => "mathTest",
+    io => "ioTest",
+    os => "osTest",
+    coroutine => "coroutineTest",
+}
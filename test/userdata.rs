@@ -0,0 +1,107 @@
+/* checks the userdata created by the host: the type of their value, the order of their finalizers and the errors they raise
+ * the functions of test/lua/userdataTest.lua create and release them, the program counts its memory with CountingAllocator
+ */
+use luaca::interpreter::limits::CountingAllocator;
+use luaca::interpreter::object::Value;
+use luaca::interpreter::table::Table;
+use luaca::lua::{Lua, LuaError};
+
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+#[global_allocator]
+static ALLOCATOR : CountingAllocator = CountingAllocator;
+
+struct Named(String);
+
+struct Point {
+    x : f64,
+    y : f64
+}
+
+/// state whose userdata Named log their name when they are finalized, the finalizer of "bad" raises an error
+fn load(log : &Rc<RefCell<Vec<String>>>) -> Lua {
+    let mut lua = Lua::new();
+    let log = Rc::clone(log);
+    let gc = lua.create_function(move |_, args| {
+        let Some(Value::UserData(userdata)) = args.first() else { panic!("__gc called without its userdata") };
+        let name = userdata.borrow::<Named>().expect("value of a Named").0.clone();
+        log.borrow_mut().push(name.clone());
+        if name == "bad" { Err(LuaError::runtime("finalizer failure")) } else { Ok(Vec::new()) }
+    });
+    let mut metatable = Table::new();
+    metatable.set(Value::from_string("__gc"), gc).expect("string key");
+    lua.set_userdata_metatable::<Named>(Rc::new(RefCell::new(metatable)));
+    lua.register("make", |context, args| Ok(vec![context.create_userdata(Named(args[0].to_string()))]));
+
+    let bytecode = fs::read("test/out/userdataTest.out").expect("compiled script");
+    lua.exec(&bytecode).expect("functions of the script");
+    lua
+}
+
+fn names(names : &[&str]) -> Vec<Value> {
+    names.iter().map(|name| Value::from_string(name)).collect()
+}
+
+#[test]
+fn values_are_downcast_to_their_type() {
+    let mut lua = Lua::new();
+    let Value::UserData(userdata) = lua.create_userdata(Point { x: 1., y: 2. }) else { panic!("userdata expected") };
+    assert!(userdata.is::<Point>());
+    assert!(!userdata.is::<Named>());
+    assert!(userdata.borrow::<Named>().is_none());
+    assert!(userdata.borrow_mut::<String>().is_none());
+
+    let point = userdata.borrow::<Point>().expect("value of a Point");
+    assert_eq!((point.x, point.y), (1., 2.));
+    // a value being read can't be modified
+    assert!(userdata.borrow_mut::<Point>().is_none());
+    drop(point);
+
+    userdata.borrow_mut::<Point>().expect("value of a Point").x = 3.;
+    let point = userdata.borrow_mut::<Point>().expect("value of a Point");
+    assert_eq!(point.x, 3.);
+    // nor read while it is modified
+    assert!(userdata.borrow::<Point>().is_none());
+}
+
+#[test]
+fn finalizers_run_in_the_order_of_release() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut lua = load(&log);
+    let release_in_reverse = lua.get_global("release_in_reverse");
+    lua.call(release_in_reverse, names(&["a", "b", "c"])).expect("collection");
+    assert_eq!(*log.borrow(), ["c", "b", "a"]);
+
+    // the userdata still referenced by a global variable is finalized with the state
+    let keep = lua.get_global("keep");
+    lua.call(keep, names(&["kept"])).expect("global userdata");
+    assert_eq!(log.borrow().len(), 3);
+    drop(lua);
+    assert_eq!(*log.borrow(), ["c", "b", "a", "kept"]);
+}
+
+#[test]
+fn finalizer_errors_are_ignored() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut lua = load(&log);
+    let release_all = lua.get_global("release_all");
+    let res = lua.call(release_all, names(&["first", "bad", "last"])).expect("errors of finalizers are not raised");
+    assert_eq!(res, names(&["collected"]));
+    let mut finalized = log.borrow().clone();
+    finalized.sort();
+    assert_eq!(finalized, ["bad", "first", "last"]);
+}
+
+#[test]
+fn count_measures_the_allocated_memory() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut lua = load(&log);
+    let count = lua.get_global("count");
+    let res = lua.call(count, vec![Value::Number(1e6)]).expect("memory count");
+    let [Value::Number(before), Value::Number(after)] = res[..] else { panic!("two counts expected, got {:?}", res) };
+    assert!(before > 0.);
+    // the string of a million bytes takes about 977 kilobytes
+    assert!(after - before >= 976., "{} then {} kilobytes", before, after);
+}